@@ -0,0 +1,327 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+
+/// 地址簿最多保存的地址数
+pub const MAX_ADDRESSES: usize = 2000;
+/// 单条 Addr 消息最多包含的地址数
+pub const MAX_ADDR_ITEMS: usize = 1000;
+/// 从未连接成功的地址连续失败超过该次数后移除（种子地址除外）
+const MAX_FAILURES: u32 = 5;
+/// 连接失败后的基础重试间隔（秒），每多失败一次加倍
+const RETRY_BASE_SECS: i64 = 30;
+/// 重试间隔上限（秒）
+const MAX_RETRY_SECS: i64 = 60 * 60;
+
+/// 已知的节点地址及其连接质量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnownAddress {
+    pub address: SocketAddr,
+    pub last_seen: DateTime<Utc>,            // 最近一次得知或连接到该地址的时间
+    pub last_success: Option<DateTime<Utc>>, // 最近一次连接成功的时间
+    pub last_failure: Option<DateTime<Utc>>, // 最近一次连接失败的时间
+    pub failures: u32,                       // 上次成功以来的连续失败次数
+    pub seed: bool,                          // 是否来自配置的种子地址
+    #[serde(default)]
+    pub banned_until: Option<DateTime<Utc>>, // 封禁到期时间（封禁该地址的 IP）
+}
+
+impl KnownAddress {
+    fn new(address: SocketAddr) -> Self {
+        Self {
+            address,
+            last_seen: Utc::now(),
+            last_success: None,
+            last_failure: None,
+            failures: 0,
+            seed: false,
+            banned_until: None,
+        }
+    }
+
+    /// 是否处于封禁期
+    pub fn is_banned(&self, now: DateTime<Utc>) -> bool {
+        self.banned_until.is_some_and(|banned_until| banned_until > now)
+    }
+
+    /// 是否可以尝试连接：连续失败后按指数退避等待
+    pub fn ready_to_dial(&self, now: DateTime<Utc>) -> bool {
+        match self.last_failure {
+            Some(last_failure) if self.failures > 0 => {
+                let delay = RETRY_BASE_SECS.saturating_mul(1 << self.failures.min(16)).min(MAX_RETRY_SECS);
+                now - last_failure >= Duration::seconds(delay)
+            }
+            _ => true,
+        }
+    }
+
+    /// 是否值得分享给其他节点：最近没有失败，或曾经连接成功
+    fn is_good(&self) -> bool {
+        self.failures == 0 || self.last_success.is_some()
+    }
+}
+
+/// 地址簿 - 记录通过种子、握手和 Addr 消息得知的节点地址，为自动连接挑选候选地址
+#[derive(Debug, Clone, Default)]
+pub struct AddressBook {
+    addresses: HashMap<SocketAddr, KnownAddress>,
+}
+
+impl AddressBook {
+    /// 创建空的地址簿
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 保存地址簿到文件
+    pub fn save_to_file(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(&self.entries())?;
+        fs::write(filename, json)?;
+        Ok(())
+    }
+
+    /// 从文件加载地址簿
+    pub fn load_from_file(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = fs::read_to_string(filename)?;
+        let entries: Vec<KnownAddress> = serde_json::from_str(&json)?;
+        let addresses = entries.into_iter().map(|known| (known.address, known)).collect();
+        Ok(Self { addresses })
+    }
+
+    /// 记录地址，返回是否为新地址；地址簿已满时淘汰最差的非种子、未封禁地址
+    pub fn add(&mut self, address: SocketAddr) -> bool {
+        if address.ip().is_unspecified() || address.port() == 0 {
+            return false;
+        }
+        if let Some(known) = self.addresses.get_mut(&address) {
+            known.last_seen = Utc::now();
+            return false;
+        }
+
+        if self.addresses.len() >= MAX_ADDRESSES {
+            let now = Utc::now();
+            let worst = self.addresses
+                .values()
+                .filter(|known| !known.seed && !known.is_banned(now))
+                .min_by_key(|known| (known.last_success.is_some(), std::cmp::Reverse(known.failures), known.last_seen))
+                .map(|known| known.address);
+            match worst {
+                Some(worst) => self.addresses.remove(&worst),
+                None => return false,
+            };
+        }
+        self.addresses.insert(address, KnownAddress::new(address));
+        true
+    }
+
+    /// 记录配置的种子地址（不会因连接失败被移除）
+    pub fn add_seed(&mut self, address: SocketAddr) {
+        self.add(address);
+        if let Some(known) = self.addresses.get_mut(&address) {
+            known.seed = true;
+        }
+    }
+
+    /// 记录一次成功连接
+    pub fn mark_success(&mut self, address: SocketAddr) {
+        self.add(address);
+        if let Some(known) = self.addresses.get_mut(&address) {
+            let now = Utc::now();
+            known.last_seen = now;
+            known.last_success = Some(now);
+            known.failures = 0;
+        }
+    }
+
+    /// 记录一次连接失败，从未成功过的地址失败次数过多时移除（封禁中的地址保留封禁记录）
+    pub fn mark_failure(&mut self, address: SocketAddr) {
+        let Some(known) = self.addresses.get_mut(&address) else {
+            return;
+        };
+        let now = Utc::now();
+        known.failures += 1;
+        known.last_failure = Some(now);
+        if known.failures > MAX_FAILURES && known.last_success.is_none() && !known.seed && !known.is_banned(now) {
+            self.addresses.remove(&address);
+        }
+    }
+
+    /// 更新已连接地址的最近活跃时间
+    pub fn mark_seen(&mut self, address: SocketAddr) {
+        if let Some(known) = self.addresses.get_mut(&address) {
+            known.last_seen = Utc::now();
+        }
+    }
+
+    /// 封禁地址的 IP 直到 until
+    pub fn ban(&mut self, address: SocketAddr, until: DateTime<Utc>) {
+        self.add(address);
+        if let Some(known) = self.addresses.get_mut(&address) {
+            known.banned_until = Some(until);
+        }
+    }
+
+    /// 解除 IP 的封禁，返回该 IP 此前是否处于封禁期
+    pub fn unban(&mut self, ip: IpAddr) -> bool {
+        let now = Utc::now();
+        let mut unbanned = false;
+        for known in self.addresses.values_mut().filter(|known| known.address.ip() == ip) {
+            unbanned |= known.banned_until.take().is_some_and(|banned_until| banned_until > now);
+        }
+        unbanned
+    }
+
+    /// 处于封禁期的 IP
+    fn banned_ips(&self, now: DateTime<Utc>) -> HashSet<IpAddr> {
+        self.addresses
+            .values()
+            .filter(|known| known.is_banned(now))
+            .map(|known| known.address.ip())
+            .collect()
+    }
+
+    /// IP 是否处于封禁期
+    pub fn is_banned(&self, ip: IpAddr) -> bool {
+        let now = Utc::now();
+        self.addresses.values().any(|known| known.address.ip() == ip && known.is_banned(now))
+    }
+
+    /// 处于封禁期的地址，按到期时间排序
+    pub fn banned(&self) -> Vec<KnownAddress> {
+        let now = Utc::now();
+        let mut banned: Vec<KnownAddress> = self.addresses.values().filter(|known| known.is_banned(now)).cloned().collect();
+        banned.sort_by_key(|known| known.banned_until);
+        banned
+    }
+
+    /// 挑选最多 count 个可以连接的地址（跳过 exclude 返回 true 的地址和被封禁的 IP），失败次数少、曾连接成功的优先
+    pub fn dial_candidates(&self, count: usize, exclude: impl Fn(&SocketAddr) -> bool) -> Vec<SocketAddr> {
+        let now = Utc::now();
+        let banned_ips = self.banned_ips(now);
+        let mut candidates: Vec<&KnownAddress> = self.addresses
+            .values()
+            .filter(|known| !exclude(&known.address) && !banned_ips.contains(&known.address.ip()) && known.ready_to_dial(now))
+            .collect();
+        candidates.shuffle(&mut rand::thread_rng());
+        candidates.sort_by_key(|known| (known.failures, known.last_success.is_none()));
+        candidates.into_iter().take(count).map(|known| known.address).collect()
+    }
+
+    /// 随机挑选最多 count 个质量较好且未被封禁的地址，用于响应 GetAddr
+    pub fn sample(&self, count: usize) -> Vec<SocketAddr> {
+        let banned_ips = self.banned_ips(Utc::now());
+        let mut addresses: Vec<SocketAddr> = self.addresses
+            .values()
+            .filter(|known| known.is_good() && !banned_ips.contains(&known.address.ip()))
+            .map(|known| known.address)
+            .collect();
+        addresses.shuffle(&mut rand::thread_rng());
+        addresses.truncate(count);
+        addresses
+    }
+
+    /// 全部已知地址，最近连接成功的在前
+    pub fn entries(&self) -> Vec<KnownAddress> {
+        let mut entries: Vec<KnownAddress> = self.addresses.values().cloned().collect();
+        entries.sort_by(|a, b| b.last_success.cmp(&a.last_success).then(b.last_seen.cmp(&a.last_seen)));
+        entries
+    }
+
+    /// 已知地址数
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    /// 地址簿是否为空
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(text: &str) -> SocketAddr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn ban_expires() {
+        let mut book = AddressBook::new();
+        let peer = address("10.0.0.1:7878");
+        book.ban(peer, Utc::now() + Duration::hours(1));
+        assert!(book.is_banned(peer.ip()));
+        assert_eq!(book.banned().len(), 1);
+
+        book.ban(peer, Utc::now() - Duration::seconds(1));
+        assert!(!book.is_banned(peer.ip()));
+        assert!(book.banned().is_empty());
+        assert!(!book.unban(peer.ip()));
+    }
+
+    #[test]
+    fn dial_candidates_skip_banned_ips_and_backoff() {
+        let mut book = AddressBook::new();
+        let good = address("10.0.0.1:7878");
+        let banned = address("10.0.0.2:7878");
+        let same_ip = address("10.0.0.2:7879");
+        let failing = address("10.0.0.3:7878");
+        let excluded = address("10.0.0.4:7878");
+        for peer in [good, same_ip, failing, excluded] {
+            book.add(peer);
+        }
+        book.mark_success(good);
+        book.ban(banned, Utc::now() + Duration::hours(1));
+        book.mark_failure(failing);
+
+        // 封禁按 IP 生效，刚失败的地址在退避期内不连接
+        let candidates = book.dial_candidates(10, |peer| *peer == excluded);
+        assert_eq!(candidates, vec![good]);
+
+        assert!(book.unban(banned.ip()));
+        let mut candidates = book.dial_candidates(10, |_| false);
+        candidates.sort();
+        assert_eq!(candidates, vec![good, banned, same_ip, excluded]);
+        assert_eq!(book.dial_candidates(1, |_| false), vec![good]);
+    }
+
+    #[test]
+    fn repeated_failures_remove_only_unproven_addresses() {
+        let mut book = AddressBook::new();
+        let unproven = address("10.0.0.1:7878");
+        let seed = address("10.0.0.2:7878");
+        book.add(unproven);
+        book.add_seed(seed);
+        for _ in 0..=MAX_FAILURES {
+            book.mark_failure(unproven);
+            book.mark_failure(seed);
+        }
+        assert_eq!(book.entries().iter().map(|known| known.address).collect::<Vec<_>>(), vec![seed]);
+    }
+
+    #[test]
+    fn persists_round_trip() {
+        let mut book = AddressBook::new();
+        let peer = address("10.0.0.1:7878");
+        let banned = address("10.0.0.2:7878");
+        book.add_seed(peer);
+        book.mark_success(peer);
+        book.ban(banned, Utc::now() + Duration::hours(1));
+
+        let path = std::env::temp_dir().join(format!("address_book_test_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        book.save_to_file(path).unwrap();
+        let loaded = AddressBook::load_from_file(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.is_banned(banned.ip()));
+        let known = loaded.entries().into_iter().find(|known| known.address == peer).unwrap();
+        assert!(known.seed);
+        assert!(known.last_success.is_some());
+    }
+}
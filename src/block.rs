@@ -2,17 +2,92 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use ring::{rand, signature::{self, KeyPair, Ed25519KeyPair}};
+use crate::consensus::{Evidence, STAKING_ADDRESS};
+use crate::governance::{GOVERNANCE_ADDRESS, ParamChange};
+use crate::monetary::ISSUER_ADDRESS;
+use crate::pow;
+use crate::wallet::ValidatorKey;
 
 /// 区块头信息
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BlockHeader {
     pub timestamp: DateTime<Utc>,
     pub prev_hash: String,
     pub hash: String,
     pub nonce: u64,
     pub difficulty: u32,
+    #[serde(default)]
+    pub bits: u32, // 紧凑编码的 256 位目标值（为 0 时使用旧版十六进制前导零难度）
     pub validator: Option<String>, // 验证者地址（用于 PoS/DPoS）
+    #[serde(default)]
+    pub signature: Option<String>, // 验证者对区块头的签名（十六进制字符串）
+    #[serde(default)]
+    pub public_key: Option<String>, // 验证者公钥（十六进制字符串）
+    #[serde(default)]
+    pub authority_vote: Option<AuthorityVote>, // PoA 授权节点增删投票
+}
+
+/// PoA 授权节点投票（由出块的授权节点写入区块头）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthorityVote {
+    pub target: String,
+    pub authorize: bool,            // true 为加入，false 为移除
+    pub public_key: Option<String>, // 加入时需提供目标节点的签名公钥
+}
+
+impl BlockHeader {
+    /// 计算验证者签名的消息（绑定区块高度和区块哈希）
+    pub fn signing_message(&self, height: u64) -> String {
+        format!("{}:{}", height, self.hash)
+    }
+
+    /// 验证区块头签名
+    pub fn verify_signature(&self, height: u64) -> bool {
+        match (&self.public_key, &self.signature) {
+            (Some(public_key), Some(signature)) => {
+                verify_hex_signature(public_key, &self.signing_message(height), signature)
+            }
+            _ => false,
+        }
+    }
+
+    /// 检查区块头声明的哈希是否满足其难度要求（不重新计算哈希）
+    pub fn meets_target(&self) -> bool {
+        if self.bits != 0 {
+            return pow::hash_meets_target(&self.hash, &pow::compact_to_target(self.bits));
+        }
+
+        let prefix = "0".repeat(self.difficulty as usize);
+        self.hash.starts_with(&prefix)
+    }
+
+    /// 区块的工作量（目标值区块按 2^256 / (target + 1) 计算，旧版区块按 16^difficulty 计算）
+    pub fn work(&self) -> u128 {
+        match (self.bits, self.difficulty) {
+            (0, 0) => 1, // PoA 等无需挖矿的区块
+            (0, difficulty) => 1u128.checked_shl(difficulty * 4).unwrap_or(u128::MAX),
+            (bits, _) => pow::work_from_bits(bits),
+        }
+    }
+}
+
+/// 使用十六进制编码的公钥验证 Ed25519 签名
+pub fn verify_hex_signature(public_key_hex: &str, message: &str, signature_hex: &str) -> bool {
+    let public_key_bytes = match hex::decode(public_key_hex) {
+        Ok(bytes) => bytes,
+        Err(_) => return false,
+    };
+
+    let signature_bytes = match hex::decode(signature_hex) {
+        Ok(bytes) => bytes,
+        Err(_) => return false,
+    };
+
+    signature::UnparsedPublicKey::new(&signature::ED25519, &public_key_bytes)
+        .verify(message.as_bytes(), &signature_bytes)
+        .is_ok()
 }
 
 /// 区块数据
@@ -33,82 +108,335 @@ pub struct Transaction {
     pub timestamp: DateTime<Utc>,
     pub signature: Option<String>, // 交易签名（十六进制字符串）
     pub public_key: Option<String>, // 发送者公钥（十六进制字符串）
+    #[serde(default)]
+    pub kind: TransactionKind, // 交易类型
+    #[serde(default)]
+    pub fee: u64, // 手续费（由出块者通过 coinbase 交易领取）
+}
+
+/// coinbase 交易的一笔奖励输出
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoinbaseOutput {
+    pub address: String,
+    pub amount: u64,
+}
+
+/// 交易类型
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum TransactionKind {
+    /// 普通转账
+    #[default]
+    Transfer,
+    /// 治理提案：在投票通过后修改链参数
+    GovernanceProposal {
+        change: ParamChange,
+        description: String,
+    },
+    /// 治理投票
+    GovernanceVote {
+        proposal_id: String,
+        approve: bool,
+    },
+    /// 出块奖励：只能是区块的第一笔交易，发放出块奖励和手续费
+    Coinbase {
+        height: u64,
+        outputs: Vec<CoinbaseOutput>,
+    },
+    /// PoS 质押：交易金额转入质押地址锁定
+    Stake,
+    /// PoS 取消质押：全部质押立即退还
+    Unstake,
+    /// DPoS 注册候选人：交易金额作为候选人自有质押
+    RegisterCandidate,
+    /// DPoS 注销候选人：自有质押和收到的委托进入解绑期
+    DeregisterCandidate,
+    /// DPoS 委托：交易金额委托给候选人
+    Delegate {
+        candidate: String,
+    },
+    /// DPoS 撤销委托：撤销金额进入解绑期，到期后退还
+    Undelegate {
+        candidate: String,
+        amount: u64,
+    },
+    /// DPoS 转移委托（立即生效）
+    Redelegate {
+        from: String,
+        to: String,
+        amount: u64,
+    },
+    /// 登记发送者自己的 PoS 质押或 DPoS 候选人的区块签名公钥（交易必须由该公钥签名，登记后不能替换）
+    RegisterValidatorKey {
+        public_key: String,
+    },
+    /// DPoS 候选人设置佣金比例（百分比，下一个区块起生效）
+    SetCommission {
+        rate: u64,
+    },
+    /// 提交验证者作恶证据（任何人都可以提交，核验通过后罚没并监禁）
+    Evidence {
+        evidence: Evidence,
+    },
+    /// 监禁期满的验证者解除监禁
+    Unjail,
+}
+
+impl TransactionKind {
+    /// 参与哈希和签名的类型数据（普通转账为空，与旧版本哈希保持一致）
+    fn hash_data(&self) -> String {
+        match self {
+            TransactionKind::Transfer => String::new(),
+            TransactionKind::GovernanceProposal { change, description } => {
+                format!("proposal{:?}{}{}", change.param, change.value, description)
+            }
+            TransactionKind::GovernanceVote { proposal_id, approve } => {
+                format!("vote{}{}", proposal_id, approve)
+            }
+            TransactionKind::Coinbase { height, outputs } => {
+                let outputs: Vec<String> = outputs.iter().map(|output| format!("{}:{}", output.address, output.amount)).collect();
+                format!("coinbase{}{}", height, outputs.join(","))
+            }
+            TransactionKind::Stake => "stake".to_string(),
+            TransactionKind::Unstake => "unstake".to_string(),
+            TransactionKind::RegisterCandidate => "register".to_string(),
+            TransactionKind::DeregisterCandidate => "deregister".to_string(),
+            TransactionKind::Delegate { candidate } => format!("delegate{}", candidate),
+            TransactionKind::Undelegate { candidate, amount } => format!("undelegate{}{}", candidate, amount),
+            TransactionKind::Redelegate { from, to, amount } => format!("redelegate{}{}{}", from, to, amount),
+            TransactionKind::RegisterValidatorKey { public_key } => format!("validatorkey{}", public_key),
+            TransactionKind::SetCommission { rate } => format!("commission{}", rate),
+            TransactionKind::Evidence { evidence } => format!("evidence{}", evidence.id()),
+            TransactionKind::Unjail => "unjail".to_string(),
+        }
+    }
+
+    /// 是否为质押交易（由 PoS/DPoS 共识引擎执行）
+    pub fn is_staking(&self) -> bool {
+        matches!(
+            self,
+            TransactionKind::Stake
+                | TransactionKind::Unstake
+                | TransactionKind::RegisterCandidate
+                | TransactionKind::DeregisterCandidate
+                | TransactionKind::Delegate { .. }
+                | TransactionKind::Undelegate { .. }
+                | TransactionKind::Redelegate { .. }
+                | TransactionKind::RegisterValidatorKey { .. }
+                | TransactionKind::SetCommission { .. }
+                | TransactionKind::Evidence { .. }
+                | TransactionKind::Unjail
+        )
+    }
+
+    /// 是否为锁定资金的质押交易（交易金额转入质押地址）
+    pub fn locks_funds(&self) -> bool {
+        matches!(
+            self,
+            TransactionKind::Stake | TransactionKind::RegisterCandidate | TransactionKind::Delegate { .. }
+        )
+    }
 }
 
 impl Transaction {
     /// 创建新交易
     pub fn new(sender: String, receiver: String, amount: u64) -> Self {
-        let id = format!("tx_{}", Utc::now().timestamp());
-        Self {
-            id,
+        let mut transaction = Self {
+            id: String::new(),
             sender,
             receiver,
             amount,
             timestamp: Utc::now(),
             signature: None,
             public_key: None,
+            kind: TransactionKind::Transfer,
+            fee: 0,
+        };
+        transaction.id = transaction.txid();
+        transaction
+    }
+
+    /// 设置手续费（需在签名前设置）
+    pub fn with_fee(mut self, fee: u64) -> Self {
+        self.fee = fee;
+        self.id = self.txid();
+        self
+    }
+
+    /// 创建 coinbase 交易，奖励总额为各输出之和
+    pub fn new_coinbase(height: u64, producer: String, outputs: Vec<CoinbaseOutput>) -> Self {
+        let amount = outputs.iter().map(|output| output.amount).sum();
+        let mut transaction = Self::new(ISSUER_ADDRESS.to_string(), producer, amount);
+        transaction.id = format!("coinbase_{}", height);
+        transaction.kind = TransactionKind::Coinbase { height, outputs };
+        transaction
+    }
+
+    /// 是否为 coinbase 交易
+    pub fn is_coinbase(&self) -> bool {
+        matches!(self.kind, TransactionKind::Coinbase { .. })
+    }
+
+    /// 交易入账的地址和金额（coinbase 按输出入账，其他交易入账给接收者）
+    pub fn credits(&self) -> Vec<(&str, u64)> {
+        match &self.kind {
+            TransactionKind::Coinbase { outputs, .. } => {
+                outputs.iter().map(|output| (output.address.as_str(), output.amount)).collect()
+            }
+            _ => vec![(self.receiver.as_str(), self.amount)],
         }
     }
 
+    /// 创建治理提案交易
+    pub fn new_proposal(proposer: String, change: ParamChange, description: String) -> Self {
+        let mut transaction = Self::new(proposer, GOVERNANCE_ADDRESS.to_string(), 0);
+        transaction.kind = TransactionKind::GovernanceProposal { change, description };
+        transaction.id = transaction.txid();
+        transaction
+    }
+
+    /// 创建治理投票交易
+    pub fn new_vote(voter: String, proposal_id: String, approve: bool) -> Self {
+        let mut transaction = Self::new(voter, GOVERNANCE_ADDRESS.to_string(), 0);
+        transaction.kind = TransactionKind::GovernanceVote { proposal_id, approve };
+        transaction.id = transaction.txid();
+        transaction
+    }
+
+    /// 创建质押交易（锁定资金的交易金额为锁定金额，其余质押交易金额为 0）
+    pub fn new_staking(sender: String, kind: TransactionKind, amount: u64) -> Self {
+        let amount = if kind.locks_funds() { amount } else { 0 };
+        let mut transaction = Self::new(sender, STAKING_ADDRESS.to_string(), amount);
+        transaction.kind = kind;
+        transaction.id = transaction.txid();
+        transaction
+    }
+
+    /// 创建验证者公钥登记交易，并用该验证者密钥签名以证明持有私钥
+    pub fn new_validator_key(key: &ValidatorKey) -> Self {
+        let kind = TransactionKind::RegisterValidatorKey { public_key: key.public_key_hex() };
+        let mut transaction = Self::new_staking(key.address.clone(), kind, 0);
+        transaction.sign(&key.keypair());
+        transaction
+    }
+
     /// 创建带签名的交易
-    pub fn new_signed(sender: String, receiver: String, amount: u64, keypair: &Ed25519KeyPair) -> Self {
-        let id = format!("tx_{}", Utc::now().timestamp());
+    pub fn new_signed(sender: String, receiver: String, amount: u64, fee: u64, keypair: &Ed25519KeyPair) -> Self {
         let mut transaction = Self {
-            id,
+            id: String::new(),
             sender,
             receiver,
             amount,
             timestamp: Utc::now(),
             signature: None,
-            public_key: Some(hex::encode(keypair.public_key().as_ref())),
+            public_key: None,
+            kind: TransactionKind::Transfer,
+            fee,
         };
+        transaction.id = transaction.txid();
+        transaction.sign(keypair);
+        transaction
+    }
 
-        // 计算交易数据哈希并签名
-        let message = transaction.calculate_message_hash();
+    /// 计算交易数据哈希并签名（ID 由交易内容决定，签名同时覆盖 ID）
+    fn sign(&mut self, keypair: &Ed25519KeyPair) {
+        self.public_key = Some(hex::encode(keypair.public_key().as_ref()));
+        let message = self.calculate_message_hash();
         let signature = keypair.sign(message.as_bytes());
-        transaction.signature = Some(hex::encode(signature.as_ref()));
-
-        transaction
+        self.signature = Some(hex::encode(signature.as_ref()));
     }
 
     /// 计算用于签名的消息哈希（不包含签名和公钥）
     pub fn calculate_message_hash(&self) -> String {
         let data = format!(
-            "{}{}{}{}{}",
+            "{}{}{}{}{}{}{}",
             self.sender,
             self.receiver,
             self.amount,
             self.timestamp.timestamp(),
-            self.id
+            self.id,
+            self.kind.hash_data(),
+            self.fee_data()
         );
         let mut hasher = Sha256::new();
         hasher.update(data.as_bytes());
         format!("{:x}", hasher.finalize())
     }
 
+    /// 交易 ID：不含 ID 本身的交易内容哈希，签名前确定，任何节点都能在本地重新计算
+    pub fn txid(&self) -> String {
+        let data = format!(
+            "{}{}{}{}{}{}",
+            self.sender,
+            self.receiver,
+            self.amount,
+            self.timestamp.timestamp(),
+            self.kind.hash_data(),
+            self.fee_data()
+        );
+        let mut hasher = Sha256::new();
+        hasher.update(data.as_bytes());
+        format!("tx_{:x}", hasher.finalize())
+    }
+
+    /// 参与哈希和签名的手续费数据（无手续费时为空，与旧版本哈希保持一致）
+    fn fee_data(&self) -> String {
+        if self.fee == 0 { String::new() } else { format!("fee{}", self.fee) }
+    }
+
     /// 验证交易签名
     pub fn verify_signature(&self) -> bool {
-        if self.signature.is_none() || self.public_key.is_none() {
-            return false; // 系统交易不需要签名验证
+        match (&self.public_key, &self.signature) {
+            (Some(public_key), Some(signature)) => {
+                verify_hex_signature(public_key, &self.calculate_message_hash(), signature)
+            }
+            _ => false, // 系统交易不需要签名验证
         }
+    }
 
-        // 解码公钥和签名
-        let public_key_bytes = match hex::decode(self.public_key.as_ref().unwrap()) {
-            Ok(bytes) => bytes,
-            Err(_) => return false,
-        };
+    /// 与链上状态无关的交易检查：不能是系统发行，治理交易发送到治理地址且金额为 0，
+    /// 质押交易发送到质押地址，转账双方不同且金额大于 0，带签名的交易签名有效
+    pub fn check_format(&self) -> Result<(), String> {
+        // 新发行只能来自出块者打包的 coinbase 交易
+        if self.is_coinbase() || self.sender == ISSUER_ADDRESS {
+            return Err("系统发行只能通过区块的 coinbase 交易".to_string());
+        }
 
-        let signature_bytes = match hex::decode(self.signature.as_ref().unwrap()) {
-            Ok(bytes) => bytes,
-            Err(_) => return false,
-        };
+        match &self.kind {
+            TransactionKind::Transfer => {
+                if self.sender == self.receiver {
+                    return Err("发送者和接收者不能是同一个人".to_string());
+                }
+                if self.amount == 0 {
+                    return Err("交易金额必须大于0".to_string());
+                }
+            }
+            kind if kind.is_staking() => {
+                if self.receiver != STAKING_ADDRESS {
+                    return Err("质押交易必须发送到质押地址".to_string());
+                }
+                if kind.locks_funds() != (self.amount > 0) {
+                    return Err("只有质押、注册候选人和委托交易携带金额且金额必须大于0".to_string());
+                }
+                if let TransactionKind::RegisterValidatorKey { public_key } = kind
+                    && (self.public_key.as_ref() != Some(public_key) || self.signature.is_none())
+                {
+                    return Err("验证者公钥登记交易必须由登记的公钥签名".to_string());
+                }
+                if let TransactionKind::Evidence { evidence: Evidence::Downtime { .. } } = kind {
+                    return Err("停机由链上出块记录推导，不能作为证据提交".to_string());
+                }
+            }
+            _ => {
+                if self.receiver != GOVERNANCE_ADDRESS || self.amount != 0 {
+                    return Err("治理交易必须发送到治理地址且金额为0".to_string());
+                }
+            }
+        }
 
-        // 验证签名
-        let message = self.calculate_message_hash();
-        match signature::UnparsedPublicKey::new(&signature::ED25519, &public_key_bytes).verify(message.as_bytes(), &signature_bytes) {
-            Ok(_) => true,
-            Err(_) => false,
+        if self.signature.is_some() && !self.verify_signature() {
+            return Err("交易签名验证失败".to_string());
         }
+        Ok(())
     }
 
     /// 生成新的密钥对
@@ -121,12 +449,14 @@ impl Transaction {
     /// 计算交易哈希
     pub fn calculate_hash(&self) -> String {
         let data = format!(
-            "{}{}{}{}{}",
+            "{}{}{}{}{}{}{}",
             self.sender,
             self.receiver,
             self.amount,
             self.timestamp.timestamp(),
-            self.id
+            self.id,
+            self.kind.hash_data(),
+            self.fee_data()
         );
         let mut hasher = Sha256::new();
         hasher.update(data.as_bytes());
@@ -150,7 +480,11 @@ impl Block {
                 hash: String::new(),
                 nonce: 0,
                 difficulty,
+                bits: 0,
                 validator: None,
+                signature: None,
+                public_key: None,
+                authority_vote: None,
             },
             transactions,
             height,
@@ -161,27 +495,47 @@ impl Block {
     }
 
     /// 创建创世区块
-    pub fn create_genesis(difficulty: u32) -> Self {
-        let genesis_transaction = Transaction::new(
-            "system".to_string(),
+    ///
+    /// 时间戳固定为链配置中的创世时间，创世交易 ID 包含链配置哈希，相同配置的节点得到相同的创世区块
+    pub fn create_genesis(difficulty: u32, timestamp: DateTime<Utc>, spec_hash: &str) -> Self {
+        let mut genesis_transaction = Transaction::new(
+            ISSUER_ADDRESS.to_string(),
             "genesis".to_string(),
             0,
         );
-        Self::new(
+        genesis_transaction.id = format!("genesis_{}", spec_hash);
+        genesis_transaction.timestamp = timestamp;
+
+        let mut block = Self::new(
             "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
             vec![genesis_transaction],
             0,
             difficulty,
-        )
+        );
+        block.header.timestamp = timestamp;
+        block.header.hash = block.calculate_hash();
+        block
+    }
+
+    /// 区块序列化后的字节数（用于区块大小限制）
+    pub fn size(&self) -> usize {
+        serde_json::to_vec(self).map(|bytes| bytes.len()).unwrap_or(usize::MAX)
     }
 
     /// 计算区块哈希
     pub fn calculate_hash(&self) -> String {
         let mut hasher = Sha256::new();
 
-        // 构建要哈希的数据
+        // 构建要哈希的数据（验证者、投票和目标值为空时与旧版本哈希保持一致）
+        let vote = self.header.authority_vote.as_ref().map(|vote| {
+            format!("{}{}{}", vote.target, vote.authorize, vote.public_key.as_deref().unwrap_or(""))
+        });
+        let bits = match self.header.bits {
+            0 => String::new(),
+            bits => format!("{:08x}", bits),
+        };
         let data = format!(
-            "{}{}{}{}{}{}",
+            "{}{}{}{}{}{}{}{}{}",
             self.header.timestamp.timestamp(),
             self.header.prev_hash,
             self.header.nonce,
@@ -190,29 +544,62 @@ impl Block {
             self.transactions
                 .iter()
                 .map(|tx| tx.calculate_hash())
-                .collect::<String>()
+                .collect::<String>(),
+            self.header.validator.as_deref().unwrap_or(""),
+            vote.unwrap_or_default(),
+            bits
         );
 
         hasher.update(data.as_bytes());
         format!("{:x}", hasher.finalize())
     }
 
-    /// 挖矿 - 寻找合适的nonce值
-    pub fn mine(&mut self) {
-        println!("Mining block {}...", self.height);
+    /// 挖矿 - 从 start_nonce 起按 step 步长寻找合适的nonce值，stop 置位时放弃
+    ///
+    /// 多个线程使用不同的起点和相同的步长即可无重叠地划分 nonce 空间
+    pub fn mine_range(&mut self, start_nonce: u64, step: u64, stop: &AtomicBool, hashes: &AtomicU64) -> bool {
+        self.header.nonce = start_nonce;
+        self.header.hash = self.calculate_hash();
 
+        let mut local_hashes = 0u64;
         while !self.is_valid_hash() {
-            self.header.nonce += 1;
+            // 批量更新计数并检查停止标志，减少原子操作开销
+            if local_hashes == 1024 {
+                hashes.fetch_add(local_hashes, Ordering::Relaxed);
+                local_hashes = 0;
+                if stop.load(Ordering::Relaxed) {
+                    return false;
+                }
+            }
+
+            self.header.nonce = match self.header.nonce.checked_add(step) {
+                Some(nonce) => nonce,
+                None => return false, // nonce 空间耗尽
+            };
             self.header.hash = self.calculate_hash();
+            local_hashes += 1;
         }
 
-        println!("Block {} mined! Nonce: {}", self.height, self.header.nonce);
+        hashes.fetch_add(local_hashes + 1, Ordering::Relaxed);
+        true
+    }
+
+    /// 验证者签名区块头（需在挖矿完成、哈希确定之后调用）
+    pub fn sign(&mut self, keypair: &Ed25519KeyPair) {
+        let message = self.header.signing_message(self.height);
+        let signature = keypair.sign(message.as_bytes());
+        self.header.public_key = Some(hex::encode(keypair.public_key().as_ref()));
+        self.header.signature = Some(hex::encode(signature.as_ref()));
     }
 
-    /// 验证哈希是否满足难度要求
+    /// 验证哈希是否满足难度要求（有目标值时按 256 位数值比较）
     pub fn is_valid_hash(&self) -> bool {
-        let prefix = "0".repeat(self.header.difficulty as usize);
-        self.header.hash.starts_with(&prefix)
+        self.header.meets_target()
+    }
+
+    /// 区块的工作量
+    pub fn work(&self) -> u128 {
+        self.header.work()
     }
 
     /// 验证区块的有效性
@@ -234,6 +621,26 @@ impl Block {
 
         true
     }
+
+    /// 不依赖链状态的完整性检查：哈希与内容一致且满足难度要求、区块头签名（如有）有效、
+    /// 普通交易格式和签名有效。不通过的区块在任何节点上都无效
+    pub fn check_integrity(&self) -> Result<(), String> {
+        if self.header.hash != self.calculate_hash() {
+            return Err("区块哈希与内容不符".to_string());
+        }
+        if !self.is_valid_hash() {
+            return Err("区块哈希不满足难度要求".to_string());
+        }
+        if self.header.signature.is_some() && !self.header.verify_signature(self.height) {
+            return Err("区块头签名无效".to_string());
+        }
+
+        // coinbase 和旧版系统奖励交易由链规则验证
+        for transaction in self.transactions.iter().filter(|tx| !tx.is_coinbase() && tx.sender != ISSUER_ADDRESS) {
+            transaction.check_format().map_err(|e| format!("交易 {} 无效: {}", transaction.id, e))?;
+        }
+        Ok(())
+    }
 }
 
 impl Display for Block {
@@ -249,3 +656,73 @@ impl Display for Block {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_transaction_id_is_content_hash() {
+        let keypair = Transaction::generate_keypair();
+        let transaction = Transaction::new_signed("alice".to_string(), "bob".to_string(), 5, 1, &keypair);
+
+        assert_eq!(transaction.id, transaction.txid());
+        assert!(transaction.check_format().is_ok());
+    }
+
+    #[test]
+    fn txid_ignores_claimed_id() {
+        let mut transaction = Transaction::new("alice".to_string(), "bob".to_string(), 5).with_fee(2);
+        let id = transaction.txid();
+        assert_eq!(transaction.id, id);
+
+        transaction.id = "tx_collision".to_string();
+        assert_eq!(transaction.txid(), id);
+    }
+
+    #[test]
+    fn tampered_signed_transaction_fails_verification() {
+        let keypair = Transaction::generate_keypair();
+        let mut transaction = Transaction::new_signed("alice".to_string(), "bob".to_string(), 5, 0, &keypair);
+        transaction.amount = 50;
+
+        assert!(transaction.check_format().is_err());
+    }
+
+    #[test]
+    fn integrity_check_rejects_context_free_invalidity() {
+        let keypair = Transaction::generate_keypair();
+        let transaction = Transaction::new_signed("alice".to_string(), "bob".to_string(), 5, 0, &keypair);
+        let mut block = Block::new("prev".to_string(), vec![transaction], 1, 0);
+        let key = ValidatorKey::generate("alice".to_string());
+        block.sign(&key.keypair());
+        assert!(block.check_integrity().is_ok());
+
+        // 区块头签名与公钥不符
+        let mut forged = block.clone();
+        forged.header.public_key = Some(ValidatorKey::generate("alice".to_string()).public_key_hex());
+        assert!(forged.check_integrity().is_err());
+
+        // 篡改交易后即使重新计算区块哈希，交易签名仍然无效
+        let mut tampered = Block::new("prev".to_string(), block.transactions.clone(), 1, 0);
+        tampered.transactions[0].amount = 50;
+        tampered.header.hash = tampered.calculate_hash();
+        assert!(tampered.check_integrity().unwrap_err().contains("交易"));
+    }
+
+    #[test]
+    fn validator_key_registration_requires_signature_by_registered_key() {
+        let key = ValidatorKey::generate("alice".to_string());
+        assert!(Transaction::new_validator_key(&key).check_format().is_ok());
+
+        // 未签名或由其他密钥签名的登记交易无效
+        let other = ValidatorKey::generate("alice".to_string());
+        let kind = TransactionKind::RegisterValidatorKey { public_key: key.public_key_hex() };
+        let unsigned = Transaction::new_staking("alice".to_string(), kind.clone(), 0);
+        assert!(unsigned.check_format().is_err());
+
+        let mut forged = Transaction::new_staking("alice".to_string(), kind, 0);
+        forged.sign(&other.keypair());
+        assert!(forged.check_format().is_err());
+    }
+}
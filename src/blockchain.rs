@@ -1,9 +1,39 @@
-use crate::block::{Block, Transaction};
-use crate::consensus::{ConsensusType, ProofOfStake, DelegatedProofOfStake};
+use crate::block::{Block, BlockHeader, CoinbaseOutput, Transaction, TransactionKind};
+use crate::chain_spec::ChainSpec;
+use crate::chain_params::{ChainParams, CoinbaseRule, DifficultyRule, HardFork, RuleSet};
+use crate::consensus::{
+    Consensus, ConsensusType, DelegatedProofOfStake, Evidence, ProofOfAuthority, ProofOfStake, RewardKind, RewardShare,
+    Slashing, StakingReward, STAKING_ADDRESS, signed_by_registered_key,
+};
+use crate::finality::{FinalityGadget, ValidatorWeight, Vote, VoteOutcome, VoteType};
+use crate::governance::{Governance, GovernanceParam, ProposalStatus};
+use crate::monetary::{BURN_ADDRESS, ISSUER_ADDRESS, SupplyAudit, SupplyLedger};
+use crate::pow;
+use crate::wallet::ValidatorKey;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 
+/// 难度调整窗口（区块数）
+const RETARGET_INTERVAL: u64 = 10;
+/// 目标出块时间（秒）
+const TARGET_BLOCK_TIME_SECS: i64 = 10;
+/// 中位时间计算使用的区块数
+const MEDIAN_TIME_SPAN: usize = 11;
+/// 区块时间戳允许超前本地时间的最大秒数
+const MAX_FUTURE_BLOCK_TIME_SECS: i64 = 2 * 60 * 60;
+/// 难度上限（十六进制前导零个数）
+const MAX_DIFFICULTY: u32 = 20;
+/// 创建区块模板时为区块头和 coinbase 交易预留的字节数
+const BLOCK_OVERHEAD_BYTES: usize = 2048;
+
+/// 待挖矿的区块模板，以及提交区块时需要的出块上下文
+#[derive(Debug, Clone)]
+pub struct BlockTemplate {
+    pub block: Block,
+    producer: Option<String>,
+}
+
 /// 区块链结构体
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Blockchain {
@@ -15,28 +45,46 @@ pub struct Blockchain {
     pub consensus_type: ConsensusType,
     pub pos_consensus: Option<ProofOfStake>,
     pub dpos_consensus: Option<DelegatedProofOfStake>,
+    #[serde(default)]
+    pub poa_consensus: Option<ProofOfAuthority>,
+    #[serde(skip)]
+    pub validator_key: Option<ValidatorKey>, // 本节点验证者签名密钥（保存在 validator_key.json，不随链数据保存）
+    #[serde(skip)]
+    pub staking_rewards: HashMap<String, Vec<StakingReward>>, // 地址 -> 质押奖励历史（由链上 coinbase 输出重放得到）
+    #[serde(default)]
+    pub finality: FinalityGadget,
+    #[serde(default)]
+    pub chain_params: ChainParams, // 硬分叉计划（各高度生效的链规则）
+    #[serde(default)]
+    pub governance: Governance, // 链上治理提案
+    #[serde(skip)]
+    supply: SupplyLedger, // 发行统计和近期 coinbase 索引（加载时由链重放得到）
 }
 
 impl Blockchain {
-    /// 创建新区块链
-    pub fn new(difficulty: u32, mining_reward: u64) -> Self {
+    /// 按链配置创建新区块链，相同配置的节点得到相同的创世区块
+    pub fn from_spec(spec: &ChainSpec) -> Self {
         let mut blockchain = Self {
             chain: Vec::new(),
             pending_transactions: Vec::new(),
-            difficulty,
-            mining_reward,
+            difficulty: spec.difficulty,
+            mining_reward: spec.monetary.initial_subsidy,
             balances: HashMap::new(),
             consensus_type: ConsensusType::PoW,
             pos_consensus: None,
             dpos_consensus: None,
+            poa_consensus: None,
+            validator_key: None,
+            staking_rewards: HashMap::new(),
+            finality: FinalityGadget::default(),
+            chain_params: spec.chain_params(),
+            governance: Governance::default(),
+            supply: SupplyLedger::default(),
         };
 
-        // 创建创世区块
-        let genesis_block = Block::create_genesis(difficulty);
-        blockchain.chain.push(genesis_block);
-
-        // 添加初始余额给系统账户（用于演示）
-        blockchain.balances.insert("system".to_string(), 1000);
+        // 创建创世区块，余额来自链参数中的创世分配，共识引擎按创世规则初始化
+        blockchain.chain.push(spec.genesis_block());
+        blockchain.rebuild_state();
 
         blockchain
     }
@@ -51,136 +99,1346 @@ impl Blockchain {
         self.chain.len()
     }
 
-    /// 添加交易到待处理队列
-    pub fn add_transaction(&mut self, mut transaction: Transaction) -> Result<(), String> {
-        // 验证交易
-        if transaction.sender == transaction.receiver {
-            return Err("发送者和接收者不能是同一个人".to_string());
+    /// 按哈希查找链上的区块
+    pub fn find_block(&self, hash: &str) -> Option<&Block> {
+        self.chain.iter().rev().find(|block| block.header.hash == hash)
+    }
+
+    /// 按 ID 查找交易池中的交易
+    pub fn pending_transaction(&self, id: &str) -> Option<&Transaction> {
+        self.pending_transactions.iter().find(|tx| tx.id == id)
+    }
+
+    /// 交易是否已在交易池或链上
+    pub fn has_transaction(&self, id: &str) -> bool {
+        self.pending_transaction(id).is_some()
+            || self.chain.iter().any(|block| block.transactions.iter().any(|tx| tx.id == id))
+    }
+
+    /// 接受网络上收到的、连接到当前链顶的区块：按该高度的规则校验后追加，并移除交易池中已打包的交易
+    pub fn accept_block(&mut self, block: Block) -> Result<(), String> {
+        if block.header.prev_hash != self.get_latest_block().header.hash {
+            return Err("区块不连接到当前链顶".to_string());
         }
+        let included: HashSet<String> = block.transactions.iter().map(|tx| tx.id.clone()).collect();
+        self.connect_block(block)?;
+        self.pending_transactions.retain(|tx| !included.contains(&tx.id));
+        Ok(())
+    }
 
-        if transaction.amount == 0 {
-            return Err("交易金额必须大于0".to_string());
+    /// 用从 fork_height 开始的分支替换本地链的后续部分：只校验分支中的区块，新链累计工作量必须更大。
+    /// 直接延长链顶时逐个连接（已连接的有效区块保留）；回滚本地区块时先重放到分叉点，失败则恢复原链
+    pub fn accept_branch(&mut self, fork_height: usize, blocks: Vec<Block>) -> Result<(), String> {
+        let connects = fork_height > 0
+            && fork_height <= self.chain.len()
+            && blocks.first().is_some_and(|block| block.header.prev_hash == self.chain[fork_height - 1].header.hash);
+        if !connects {
+            return Err("区块不连接到本地链".to_string());
         }
 
-        // 检查发送者余额
-        let sender_balance = self.get_balance(&transaction.sender);
-        if sender_balance < transaction.amount {
-            return Err(format!(
-                "发送者余额不足。当前余额: {}, 交易金额: {}",
-                sender_balance, transaction.amount
-            ));
+        let included: HashSet<String> = blocks.iter().flat_map(|block| &block.transactions).map(|tx| tx.id.clone()).collect();
+        if fork_height == self.chain.len() {
+            for block in blocks {
+                let height = block.height;
+                self.connect_block(block).map_err(|e| format!("区块 {} 无效: {}", height, e))?;
+                self.pending_transactions.retain(|tx| !included.contains(&tx.id));
+            }
+            return Ok(());
+        }
+
+        // 已最终确认的区块不可回滚
+        if self.finality.finalized_hash.is_some() && fork_height as u64 <= self.finality.finalized_height {
+            return Err(format!("分支回滚了已最终确认的区块 {}", self.finality.finalized_height));
         }
+        let new_work = Self::total_work(&blocks);
+        let local_work = Self::total_work(&self.chain[fork_height..]);
+        if new_work <= local_work {
+            return Err(format!("分支累计工作量不大于本地链 ({} <= {})", new_work, local_work));
+        }
+
+        // 保存分叉点之后的本地区块和当前状态（状态副本不含区块），失败时恢复
+        let detached = self.chain.split_off(fork_height);
+        let prefix = std::mem::take(&mut self.chain);
+        let snapshot = self.clone();
+        self.chain = prefix;
+        self.rebuild_state();
+
+        for block in blocks {
+            let height = block.height;
+            if let Err(e) = self.connect_block(block) {
+                let mut chain = std::mem::take(&mut self.chain);
+                chain.truncate(fork_height);
+                chain.extend(detached);
+                *self = snapshot;
+                self.chain = chain;
+                return Err(format!("区块 {} 无效: {}", height, e));
+            }
+        }
+
+        self.pending_transactions.retain(|tx| !included.contains(&tx.id));
+        Ok(())
+    }
+
+    /// 在当前链顶之后连接一个区块：按该高度生效的规则校验后追加，并增量更新链状态
+    fn connect_block(&mut self, block: Block) -> Result<(), String> {
+        self.check_block(&block)?;
+        self.apply_block(block);
+        Ok(())
+    }
 
-        // 验证交易签名（如果是签名交易）
-        if transaction.signature.is_some() {
-            if !transaction.verify_signature() {
-                return Err("交易签名验证失败".to_string());
+    /// 将已校验的区块追加到链上：交易计入余额、发行统计和质押状态，释放到期的解绑，
+    /// 然后调整难度并切换到下一高度生效的规则
+    fn apply_block(&mut self, block: Block) {
+        let height = block.height;
+        self.record_staking_rewards(&block);
+        self.record_validator_slots(&block);
+        for transaction in &block.transactions {
+            Self::apply_transaction(&mut self.balances, transaction);
+            if transaction.kind.is_staking()
+                && let Err(e) = self.apply_staking_transaction(transaction, height)
+            {
+                // 未经校验的旧区块中无效的质押交易不锁定资金
+                println!("⚠️ 质押交易 {} 未生效: {}", transaction.id, e);
+                if transaction.kind.locks_funds() {
+                    self.release_stake(&transaction.sender, transaction.amount);
+                }
             }
         }
+        self.supply.connect(&block, self.chain_params.monetary.coinbase_maturity);
+
+        // 重放区块头中的授权节点投票
+        if self.consensus_type == ConsensusType::PoA
+            && let Some(poa) = &mut self.poa_consensus
+            && let Some(change) = poa.apply_block(&block)
+        {
+            println!("🏛️ {}", change);
+        }
+        // 记录治理提案和投票，统计到期提案
+        self.process_governance(&block);
+        self.chain.push(block);
+
+        self.release_unbonded_delegations(height);
+        self.adjust_difficulty();
+        self.apply_scheduled_rules();
+        self.refresh_producer_schedule();
+    }
+
+    /// 由创世状态和链上全部区块重新计算余额、发行统计、难度、质押和治理状态
+    fn rebuild_state(&mut self) {
+        let chain = std::mem::take(&mut self.chain);
+        let Some(genesis) = chain.first() else {
+            return;
+        };
+        self.balances = self.chain_params.monetary.genesis_allocations
+            .iter()
+            .map(|allocation| (allocation.address.clone(), allocation.amount))
+            .collect();
+        self.supply = SupplyLedger::default();
+        self.staking_rewards.clear();
+        self.governance = Governance { config: self.governance.config.clone(), ..Governance::default() };
+        self.difficulty = genesis.header.difficulty;
+        self.consensus_type = ConsensusType::PoW;
+        self.pos_consensus = None;
+        self.dpos_consensus = None;
+
+        // 授权节点集合由硬分叉规则和区块头投票重放得到，只保留本节点尚未写入区块的提议
+        let proposals = self.poa_consensus.take().map(|poa| poa.proposals).unwrap_or_default();
+        for block in chain {
+            self.apply_block(block);
+        }
+        if let Some(poa) = &mut self.poa_consensus {
+            poa.proposals = proposals;
+        }
+
+        // 最终性来自链外的投票，无法由区块重放；已确认的区块不在重建后的链上时（如数据文件被替换）重置
+        if let Some(hash) = &self.finality.finalized_hash
+            && self.chain.get(self.finality.finalized_height as usize).map(|block| &block.header.hash) != Some(hash)
+        {
+            println!("⚠️ 已最终确认的区块 {} 不在链上，重置最终性状态", self.finality.finalized_height);
+            self.finality = FinalityGadget::default();
+        }
+    }
+
+    /// 由当前共识引擎执行质押交易（PoS 取消质押立即退还，作恶证据罚没的质押从质押地址销毁）
+    fn apply_staking_transaction(&mut self, transaction: &Transaction, height: u64) -> Result<(), String> {
+        let burned_before = self.slashing().map(|slashing| slashing.total_burned).unwrap_or(0);
+        let refund = Self::execute_staking(
+            &self.consensus_type,
+            self.pos_consensus.as_mut(),
+            self.dpos_consensus.as_mut(),
+            transaction,
+            height,
+        )?;
+        if let Some(amount) = refund {
+            self.release_stake(&transaction.sender, amount);
+        }
+        self.burn_slashed_stake(burned_before);
+        Ok(())
+    }
+
+    /// 在给定的共识引擎上执行质押交易，返回需要立即退还的金额
+    fn execute_staking(
+        consensus_type: &ConsensusType,
+        pos: Option<&mut ProofOfStake>,
+        dpos: Option<&mut DelegatedProofOfStake>,
+        transaction: &Transaction,
+        height: u64,
+    ) -> Result<Option<u64>, String> {
+        match (consensus_type, pos, dpos) {
+            (ConsensusType::PoS, Some(pos), _) => pos.apply_transaction(transaction, height),
+            (ConsensusType::DPoS, _, Some(dpos)) => dpos.apply_transaction(transaction, height).map(|_| None),
+            _ => Err(format!("{:?} 共识不支持质押交易", consensus_type)),
+        }
+    }
 
-        // 计算交易哈希
-        transaction.id = transaction.calculate_hash();
+    /// 在共识引擎副本上按顺序试执行质押交易（不修改链状态）
+    fn check_staking_transactions(&self, transactions: &[Transaction], height: u64) -> Result<(), String> {
+        if !transactions.iter().any(|transaction| transaction.kind.is_staking()) {
+            return Ok(());
+        }
+
+        let (mut pos, mut dpos) = (self.pos_consensus.clone(), self.dpos_consensus.clone());
+        for transaction in transactions.iter().filter(|transaction| transaction.kind.is_staking()) {
+            Self::execute_staking(&self.consensus_type, pos.as_mut(), dpos.as_mut(), transaction, height)
+                .map_err(|e| format!("质押交易 {} 无效: {}", transaction.id, e))?;
+        }
+        Ok(())
+    }
+
+    /// 从质押地址销毁罚没模块累计销毁量中新增的部分，计入销毁统计
+    fn burn_slashed_stake(&mut self, burned_before: u64) {
+        let burned = self.slashing().map(|slashing| slashing.total_burned).unwrap_or(0).saturating_sub(burned_before);
+        if burned == 0 {
+            return;
+        }
+        let locked = self.balances.entry(STAKING_ADDRESS.to_string()).or_insert(0);
+        *locked = locked.saturating_sub(burned);
+        self.supply.burned = self.supply.burned.saturating_add(burned);
+    }
+
+    /// 从质押地址退还锁定的代币
+    fn release_stake(&mut self, address: &str, amount: u64) {
+        let locked = self.balances.entry(STAKING_ADDRESS.to_string()).or_insert(0);
+        let amount = amount.min(*locked);
+        *locked -= amount;
+        *self.balances.entry(address.to_string()).or_insert(0) += amount;
+    }
+
+    /// 区块定位器：从链顶开始的区块哈希，最近 10 个逐个列出，之后间隔加倍，最后是创世区块
+    pub fn block_locator(&self) -> Vec<String> {
+        let mut locator = Vec::new();
+        let mut height = self.chain.len() - 1;
+        let mut step = 1;
+        loop {
+            locator.push(self.chain[height].header.hash.clone());
+            if height == 0 {
+                return locator;
+            }
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            height = height.saturating_sub(step);
+        }
+    }
+
+    /// 定位器中第一个在本地链上的区块之后的区块头，到 stop_hash（包含）或 max 个为止；
+    /// 定位器中的区块都不在本地链上时从创世区块之后开始
+    pub fn headers_after(&self, locator: &[String], stop_hash: &str, max: usize) -> Vec<BlockHeader> {
+        let start = locator
+            .iter()
+            .find_map(|hash| self.chain.iter().rposition(|block| &block.header.hash == hash))
+            .map_or(1, |height| height + 1);
+
+        let mut headers = Vec::new();
+        for block in self.chain.iter().skip(start).take(max) {
+            headers.push(block.header.clone());
+            if block.header.hash == stop_hash {
+                break;
+            }
+        }
+        headers
+    }
+
+    /// 在下载区块体前验证区块头链：连接到本地链、相互连接、满足工作量证明和该高度规则要求的难度。
+    /// 返回第一个区块头的高度（分叉高度）；验证失败说明对方发送了无效数据
+    pub fn validate_headers(&self, headers: &[BlockHeader]) -> Result<usize, String> {
+        let first = headers.first().ok_or("区块头列表为空")?;
+        let fork_height = self.chain
+            .iter()
+            .rposition(|block| block.header.hash == first.prev_hash)
+            .ok_or("区块头不连接到本地链")?
+            + 1;
+
+        // 分叉点之前取本地区块头，之后取收到的区块头
+        let header_at = |index: usize| {
+            if index < fork_height {
+                self.chain.get(index).map(|block| &block.header)
+            } else {
+                headers.get(index - fork_height)
+            }
+        };
+
+        for (offset, header) in headers.iter().enumerate() {
+            let height = fork_height + offset;
+            if offset > 0 && header.prev_hash != headers[offset - 1].hash {
+                return Err(format!("区块头 {} 不连接到前一个区块头", height));
+            }
+            if !header.meets_target() {
+                return Err(format!("区块头 {} 的哈希不满足难度要求", height));
+            }
+            if let Some(rules) = self.chain_params.rules_at(height as u64) {
+                let difficulty_valid = match rules.difficulty_rule {
+                    DifficultyRule::Legacy => true,
+                    DifficultyRule::Windowed => header.difficulty == self.difficulty_with(height as u64, header_at),
+                    DifficultyRule::CompactTarget => {
+                        header.difficulty == 0 && header.bits == self.bits_with(height as u64, header_at)
+                    }
+                };
+                if !difficulty_valid {
+                    return Err(format!("区块头 {} 的难度不符合规则", height));
+                }
+            }
+        }
+
+        Ok(fork_height)
+    }
+
+    /// 检查从 fork_height 开始的区块头分支能否替换本地链：仍连接到本地链、不回滚已最终确认的区块，
+    /// 且分叉后的累计工作量大于本地链
+    pub fn check_branch(&self, fork_height: usize, headers: &[BlockHeader]) -> Result<(), String> {
+        let connects = fork_height
+            .checked_sub(1)
+            .and_then(|height| self.chain.get(height))
+            .zip(headers.first())
+            .is_some_and(|(block, header)| header.prev_hash == block.header.hash);
+        if !connects {
+            return Err("区块头不连接到本地链".to_string());
+        }
+        if self.finality.finalized_hash.is_some() && fork_height as u64 <= self.finality.finalized_height {
+            return Err(format!("区块头链回滚了已最终确认的区块 {}", self.finality.finalized_height));
+        }
+
+        let new_work = headers.iter().fold(0u128, |total, header| total.saturating_add(header.work()));
+        let local_work = Self::total_work(&self.chain[fork_height..]);
+        if new_work <= local_work {
+            return Err(format!("区块头链累计工作量不大于本地链 ({} <= {})", new_work, local_work));
+        }
+        Ok(())
+    }
+
+    /// 添加本地创建的交易到待处理队列（未签名交易的 ID 重新按内容计算；签名交易的 ID 受签名保护，不能修改）
+    pub fn add_transaction(&mut self, mut transaction: Transaction) -> Result<(), String> {
+        if transaction.signature.is_none() {
+            transaction.id = transaction.txid();
+        }
+        self.add_pending_transaction(transaction)
+    }
+
+    /// 校验交易并加入待处理队列（交易 ID 必须等于按内容计算的 ID）
+    pub fn add_pending_transaction(&mut self, transaction: Transaction) -> Result<(), String> {
+        transaction.check_format()?;
+        if transaction.id != transaction.txid() {
+            return Err("交易 ID 与交易内容不符".to_string());
+        }
+        if self.has_transaction(&transaction.id) {
+            return Err("交易已存在".to_string());
+        }
+
+        // 同一作恶证据在交易池中只保留一笔
+        if let TransactionKind::Evidence { evidence } = &transaction.kind
+            && self.pending_transactions.iter().any(|pending| {
+                matches!(&pending.kind, TransactionKind::Evidence { evidence: other } if other.id() == evidence.id())
+            })
+        {
+            return Err("该作恶证据已在交易池中".to_string());
+        }
+
+        // 质押交易按当前共识状态试执行，锁定金额和手续费按普通转账检查余额
+        if transaction.kind.is_staking() {
+            self.check_staking_transactions(std::slice::from_ref(&transaction), self.get_length() as u64)?;
+        }
+
+        // 治理交易不转移资金，只校验投票权重和提案状态
+        if matches!(transaction.kind, TransactionKind::GovernanceProposal { .. } | TransactionKind::GovernanceVote { .. }) {
+            let voting_power = self.voting_power(&transaction.sender);
+            self.governance.validate_transaction(&transaction, voting_power, self.get_length() as u64)?;
+            self.pending_transactions.push(transaction);
+            return Ok(());
+        }
+
+        // 检查发送者可花费余额（未成熟的出块奖励不可花费）
+        let sender_balance = self.spendable_balance(&transaction.sender);
+        let cost = transaction.amount.saturating_add(transaction.fee);
+        if sender_balance < cost {
+            return Err(format!(
+                "发送者可花费余额不足。可花费余额: {}, 交易金额: {}, 手续费: {}",
+                sender_balance, transaction.amount, transaction.fee
+            ));
+        }
 
         self.pending_transactions.push(transaction);
         Ok(())
     }
 
-    /// 挖矿 - 创建新区块
-    pub fn mine_pending_transactions(&mut self, miner_address: String) -> Result<Block, String> {
-        if self.pending_transactions.is_empty() {
-            return Err("没有待处理的交易".to_string());
+    /// 创建待挖矿的区块模板（挖矿本身在锁外进行，见 miner 模块；交易池为空时只包含 coinbase 交易）
+    pub fn create_block_template(&mut self, miner_address: String) -> Result<BlockTemplate, String> {
+        // 按硬分叉计划切换到当前高度生效的规则
+        self.apply_scheduled_rules();
+
+        // PoS/DPoS/PoA 下矿工地址即出块验证者
+        let height = self.get_length() as u64;
+        let expected_validator = self.expected_validator();
+        let producer = match self.consensus_type {
+            ConsensusType::PoW => None,
+            _ => Some(miner_address.clone()),
+        };
+        if let Some(producer) = &producer
+            && self.slashing().is_some_and(|s| s.is_jailed(producer, height))
+        {
+            return Err(format!("验证者 {} 处于监禁期，不能出块", producer));
+        }
+        // PoS/DPoS/PoA 区块头必须由验证者签名
+        if producer.is_some() && self.validator_key.as_ref().map(|key| &key.address) != producer.as_ref() {
+            return Err(format!("本节点没有验证者 {} 的签名密钥", miner_address));
+        }
+
+        // DPoS 只有当前槽位排定的出块人可以出块
+        if matches!(self.consensus_type, ConsensusType::DPoS)
+            && let Some(expected) = &expected_validator
+            && producer.as_ref() != Some(expected)
+        {
+            return Err(format!("当前槽位的出块人是 {}", expected));
         }
 
-        // 创建矿工奖励交易
-        let reward_transaction = Transaction::new(
-            "system".to_string(),
-            miner_address,
-            self.mining_reward,
-        );
+        // PoA 由授权节点签名出块，无需挖矿
+        let is_poa = matches!(self.consensus_type, ConsensusType::PoA);
+        let mut authority_vote = None;
+        if is_poa {
+            let poa = self.poa_consensus.as_ref().ok_or("PoA 共识未初始化")?;
+            poa.check_signer(&self.chain, &miner_address, chrono::Utc::now())?;
+            authority_vote = poa.next_vote(&miner_address);
+        }
+
+        // 按可花费余额筛选待处理交易，余额已不足的交易从交易池移除；
+        // 超出区块交易数或大小限制的交易留在交易池等待下一个区块（预留 coinbase 和区块头的空间）
+        let limits = self.chain_params.limits;
+        let mut block_bytes = BLOCK_OVERHEAD_BYTES;
+        let mut outgoing: HashMap<String, u64> = HashMap::new();
+        let mut selected = Vec::new();
+        let mut dropped = HashSet::new();
+        let (mut pos, mut dpos) = (self.pos_consensus.clone(), self.dpos_consensus.clone());
+        for transaction in &self.pending_transactions {
+            let transaction_bytes = serde_json::to_vec(transaction).map(|bytes| bytes.len() + 1).unwrap_or(usize::MAX);
+            if selected.len() + 1 >= limits.max_transactions
+                || block_bytes.saturating_add(transaction_bytes) > limits.max_block_bytes
+            {
+                break;
+            }
+            let cost = transaction.amount.saturating_add(transaction.fee);
+            let spent = outgoing.entry(transaction.sender.clone()).or_insert(0);
+            if self.spendable_balance(&transaction.sender).saturating_sub(*spent) < cost {
+                println!("⚠️ 交易 {} 的发送者可花费余额不足，已从交易池移除", transaction.id);
+                dropped.insert(transaction.id.clone());
+                continue;
+            }
+            // 质押交易按打包顺序试执行，已失效的（如候选人已注销）从交易池移除
+            if transaction.kind.is_staking()
+                && let Err(e) = Self::execute_staking(&self.consensus_type, pos.as_mut(), dpos.as_mut(), transaction, height)
+            {
+                println!("⚠️ 质押交易 {} 已失效（{}），已从交易池移除", transaction.id, e);
+                dropped.insert(transaction.id.clone());
+                continue;
+            }
+            *spent += cost;
+            block_bytes += transaction_bytes;
+            selected.push(transaction.clone());
+        }
+        self.pending_transactions.retain(|transaction| !dropped.contains(&transaction.id));
 
-        // 合并待处理交易和奖励交易
-        let mut transactions = self.pending_transactions.clone();
-        transactions.push(reward_transaction);
+        // 创建 coinbase 交易领取出块奖励和手续费（DPoS 下按佣金和委托比例分配给出块人和委托人）
+        let fees = selected.iter().map(|transaction| transaction.fee).sum();
+        let outputs = self.coinbase_outputs(&miner_address, fees);
+
+        // coinbase 交易必须是区块的第一笔交易
+        let mut transactions = vec![Transaction::new_coinbase(height, miner_address.clone(), outputs)];
+        transactions.extend(selected);
 
         // 创建新区块
         let prev_hash = self.get_latest_block().header.hash.clone();
-        let height = self.get_length() as u64;
 
+        let difficulty_rule = self.chain_params.rules_at(height).map(|rules| rules.difficulty_rule);
+        let difficulty = match difficulty_rule {
+            Some(DifficultyRule::Windowed) => self.expected_difficulty(height),
+            Some(DifficultyRule::CompactTarget) => 0,
+            _ if is_poa => 0,
+            _ => self.difficulty,
+        };
         let mut new_block = Block::new(
             prev_hash,
             transactions,
             height,
-            self.difficulty,
+            difficulty,
         );
+        if difficulty_rule == Some(DifficultyRule::CompactTarget) {
+            new_block.header.bits = self.expected_bits(height);
+        }
+        if let Some(median_time) = self.median_time_past(height)
+            && new_block.header.timestamp <= median_time
+        {
+            return Err(format!("本地时间早于最近 {} 个区块的中位时间 {}", MEDIAN_TIME_SPAN, median_time));
+        }
+        new_block.header.validator = producer.clone();
+        new_block.header.authority_vote = authority_vote;
+        new_block.header.hash = new_block.calculate_hash();
 
-        // 挖矿
-        new_block.mine();
+        Ok(BlockTemplate {
+            block: new_block,
+            producer,
+        })
+    }
 
-        // 添加区块到链
-        self.chain.push(new_block.clone());
+    /// 提交已挖出的区块模板：确认模板仍基于当前链顶后签名并上链
+    pub fn submit_block(&mut self, template: BlockTemplate) -> Result<Block, String> {
+        let BlockTemplate { block: mut new_block, producer } = template;
+        let height = new_block.height;
 
-        // 更新余额
-        self.update_balances();
+        if new_block.header.prev_hash != self.get_latest_block().header.hash || height != self.get_length() as u64 {
+            return Err("链顶已变化，区块模板已过期".to_string());
+        }
+        // 验证者使用本节点密钥签名区块头
+        if let Some(key) = &self.validator_key
+            && producer.as_ref() == Some(&key.address)
+        {
+            new_block.sign(&key.keypair());
+        }
 
-        // 调整难度
-        self.adjust_difficulty();
+        // 与网络上收到的区块一样校验后上链（更新余额和难度）
+        self.connect_block(new_block.clone())?;
 
-        // 清空待处理交易
-        self.pending_transactions.clear();
+        // 移除已打包的交易（挖矿期间新到达的交易保留在交易池）
+        let included: HashSet<&str> = new_block.transactions.iter().map(|tx| tx.id.as_str()).collect();
+        self.pending_transactions.retain(|tx| !included.contains(tx.id.as_str()));
 
         Ok(new_block)
     }
 
-    /// 更新账户余额
-    fn update_balances(&mut self) {
-        // 不清空余额，从当前余额开始更新
-        for block in &self.chain {
-            for transaction in &block.transactions {
-                if transaction.sender != "system" {
-                    let sender_balance = self.balances.get(&transaction.sender).unwrap_or(&0);
-                    self.balances.insert(
-                        transaction.sender.clone(),
-                        sender_balance - transaction.amount,
-                    );
-                }
+    /// 计划在指定高度切换共识算法的硬分叉（激活高度为下一个区块时立即生效）
+    pub fn schedule_consensus_fork(
+        &mut self,
+        consensus_type: ConsensusType,
+        activation_height: u64,
+        authority_keys: BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        let next_height = self.get_length() as u64;
+        let (mining_reward, difficulty_rule, coinbase_rule) = self.chain_params
+            .rules_at(activation_height)
+            .map(|rules| (rules.mining_reward, rules.difficulty_rule, rules.coinbase_rule))
+            .unwrap_or((self.mining_reward, DifficultyRule::Legacy, CoinbaseRule::Legacy));
+        let name = format!("{:?}@{}", consensus_type, activation_height);
+        let authorities = authority_keys.keys().cloned().collect();
+        let rules = RuleSet { consensus_type, mining_reward, authorities, authority_keys, difficulty_rule, coinbase_rule };
+
+        self.chain_params.schedule_fork(name, activation_height, rules, next_height)?;
+        self.apply_scheduled_rules();
+        Ok(())
+    }
+
+    /// 将下一个待出块高度生效的规则应用到当前共识状态
+    pub fn apply_scheduled_rules(&mut self) {
+        let next_height = self.get_length() as u64;
+        let Some(rules) = self.chain_params.rules_at(next_height).cloned() else {
+            return;
+        };
+
+        self.mining_reward = rules.mining_reward;
+        if self.consensus_type != rules.consensus_type {
+            println!("🔀 高度 {} 起激活 {:?} 共识规则", next_height, rules.consensus_type);
+            self.consensus_type = rules.consensus_type.clone();
+            self.activate_consensus_engine(&rules);
+        }
+
+        self.apply_governance_params();
+    }
+
+    /// 切换共识算法时初始化新的共识引擎并移除其他引擎（被移除引擎中锁定的质押全部退还）
+    fn activate_consensus_engine(&mut self, rules: &RuleSet) {
+        let mut locked = Vec::new();
+        if rules.consensus_type != ConsensusType::PoS
+            && let Some(pos) = self.pos_consensus.take()
+        {
+            locked.extend(pos.locked_funds());
+        }
+        if rules.consensus_type != ConsensusType::DPoS
+            && let Some(dpos) = self.dpos_consensus.take()
+        {
+            locked.extend(dpos.locked_funds());
+        }
+        for (address, amount) in locked {
+            self.release_stake(&address, amount);
+        }
+
+        match rules.consensus_type {
+            ConsensusType::PoW => {
+                self.pos_consensus = None;
+                self.dpos_consensus = None;
+                self.poa_consensus = None;
+            }
+            ConsensusType::PoS => {
+                let pos = self.pos_consensus.get_or_insert_with(|| ProofOfStake::new(100)); // 最小质押100
+                pos.slashing.config = self.chain_params.slashing.clone();
+                self.dpos_consensus = None;
+                self.poa_consensus = None;
+            }
+            ConsensusType::DPoS => {
+                self.pos_consensus = None;
+                let dpos = self.dpos_consensus.get_or_insert_with(|| DelegatedProofOfStake::new(1000, 100)); // 最小质押1000，委托100
+                dpos.slashing.config = self.chain_params.slashing.clone();
+                self.poa_consensus = None;
+            }
+            ConsensusType::PoA => {
+                self.pos_consensus = None;
+                self.dpos_consensus = None;
+                self.poa_consensus.get_or_insert_with(|| {
+                    let mut poa = ProofOfAuthority::new(rules.authorities.clone());
+                    poa.authority_keys = rules.authority_keys.clone().into_iter().collect();
+                    poa
+                });
+            }
+        }
+    }
+
+    /// 将下一个待出块高度已生效的治理参数应用到当前链状态（挖矿奖励以最近的硬分叉和治理修改中较晚者为准）
+    fn apply_governance_params(&mut self) {
+        let next_height = self.get_length() as u64;
+        let fork_height = self.chain_params.fork_at(next_height).map(|fork| fork.activation_height).unwrap_or(0);
+        if let Some((activation_height, value)) = self.governance.applied_at(GovernanceParam::MiningReward, next_height)
+            && activation_height >= fork_height
+        {
+            self.mining_reward = value;
+        }
+
+        if let Some((_, value)) = self.governance.applied_at(GovernanceParam::MinimumStake, next_height) {
+            if let Some(pos) = &mut self.pos_consensus {
+                pos.minimum_stake = value;
+            }
+            if let Some(dpos) = &mut self.dpos_consensus {
+                dpos.minimum_stake = value;
+            }
+        }
+        if let Some((_, value)) = self.governance.applied_at(GovernanceParam::MinimumDelegation, next_height)
+            && let Some(dpos) = &mut self.dpos_consensus
+        {
+            dpos.minimum_delegation = value;
+        }
+    }
+
+    /// 处理新区块中的治理交易，统计投票期结束的提案并激活到期的参数修改
+    fn process_governance(&mut self, block: &Block) {
+        self.governance.apply_block(block);
+
+        let total_power = self.total_voting_power();
+        let voting_power: HashMap<String, u64> = self.governance.proposals
+            .values()
+            .filter(|proposal| proposal.status == ProposalStatus::Voting)
+            .flat_map(|proposal| proposal.votes.keys())
+            .map(|voter| (voter.clone(), self.voting_power(voter)))
+            .collect();
+        for id in self.governance.tally(block.height, total_power, |voter| voting_power.get(voter).copied().unwrap_or(0)) {
+            if let Some(proposal) = self.governance.proposals.get(&id) {
+                println!("🗳️ 提案 {} 投票结束: {:?} (赞成 {} / 反对 {})", id, proposal.status, proposal.yes_power, proposal.no_power);
+            }
+        }
 
-                if transaction.receiver != "genesis" {
-                    let receiver_balance = self.balances.get(&transaction.receiver).unwrap_or(&0);
-                    self.balances.insert(
-                        transaction.receiver.clone(),
-                        receiver_balance + transaction.amount,
-                    );
+        for change in self.governance.activate(block.height + 1) {
+            println!("🏛️ 治理参数 {:?} 自高度 {} 起修改为 {}", change.param, block.height + 1, change.value);
+        }
+    }
+
+    /// 地址的治理投票权重：PoS/DPoS 按质押（含委托）计算，其他共识按账户余额计算
+    pub fn voting_power(&self, address: &str) -> u64 {
+        match (&self.consensus_type, &self.pos_consensus, &self.dpos_consensus) {
+            (ConsensusType::PoS, Some(pos), _) => pos.stakes.get(address).map(|stake| stake.amount).unwrap_or(0),
+            (ConsensusType::DPoS, _, Some(dpos)) => {
+                let own_stake = dpos.stakes.get(address).map(|stake| stake.amount).unwrap_or(0);
+                let delegated: u64 = dpos.delegations
+                    .values()
+                    .filter(|delegation| delegation.delegator == address)
+                    .map(|delegation| delegation.amount)
+                    .sum();
+                own_stake + delegated
+            }
+            _ if address == "system" => 0,
+            _ => self.get_balance(address),
+        }
+    }
+
+    /// 全网治理投票权重总和
+    pub fn total_voting_power(&self) -> u64 {
+        match (&self.consensus_type, &self.pos_consensus, &self.dpos_consensus) {
+            (ConsensusType::PoS, Some(pos), _) => pos.stakes.values().map(|stake| stake.amount).sum(),
+            (ConsensusType::DPoS, _, Some(dpos)) => {
+                dpos.stakes.values().map(|stake| stake.amount).sum::<u64>()
+                    + dpos.delegations.values().map(|delegation| delegation.amount).sum::<u64>()
+            }
+            _ => self.balances
+                .iter()
+                .filter(|(address, _)| address.as_str() != "system")
+                .map(|(_, balance)| balance)
+                .sum(),
+        }
+    }
+
+    /// 到达纪元边界（或尚无当选出块人）时为下一高度重新选举 DPoS 出块人并洗牌
+    fn refresh_producer_schedule(&mut self) {
+        let schedule = match &self.dpos_consensus {
+            Some(dpos) => dpos.current_schedule(self),
+            None => return,
+        };
+
+        if let Some(dpos) = &mut self.dpos_consensus
+            && (dpos.schedule.epoch_start != schedule.epoch_start || dpos.schedule.producers != schedule.producers)
+        {
+            println!("🗳️ 纪元 {} 出块人: {:?}", schedule.epoch_start, schedule.producers);
+            dpos.schedule = schedule;
+        }
+    }
+
+    /// 指定高度的基础出块奖励（以该高度生效的硬分叉和治理修改中较晚者为准）
+    fn base_reward_at(&self, height: u64) -> u64 {
+        let fork = self.chain_params.fork_at(height);
+        let fork_height = fork.map(|fork| fork.activation_height).unwrap_or(0);
+        let fork_reward = fork.map(|fork| fork.rules.mining_reward).unwrap_or(self.mining_reward);
+        match self.governance.applied_at(GovernanceParam::MiningReward, height) {
+            Some((activation_height, value)) if activation_height >= fork_height => value,
+            _ => fork_reward,
+        }
+    }
+
+    /// 下一个区块的出块奖励：当前基础奖励按减半周期递减，且不超过剩余可发行量
+    pub fn block_subsidy(&self) -> u64 {
+        let height = self.get_length() as u64;
+        let current_supply = self.chain_params.monetary.genesis_supply().saturating_add(self.issued_supply());
+        self.chain_params.monetary.subsidy(self.base_reward_at(height), height, current_supply)
+    }
+
+    /// 计算出块奖励（出块奖励加手续费）的分配（PoW/PoS 全部归出块者）
+    pub fn reward_shares(&self, producer: &str, fees: u64) -> Vec<RewardShare> {
+        let reward = self.block_subsidy().saturating_add(fees);
+        match (&self.consensus_type, &self.dpos_consensus) {
+            (ConsensusType::DPoS, Some(dpos)) => dpos.distribute_reward(producer, reward),
+            _ => vec![RewardShare {
+                address: producer.to_string(),
+                amount: reward,
+                kind: RewardKind::Block,
+            }],
+        }
+    }
+
+    /// 按奖励分配生成 coinbase 输出（省略金额为 0 的份额）
+    fn coinbase_outputs(&self, producer: &str, fees: u64) -> Vec<CoinbaseOutput> {
+        self.reward_shares(producer, fees)
+            .into_iter()
+            .filter(|share| share.amount > 0)
+            .map(|share| CoinbaseOutput { address: share.address, amount: share.amount })
+            .collect()
+    }
+
+    /// 由 PoS/DPoS 区块的 coinbase 输出记录质押奖励（出块人的输出按出块时的佣金比例区分佣金和出块奖励）
+    fn record_staking_rewards(&mut self, block: &Block) {
+        let Some(validator) = &block.header.validator else {
+            return;
+        };
+        let commission_rate = match (&self.consensus_type, &self.dpos_consensus) {
+            (ConsensusType::PoS, _) => 0,
+            (ConsensusType::DPoS, Some(dpos)) => dpos.commission_rate(validator),
+            _ => return,
+        };
+        let Some(TransactionKind::Coinbase { outputs, .. }) = block.transactions.first().map(|tx| &tx.kind) else {
+            return;
+        };
+
+        for output in outputs {
+            let kind = match (output.address == *validator, commission_rate > 0) {
+                (false, _) => RewardKind::Delegation,
+                (true, true) => RewardKind::Commission,
+                (true, false) => RewardKind::Block,
+            };
+            self.staking_rewards
+                .entry(output.address.clone())
+                .or_default()
+                .push(StakingReward {
+                    height: block.height,
+                    validator: validator.clone(),
+                    amount: output.amount,
+                    kind,
+                });
+        }
+    }
+
+    /// 获取地址的质押奖励历史
+    pub fn get_staking_rewards(&self, address: &str) -> &[StakingReward] {
+        self.staking_rewards.get(address).map(|r| r.as_slice()).unwrap_or(&[])
+    }
+
+    /// 获取地址累计获得的质押奖励
+    pub fn get_total_staking_rewards(&self, address: &str) -> u64 {
+        self.get_staking_rewards(address).iter().map(|r| r.amount).sum()
+    }
+
+    /// 释放解绑期在指定高度已满的 DPoS 质押和委托，金额从质押地址退还
+    fn release_unbonded_delegations(&mut self, height: u64) {
+        let released = match &mut self.dpos_consensus {
+            Some(dpos) => dpos.process_unbonding(height),
+            None => return,
+        };
+        for entry in released {
+            self.release_stake(&entry.delegator, entry.amount);
+            println!("🔓 {} 对 {} 的 {} 已完成解绑", entry.delegator, entry.candidate, entry.amount);
+        }
+    }
+
+    /// 当前共识算法排定的下一个出块验证者（PoW 下为 None）
+    pub fn expected_validator(&self) -> Option<String> {
+        match self.consensus_type {
+            ConsensusType::PoW => None,
+            ConsensusType::PoS => self.pos_consensus.as_ref()?.select_validator(self),
+            ConsensusType::DPoS => self.dpos_consensus.as_ref()?.select_validator(self),
+            ConsensusType::PoA => self.poa_consensus.as_ref()?.select_validator(self),
+        }
+    }
+
+    /// 获取当前共识算法的罚没模块
+    pub fn slashing(&self) -> Option<&Slashing> {
+        match self.consensus_type {
+            ConsensusType::PoW | ConsensusType::PoA => None,
+            ConsensusType::PoS => self.pos_consensus.as_ref().map(|pos| &pos.slashing),
+            ConsensusType::DPoS => self.dpos_consensus.as_ref().map(|dpos| &dpos.slashing),
+        }
+    }
+
+    fn slashing_mut(&mut self) -> Option<&mut Slashing> {
+        match self.consensus_type {
+            ConsensusType::PoW | ConsensusType::PoA => None,
+            ConsensusType::PoS => self.pos_consensus.as_mut().map(|pos| &mut pos.slashing),
+            ConsensusType::DPoS => self.dpos_consensus.as_mut().map(|dpos| &mut dpos.slashing),
+        }
+    }
+
+    /// 按区块头统计出块槽位（PoS 为以链顶哈希选出的验证者，DPoS 为区块时间戳之前被跳过槽位的出块人和出块人本身），
+    /// 连续漏块达到阈值时执行停机罚没；在区块上链前调用，所有节点由链上区块得到相同结果
+    ///
+    /// 只统计由出块人登记的签名公钥签名的区块，未登记公钥时的引导区块不会让其他验证者漏块
+    fn record_validator_slots(&mut self, block: &Block) {
+        let Some(producer) = block.header.validator.clone() else {
+            return;
+        };
+        let expected: Vec<String> = match (&self.consensus_type, &self.pos_consensus, &self.dpos_consensus) {
+            (ConsensusType::PoS, Some(pos), _) if signed_by_registered_key(&pos.stakes, block) => {
+                pos.select_validator(self).into_iter().collect()
+            }
+            (ConsensusType::DPoS, _, Some(dpos)) if signed_by_registered_key(&dpos.stakes, block) => {
+                let mut slots = dpos.skipped_producers(self.get_latest_block(), block);
+                slots.push(producer.clone());
+                slots
+            }
+            _ => return,
+        };
+
+        for expected in expected {
+            let evidence = match self.slashing_mut() {
+                Some(slashing) => slashing.record_slot(&expected, &producer),
+                None => return,
+            };
+            let Some(evidence) = evidence else {
+                continue;
+            };
+
+            let burned_before = self.slashing().map(|slashing| slashing.total_burned).unwrap_or(0);
+            let result = match (&self.consensus_type, &mut self.pos_consensus, &mut self.dpos_consensus) {
+                (ConsensusType::PoS, Some(pos), _) => pos.submit_evidence(&evidence, block.height),
+                (ConsensusType::DPoS, _, Some(dpos)) => dpos.submit_evidence(&evidence, block.height),
+                _ => return,
+            };
+            match result {
+                Ok(amount) => println!("⚔️ 验证者 {} 因停机被罚没 {}", expected, amount),
+                Err(e) => println!("❌ 停机罚没失败: {}", e),
+            }
+            self.burn_slashed_stake(burned_before);
+        }
+    }
+
+    /// 当前参与最终性投票的验证者集合（PoS 为全部未监禁质押者，DPoS 为当选出块人，PoA 为授权节点）
+    pub fn validator_weights(&self) -> HashMap<String, ValidatorWeight> {
+        let height = self.get_length() as u64;
+        match self.consensus_type {
+            ConsensusType::PoW => HashMap::new(),
+            ConsensusType::PoS => match &self.pos_consensus {
+                Some(pos) => pos.stakes
+                    .iter()
+                    .filter(|(v, _)| !pos.slashing.is_jailed(v, height))
+                    .map(|(v, s)| (v.clone(), ValidatorWeight {
+                        weight: s.amount,
+                        public_key: s.public_key.clone(),
+                    }))
+                    .collect(),
+                None => HashMap::new(),
+            },
+            ConsensusType::DPoS => match &self.dpos_consensus {
+                Some(dpos) => dpos.current_schedule(self)
+                    .producers
+                    .iter()
+                    .map(|p| (p.clone(), ValidatorWeight {
+                        weight: dpos.calculate_candidate_weight(p),
+                        public_key: dpos.stakes.get(p).and_then(|s| s.public_key.clone()),
+                    }))
+                    .collect(),
+                None => HashMap::new(),
+            },
+            ConsensusType::PoA => match &self.poa_consensus {
+                Some(poa) => poa.authorities
+                    .iter()
+                    .map(|a| (a.clone(), ValidatorWeight {
+                        weight: 1,
+                        public_key: poa.authority_keys.get(a).cloned(),
+                    }))
+                    .collect(),
+                None => HashMap::new(),
+            },
+        }
+    }
+
+    /// 本节点验证者对本地链指定高度的区块签名投票（未登记密钥或已投过则返回 None）
+    fn sign_vote(&self, vote_type: VoteType, height: u64) -> Option<Vote> {
+        let key = self.validator_key.as_ref()?;
+        let block = self.chain.get(height as usize)?;
+        if self.finality.is_finalized(height)
+            || self.finality.has_voted(height, vote_type, &key.address)
+            || !self.validator_weights().contains_key(&key.address)
+        {
+            return None;
+        }
+
+        Some(Vote::new_signed(vote_type, height, block.header.hash.clone(), key))
+    }
+
+    /// 处理收到的投票，返回本节点因此产生、需要广播的新投票
+    pub fn process_vote(&mut self, vote: Vote) -> Result<Vec<Vote>, String> {
+        let validators = self.validator_weights();
+        let mut own_votes = Vec::new();
+        let mut queue = vec![vote];
+
+        while let Some(vote) = queue.pop() {
+            let local_hash = self.chain.get(vote.height as usize).map(|b| b.header.hash.clone());
+            match self.finality.add_vote(vote, &validators, local_hash.as_deref())? {
+                VoteOutcome::Recorded => {}
+                VoteOutcome::Polka { height, block_hash } => {
+                    // 预投票达到 2/3 后，若本地链是同一区块则预提交
+                    if local_hash.as_deref() == Some(block_hash.as_str())
+                        && let Some(precommit) = self.sign_vote(VoteType::Precommit, height)
+                    {
+                        own_votes.push(precommit.clone());
+                        queue.push(precommit);
+                    }
+                }
+                VoteOutcome::Finalized { height, block_hash } => {
+                    println!("🔒 区块 {} 已最终确认: {}", height, block_hash);
                 }
             }
         }
+
+        Ok(own_votes)
+    }
+
+    /// 本节点验证者对最新区块预投票，返回需要广播的投票
+    pub fn prevote_latest(&mut self) -> Vec<Vote> {
+        let height = self.get_length() as u64 - 1;
+        let prevote = match self.sign_vote(VoteType::Prevote, height) {
+            Some(vote) => vote,
+            None => return Vec::new(),
+        };
+
+        let mut votes = vec![prevote.clone()];
+        match self.process_vote(prevote) {
+            Ok(follow_up) => votes.extend(follow_up),
+            Err(e) => println!("❌ 预投票失败: {}", e),
+        }
+        votes
+    }
+
+    /// 以作恶证据交易提交证据到交易池（证据在交易上链时核验，通过后所有节点罚没并监禁验证者）
+    pub fn submit_evidence(&mut self, reporter: String, evidence: Evidence) -> Result<Transaction, String> {
+        let transaction = Transaction::new_staking(reporter, TransactionKind::Evidence { evidence }, 0);
+        self.add_transaction(transaction.clone())?;
+        Ok(transaction)
+    }
+
+    /// 检查收到的区块是否与本地同高度区块构成双签，是则返回证据
+    pub fn detect_double_sign(&self, block: &Block) -> Option<Evidence> {
+        let existing = self.chain.get(block.height as usize)?;
+        let validator = block.header.validator.as_ref()?;
+
+        if existing.header.validator.as_ref() != Some(validator)
+            || existing.header.hash == block.header.hash
+            || !existing.header.verify_signature(block.height)
+            || !block.header.verify_signature(block.height)
+        {
+            return None;
+        }
+
+        Some(Evidence::DoubleSign {
+            validator: validator.clone(),
+            height: block.height,
+            first: Box::new(existing.header.clone()),
+            second: Box::new(block.header.clone()),
+        })
+    }
+
+    /// 将一笔交易记入余额表
+    fn apply_transaction(balances: &mut HashMap<String, u64>, transaction: &Transaction) {
+        // 系统账户转出为新发行，不扣减；其他发送者扣减金额和手续费
+        if transaction.sender != ISSUER_ADDRESS {
+            let sender_balance = balances.entry(transaction.sender.clone()).or_insert(0);
+            *sender_balance = sender_balance.saturating_sub(transaction.amount.saturating_add(transaction.fee));
+        }
+
+        // 转入销毁地址的代币退出流通
+        for (address, amount) in transaction.credits() {
+            if address != "genesis" && address != BURN_ADDRESS {
+                *balances.entry(address.to_string()).or_insert(0) += amount;
+            }
+        }
+    }
+
+    /// 地址尚未成熟的 coinbase 奖励
+    pub fn immature_balance(&self, address: &str) -> u64 {
+        let maturity = self.chain_params.monetary.coinbase_maturity;
+        self.supply.immature(address, self.get_length() as u64, maturity)
+    }
+
+    /// 地址在下一个区块中可花费的余额（扣除未成熟的 coinbase 奖励）
+    pub fn spendable_balance(&self, address: &str) -> u64 {
+        self.get_balance(address).saturating_sub(self.immature_balance(address))
+    }
+
+    /// 链上累计新发行量（不含创世分配）
+    pub fn issued_supply(&self) -> u64 {
+        self.supply.issued
+    }
+
+    /// 供应审计：账户余额总和应等于创世分配加累计发行减去销毁，且不超过供应上限
+    pub fn supply_audit(&self) -> SupplyAudit {
+        let policy = &self.chain_params.monetary;
+        let (issued, burned) = (self.supply.issued, self.supply.burned);
+        let genesis_allocated = policy.genesis_supply();
+        let expected_supply = genesis_allocated.saturating_add(issued).saturating_sub(burned);
+        let actual_supply = self.balances.values().sum();
+        SupplyAudit {
+            genesis_allocated,
+            issued,
+            burned,
+            expected_supply,
+            actual_supply,
+            max_supply: policy.max_supply,
+            balanced: expected_supply == actual_supply,
+            within_cap: genesis_allocated.saturating_add(issued) <= policy.max_supply,
+        }
     }
 
     /// 获取账户余额
     pub fn get_balance(&self, address: &str) -> u64 {
-        self.balances.get(address).unwrap_or(&0).clone()
+        *self.balances.get(address).unwrap_or(&0)
     }
 
-    /// 验证区块链完整性
+    /// 验证区块链完整性：从创世状态起逐个重新连接全部区块
     pub fn is_chain_valid(&self) -> bool {
-        for i in 1..self.chain.len() {
-            let current_block = &self.chain[i];
-            let previous_block = &self.chain[i - 1];
+        let mut replay = self.clone();
+        let mut blocks = std::mem::take(&mut replay.chain).into_iter();
+        replay.chain.extend(blocks.next());
+        replay.finality = FinalityGadget::default();
+        replay.rebuild_state();
 
-            // 验证当前区块
-            if !current_block.is_valid(&previous_block.header.hash) {
+        for block in blocks {
+            let height = block.height;
+            if let Err(e) = replay.connect_block(block) {
+                println!("❌ 区块 {} 无效: {}", height, e);
                 return false;
             }
+        }
+        true
+    }
 
-            // 验证哈希链
-            if current_block.header.prev_hash != previous_block.header.hash {
-                return false;
+    /// 按当前链状态验证下一个区块（余额、发行量和难度均为该区块之前的状态）
+    fn check_block(&self, block: &Block) -> Result<(), String> {
+        let previous_block = self.get_latest_block();
+        if block.height != self.get_length() as u64 {
+            return Err("区块高度不连续".to_string());
+        }
+        if !block.is_valid(&previous_block.header.hash) {
+            return Err("区块哈希无效或不连接到前一个区块".to_string());
+        }
+
+        // 区块交易数和大小不能超过限制
+        let limits = &self.chain_params.limits;
+        if block.transactions.len() > limits.max_transactions || block.size() > limits.max_block_bytes {
+            return Err("区块超过区块限制".to_string());
+        }
+
+        // 时间戳必须晚于中位时间且不能超前本地时间太多
+        let timestamp = block.header.timestamp;
+        if self.median_time_past(block.height).is_some_and(|median_time| timestamp <= median_time)
+            || timestamp > chrono::Utc::now() + chrono::Duration::seconds(MAX_FUTURE_BLOCK_TIME_SECS)
+        {
+            return Err("区块时间戳无效".to_string());
+        }
+        // DPoS 按时间戳计算被跳过的槽位并计入停机，时间戳最多只能超前本地时间一个槽位
+        if let Some(dpos) = &self.dpos_consensus
+            && self.chain_params.rules_at(block.height).is_some_and(|rules| rules.consensus_type == ConsensusType::DPoS)
+            && timestamp > chrono::Utc::now() + chrono::Duration::seconds(dpos.schedule.slot_duration_secs)
+        {
+            return Err("区块时间戳超前本地时间超过一个出块槽位".to_string());
+        }
+
+        // 按该高度生效的硬分叉规则验证区块
+        if let Some(rules) = self.chain_params.rules_at(block.height) {
+            if !Self::satisfies_rules(block, rules) {
+                return Err(format!("区块不符合 {:?} 共识规则", rules.consensus_type));
+            }
+            if rules.consensus_type == ConsensusType::PoS {
+                let pos = self.pos_consensus.as_ref().ok_or("PoS 共识未初始化")?;
+                if !pos.validate_block(block, self.get_latest_block()) {
+                    return Err("出块验证者处于监禁期或区块未由其登记的签名公钥签名".to_string());
+                }
+            }
+            if rules.consensus_type == ConsensusType::DPoS {
+                let dpos = self.dpos_consensus.as_ref().ok_or("DPoS 共识未初始化")?;
+                if !dpos.validate_block(block, self.get_latest_block()) {
+                    return Err("出块人不是区块时间戳所在槽位排定的出块人，或区块未由其登记的签名公钥签名".to_string());
+                }
+            }
+            if rules.consensus_type == ConsensusType::PoA {
+                self.check_authority_block(block)?;
+            }
+            let difficulty_valid = match rules.difficulty_rule {
+                DifficultyRule::Legacy => true,
+                DifficultyRule::Windowed => block.header.difficulty == self.expected_difficulty(block.height),
+                DifficultyRule::CompactTarget => {
+                    block.header.difficulty == 0 && block.header.bits == self.expected_bits(block.height)
+                }
+            };
+            if !difficulty_valid {
+                return Err("区块难度不符合规则".to_string());
+            }
+            if rules.coinbase_rule == CoinbaseRule::Enforced {
+                self.check_coinbase_rules(block)?;
             }
         }
-        true
+        self.check_staking_transactions(&block.transactions, block.height)
+    }
+
+    /// 验证 PoA 区块：由登记了公钥的授权节点签名，符合签名间隔和轮值规则（按区块头时间戳），
+    /// 加入授权节点的投票必须携带目标节点公钥
+    fn check_authority_block(&self, block: &Block) -> Result<(), String> {
+        let poa = self.poa_consensus.as_ref().ok_or("PoA 共识未初始化")?;
+        if !poa.validate_block(block, self.get_latest_block()) {
+            return Err("区块不是由登记了公钥的授权节点签名".to_string());
+        }
+        let signer = block.header.validator.as_deref().unwrap_or_default();
+        poa.check_signer(&self.chain, signer, block.header.timestamp)?;
+        if let Some(vote) = &block.header.authority_vote
+            && vote.authorize
+            && vote.public_key.is_none()
+        {
+            return Err("加入授权节点的投票缺少签名公钥".to_string());
+        }
+        Ok(())
+    }
+
+    /// 验证区块的 coinbase 规则：首笔交易为 coinbase 且奖励不超过出块奖励加手续费（DPoS 下输出必须与奖励分配一致），
+    /// 其他交易不能由系统账户发出，发送者不能花费未成熟的 coinbase 奖励
+    fn check_coinbase_rules(&self, block: &Block) -> Result<(), String> {
+        let (coinbase, transactions) = block.transactions.split_first().ok_or("区块缺少 coinbase 交易")?;
+        let outputs = match &coinbase.kind {
+            TransactionKind::Coinbase { height, outputs } if *height == block.height => {
+                if coinbase.sender != ISSUER_ADDRESS || coinbase.fee != 0 {
+                    return Err("coinbase 交易格式无效".to_string());
+                }
+                if outputs.iter().map(|output| output.amount).sum::<u64>() != coinbase.amount {
+                    return Err("coinbase 输出总额与交易金额不一致".to_string());
+                }
+                outputs
+            }
+            _ => return Err("第一笔交易必须是本高度的 coinbase 交易".to_string()),
+        };
+
+        if transactions.iter().any(|tx| tx.is_coinbase() || tx.sender == ISSUER_ADDRESS) {
+            return Err("coinbase 交易只能是区块的第一笔交易".to_string());
+        }
+
+        let policy = &self.chain_params.monetary;
+        let fees: u64 = transactions.iter().map(|tx| tx.fee).sum();
+        let current_supply = policy.genesis_supply().saturating_add(self.supply.issued);
+        let subsidy = policy.subsidy(self.base_reward_at(block.height), block.height, current_supply);
+        if coinbase.amount > subsidy.saturating_add(fees) {
+            return Err(format!("coinbase 金额 {} 超过出块奖励 {} 加手续费 {}", coinbase.amount, subsidy, fees));
+        }
+        // DPoS 奖励必须按佣金和委托比例完整分配，出块人不能截留委托人的份额
+        if self.consensus_type == ConsensusType::DPoS {
+            let producer = block.header.validator.as_deref().unwrap_or_default();
+            if *outputs != self.coinbase_outputs(producer, fees) {
+                return Err("coinbase 输出与 DPoS 奖励分配不一致".to_string());
+            }
+        }
+
+        // 按交易顺序扣减，发送者只能花费已成熟的余额
+        let mut outgoing: HashMap<&str, u64> = HashMap::new();
+        for tx in transactions {
+            let spent = outgoing.entry(tx.sender.as_str()).or_insert(0);
+            *spent = spent.saturating_add(tx.amount.saturating_add(tx.fee));
+            let immature = self.supply.immature(&tx.sender, block.height, policy.coinbase_maturity);
+            let spendable = self.get_balance(&tx.sender).saturating_sub(immature);
+            if *spent > spendable {
+                return Err(format!("{} 的可花费余额 {} 不足以支付 {}", tx.sender, spendable, spent));
+            }
+        }
+        Ok(())
+    }
+
+    /// 区块是否符合指定共识规则
+    fn satisfies_rules(block: &Block, rules: &RuleSet) -> bool {
+        let header = &block.header;
+        let signature_valid = header.verify_signature(block.height);
+        match rules.consensus_type {
+            // PoW 区块必须经过工作量证明
+            ConsensusType::PoW => header.difficulty > 0 || header.bits != 0,
+            // PoS/DPoS 区块必须记录验证者并由验证者签名
+            ConsensusType::PoS | ConsensusType::DPoS => header.validator.is_some() && signature_valid,
+            // PoA 区块必须由授权节点签名且不挖矿
+            ConsensusType::PoA => {
+                header.validator.is_some() && header.difficulty == 0 && header.bits == 0 && signature_valid
+            }
+        }
+    }
+
+    /// 指定高度之前最近 MEDIAN_TIME_SPAN 个区块时间戳的中位数
+    pub fn median_time_past(&self, height: u64) -> Option<chrono::DateTime<chrono::Utc>> {
+        let end = (height as usize).min(self.chain.len());
+        if end == 0 {
+            return None;
+        }
+        let mut timestamps: Vec<_> = self.chain[end.saturating_sub(MEDIAN_TIME_SPAN)..end]
+            .iter()
+            .map(|block| block.header.timestamp)
+            .collect();
+        timestamps.sort();
+        Some(timestamps[timestamps.len() / 2])
+    }
+
+    /// 计算指定高度区块应使用的难度，只依赖链上数据，其他节点可独立验证
+    ///
+    /// 每 RETARGET_INTERVAL 个区块根据窗口实际耗时调整一次；难度每增加 1 工作量增加 16 倍，
+    /// 因此仅当耗时偏离目标超过 4 倍（对数意义上的一半步长）时才调整
+    pub fn expected_difficulty(&self, height: u64) -> u32 {
+        self.difficulty_with(height, |index| self.chain.get(index).map(|block| &block.header))
+    }
+
+    /// 按 header_at 提供的区块头（第 index 个区块的区块头）计算指定高度应使用的难度
+    fn difficulty_with<'a>(&self, height: u64, header_at: impl Fn(usize) -> Option<&'a BlockHeader>) -> u32 {
+        if matches!(self.chain_params.rules_at(height).map(|rules| &rules.consensus_type), Some(ConsensusType::PoA)) {
+            return 0;
+        }
+
+        let genesis_difficulty = header_at(0).map(|header| header.difficulty).unwrap_or(self.difficulty);
+        let height = height as usize;
+        let Some(parent) = height.checked_sub(1).and_then(&header_at) else {
+            return genesis_difficulty;
+        };
+
+        // PoA 区块难度为 0，切换回挖矿共识时从创世难度重新开始
+        let parent_difficulty = match parent.difficulty {
+            0 => genesis_difficulty.max(1),
+            difficulty => difficulty,
+        };
+
+        let interval = RETARGET_INTERVAL as usize;
+        if !height.is_multiple_of(interval) || height <= interval {
+            return parent_difficulty;
+        }
+
+        let Some(first) = header_at(height - 1 - interval) else {
+            return parent_difficulty;
+        };
+        let actual_secs = (parent.timestamp - first.timestamp).num_seconds().max(1);
+        let expected_secs = TARGET_BLOCK_TIME_SECS * RETARGET_INTERVAL as i64;
+
+        if actual_secs * 4 < expected_secs {
+            (parent_difficulty + 1).min(MAX_DIFFICULTY)
+        } else if actual_secs > expected_secs * 4 {
+            parent_difficulty.saturating_sub(1).max(1)
+        } else {
+            parent_difficulty
+        }
+    }
+
+    /// 计算指定高度区块应使用的目标值紧凑编码，只依赖链上数据，其他节点可独立验证
+    ///
+    /// 每 RETARGET_INTERVAL 个区块按窗口实际耗时与目标耗时的比例缩放目标值
+    pub fn expected_bits(&self, height: u64) -> u32 {
+        self.bits_with(height, |index| self.chain.get(index).map(|block| &block.header))
+    }
+
+    /// 按 header_at 提供的区块头（第 index 个区块的区块头）计算指定高度应使用的目标值紧凑编码
+    fn bits_with<'a>(&self, height: u64, header_at: impl Fn(usize) -> Option<&'a BlockHeader>) -> u32 {
+        if matches!(self.chain_params.rules_at(height).map(|rules| &rules.consensus_type), Some(ConsensusType::PoA)) {
+            return 0;
+        }
+
+        // 创世区块和旧版区块没有目标值，按十六进制前导零难度换算
+        let genesis_bits = header_at(0)
+            .map(|header| pow::difficulty_to_bits(header.difficulty))
+            .unwrap_or(pow::POW_LIMIT_BITS);
+        let height = height as usize;
+        let Some(parent) = height.checked_sub(1).and_then(&header_at) else {
+            return genesis_bits;
+        };
+
+        let parent_bits = match (parent.bits, parent.difficulty) {
+            (0, 0) => genesis_bits,
+            (0, difficulty) => pow::difficulty_to_bits(difficulty),
+            (bits, _) => bits,
+        };
+
+        let interval = RETARGET_INTERVAL as usize;
+        if !height.is_multiple_of(interval) || height <= interval {
+            return parent_bits;
+        }
+
+        let Some(first) = header_at(height - 1 - interval) else {
+            return parent_bits;
+        };
+        let actual_secs = (parent.timestamp - first.timestamp).num_seconds();
+        pow::retarget(parent_bits, actual_secs, TARGET_BLOCK_TIME_SECS * RETARGET_INTERVAL as i64)
+    }
+
+    /// 区块链的累计工作量
+    pub fn total_work(chain: &[Block]) -> u128 {
+        chain.iter().fold(0u128, |total, block| total.saturating_add(block.work()))
     }
 
     /// 调整挖矿难度（基于区块生成时间）
     pub fn adjust_difficulty(&mut self) {
+        let next_height = self.get_length() as u64;
+        if let Some(rules) = self.chain_params.rules_at(next_height)
+            && rules.difficulty_rule != DifficultyRule::Legacy
+        {
+            if rules.consensus_type != ConsensusType::PoA {
+                self.difficulty = match rules.difficulty_rule {
+                    DifficultyRule::CompactTarget => pow::leading_zero_digits(self.expected_bits(next_height)),
+                    _ => self.expected_difficulty(next_height),
+                };
+            }
+            return;
+        }
+
         if self.chain.len() < 2 {
             return; // 创世区块后第一个区块不调整
         }
@@ -198,34 +1456,6 @@ impl Blockchain {
         }
     }
 
-    /// 共识机制：验证并替换为更长的有效链
-    #[allow(dead_code)]
-    pub fn replace_chain(&mut self, new_chain: Vec<Block>) -> bool {
-        if new_chain.len() <= self.chain.len() {
-            return false; // 新链不更长
-        }
-
-        // 创建临时区块链验证新链
-        let temp_blockchain = Blockchain {
-            chain: new_chain,
-            pending_transactions: Vec::new(),
-            difficulty: self.difficulty,
-            mining_reward: self.mining_reward,
-            balances: HashMap::new(),
-            consensus_type: self.consensus_type.clone(),
-            pos_consensus: self.pos_consensus.clone(),
-            dpos_consensus: self.dpos_consensus.clone(),
-        };
-
-        if temp_blockchain.is_chain_valid() {
-            self.chain = temp_blockchain.chain;
-            self.update_balances();
-            self.adjust_difficulty(); // 基于新链调整难度
-            return true;
-        }
-        false
-    }
-
     /// 获取区块链的总交易数
     pub fn get_total_transactions(&self) -> usize {
         self.chain.iter().map(|block| block.transactions.len()).sum()
@@ -237,7 +1467,8 @@ impl Blockchain {
         println!("区块链长度: {}", self.get_length());
         println!("总交易数: {}", self.get_total_transactions());
         println!("挖矿难度: {}", self.difficulty);
-        println!("挖矿奖励: {}", self.mining_reward);
+        println!("累计工作量: {}", Self::total_work(&self.chain));
+        println!("挖矿奖励: {} (下一区块: {})", self.mining_reward, self.block_subsidy());
         println!("待处理交易: {}", self.pending_transactions.len());
         println!("区块链有效性: {}", self.is_chain_valid());
 
@@ -252,6 +1483,12 @@ impl Blockchain {
                 println!("{}: {}", address, balance);
             }
         }
+
+        let audit = self.supply_audit();
+        println!("\n=== 供应审计 ===");
+        println!("创世分配: {}, 累计发行: {}, 已销毁: {}", audit.genesis_allocated, audit.issued, audit.burned);
+        println!("应有供应: {}, 实际余额总和: {}, 供应上限: {}", audit.expected_supply, audit.actual_supply, audit.max_supply);
+        println!("审计结果: {}", if audit.is_ok() { "✅ 通过" } else { "❌ 不一致" });
     }
 
     /// 保存区块链到文件
@@ -264,24 +1501,643 @@ impl Blockchain {
 
     /// 从文件加载区块链
     pub fn load_from_file(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        if !fs::metadata(filename).is_ok() {
+        if fs::metadata(filename).is_err() {
             return Err(format!("文件不存在: {}", filename).into());
         }
         let json = fs::read_to_string(filename)?;
         let mut blockchain: Blockchain = serde_json::from_str(&json)?;
         println!("区块链已从文件加载: {}", filename);
 
-        // 如果加载的区块链没有余额，添加初始余额给系统账户
-        if blockchain.balances.is_empty() {
-            blockchain.balances.insert("system".to_string(), 1000);
+        // 旧版本文件没有硬分叉计划：从首个带验证者的区块（或下一个区块）起启用当前共识
+        if blockchain.chain_params.forks.is_empty() {
+            blockchain.chain_params = ChainParams::new(RuleSet {
+                consensus_type: ConsensusType::PoW,
+                mining_reward: blockchain.mining_reward,
+                authorities: Vec::new(),
+                authority_keys: BTreeMap::new(),
+                difficulty_rule: DifficultyRule::Legacy,
+                coinbase_rule: CoinbaseRule::Legacy,
+            });
+            if blockchain.consensus_type != ConsensusType::PoW {
+                let activation_height = blockchain.chain
+                    .iter()
+                    .find(|block| block.header.validator.is_some())
+                    .map(|block| block.height)
+                    .unwrap_or(blockchain.get_length() as u64);
+                let rules = RuleSet {
+                    consensus_type: blockchain.consensus_type.clone(),
+                    mining_reward: blockchain.mining_reward,
+                    authorities: blockchain.poa_consensus
+                        .as_ref()
+                        .map(|poa| poa.authorities.clone())
+                        .unwrap_or_default(),
+                    authority_keys: blockchain.poa_consensus
+                        .as_ref()
+                        .map(|poa| poa.authority_keys.clone().into_iter().collect())
+                        .unwrap_or_default(),
+                    difficulty_rule: DifficultyRule::Legacy,
+                    coinbase_rule: CoinbaseRule::Legacy,
+                };
+                blockchain.chain_params.forks.push(HardFork {
+                    name: format!("{:?}@{}", rules.consensus_type, activation_height),
+                    activation_height,
+                    rules,
+                });
+            }
         }
 
+        // 由创世分配和链上交易重建余额和发行统计（修正旧版本重复累加的余额）
+        blockchain.rebuild_state();
+
         Ok(blockchain)
     }
 }
 
 impl Default for Blockchain {
     fn default() -> Self {
-        Self::new(4, 100) // 默认难度4，奖励100
+        Self::from_spec(&ChainSpec::mainnet())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::ParamChange;
+    use std::sync::atomic::{AtomicBool, AtomicU64};
+
+    /// 以前一个区块时间加 10 秒为时间戳挖出并提交下一个区块
+    fn mine(blockchain: &mut Blockchain, miner: &str) -> Block {
+        let mut template = blockchain.create_block_template(miner.to_string()).unwrap();
+        template.block.header.timestamp = blockchain.get_latest_block().header.timestamp + chrono::Duration::seconds(10);
+        assert!(template.block.mine_range(0, 1, &AtomicBool::new(false), &AtomicU64::new(0)));
+        blockchain.submit_block(template).unwrap()
+    }
+
+    fn regtest() -> Blockchain {
+        Blockchain::from_spec(&ChainSpec::regtest())
+    }
+
+    /// 为节点生成验证者签名密钥（PoS/DPoS/PoA 出块前必须持有）
+    fn with_key(node: &mut Blockchain, validator: &str) -> ValidatorKey {
+        let key = ValidatorKey::generate(validator.to_string());
+        node.validator_key = Some(key.clone());
+        key
+    }
+
+    #[test]
+    fn accepts_blocks_extending_tip() {
+        let mut miner = regtest();
+        let mut node = regtest();
+        let blocks: Vec<Block> = (0..3).map(|_| mine(&mut miner, "alice")).collect();
+
+        for block in blocks {
+            node.accept_block(block).unwrap();
+        }
+        assert_eq!(node.get_latest_block().header.hash, miner.get_latest_block().header.hash);
+        assert_eq!(node.get_balance("alice"), 300);
+        assert_eq!(node.issued_supply(), 300);
+        assert!(node.is_chain_valid());
+    }
+
+    #[test]
+    fn reorganizes_to_branch_with_more_work() {
+        let mut node = regtest();
+        let mut other = regtest();
+        mine(&mut node, "alice");
+        mine(&mut node, "alice");
+        let branch: Vec<Block> = (0..3).map(|_| mine(&mut other, "bob")).collect();
+
+        node.accept_branch(1, branch).unwrap();
+        assert_eq!(node.get_latest_block().header.hash, other.get_latest_block().header.hash);
+        assert_eq!(node.get_balance("alice"), 0);
+        assert_eq!(node.get_balance("bob"), 300);
+        assert!(node.supply_audit().is_ok());
+    }
+
+    #[test]
+    fn invalid_branch_restores_local_chain() {
+        let mut node = regtest();
+        let mut other = regtest();
+        mine(&mut node, "alice");
+        mine(&mut node, "alice");
+        let tip = node.get_latest_block().header.hash.clone();
+
+        let mut branch: Vec<Block> = (0..3).map(|_| mine(&mut other, "bob")).collect();
+        // 最后一个区块的 coinbase 超发
+        let last = branch.last_mut().unwrap();
+        if let TransactionKind::Coinbase { outputs, .. } = &mut last.transactions[0].kind {
+            outputs[0].amount = 1_000;
+        }
+        last.transactions[0].amount = 1_000;
+        assert!(last.mine_range(0, 1, &AtomicBool::new(false), &AtomicU64::new(0)));
+
+        assert!(node.accept_branch(1, branch).is_err());
+        assert_eq!(node.get_latest_block().header.hash, tip);
+        assert_eq!(node.get_balance("alice"), 200);
+        assert_eq!(node.get_balance("bob"), 0);
+        assert!(node.supply_audit().is_ok());
+    }
+
+    #[test]
+    fn rejects_branch_without_more_work() {
+        let mut node = regtest();
+        let mut other = regtest();
+        mine(&mut node, "alice");
+        mine(&mut node, "alice");
+        let branch: Vec<Block> = (0..2).map(|_| mine(&mut other, "bob")).collect();
+
+        assert!(node.accept_branch(1, branch).is_err());
+        assert_eq!(node.get_balance("alice"), 200);
+    }
+
+    fn headers(blocks: &[Block]) -> Vec<BlockHeader> {
+        blocks.iter().map(|block| block.header.clone()).collect()
+    }
+
+    #[test]
+    fn validates_header_chain_before_download() {
+        let node = regtest();
+        let mut other = regtest();
+        let branch: Vec<Block> = (0..3).map(|_| mine(&mut other, "bob")).collect();
+        assert_eq!(node.validate_headers(&headers(&branch)), Ok(1));
+        assert!(node.validate_headers(&[]).is_err());
+        assert!(node.validate_headers(&headers(&branch[1..])).unwrap_err().contains("本地链"));
+
+        let mut gap = headers(&branch);
+        gap.remove(1);
+        assert!(node.validate_headers(&gap).unwrap_err().contains("前一个区块头"));
+
+        let mut unmined = headers(&branch);
+        unmined[2].hash = "f".repeat(64);
+        assert!(node.validate_headers(&unmined).unwrap_err().contains("不满足难度"));
+
+        // 哈希满足自称的目标值，但目标值不是该高度规则要求的目标值
+        let mut retargeted = branch.clone();
+        retargeted[2].header.bits = crate::pow::difficulty_to_bits(2);
+        assert!(retargeted[2].mine_range(0, 1, &AtomicBool::new(false), &AtomicU64::new(0)));
+        assert!(node.validate_headers(&headers(&retargeted)).unwrap_err().contains("难度不符合规则"));
+    }
+
+    #[test]
+    fn checks_branch_work_and_finality() {
+        let mut node = regtest();
+        let mut other = regtest();
+        mine(&mut node, "alice");
+        mine(&mut node, "alice");
+        let branch: Vec<Block> = (0..3).map(|_| mine(&mut other, "bob")).collect();
+
+        assert!(node.check_branch(1, &headers(&branch)).is_ok());
+        assert!(node.check_branch(2, &headers(&branch)).unwrap_err().contains("本地链"));
+        assert!(node.check_branch(1, &headers(&branch[..2])).unwrap_err().contains("工作量"));
+
+        node.finality.finalized_height = 1;
+        node.finality.finalized_hash = Some(node.chain[1].header.hash.clone());
+        assert!(node.check_branch(1, &headers(&branch)).unwrap_err().contains("最终确认"));
+    }
+
+    #[test]
+    fn finality_is_kept_only_for_blocks_on_the_chain() {
+        let mut node = regtest();
+        mine(&mut node, "alice");
+        let mut fork = node.clone();
+        let replaced = mine(&mut node, "alice");
+        let branch: Vec<Block> = (0..2).map(|_| mine(&mut fork, "bob")).collect();
+
+        // 回滚到已最终确认的区块之前的分支被拒绝，链保持不变
+        let mut other = regtest();
+        let deep_branch: Vec<Block> = (0..4).map(|_| mine(&mut other, "bob")).collect();
+        node.finality.finalized_height = 1;
+        node.finality.finalized_hash = Some(node.chain[1].header.hash.clone());
+        let error = node.accept_branch(1, deep_branch).unwrap_err();
+        assert!(error.contains("最终确认"), "{}", error);
+        assert_eq!(node.get_latest_block().header.hash, replaced.header.hash);
+
+        // 最终确认区块之后的重组保留最终性
+        node.accept_branch(2, branch.clone()).unwrap();
+        assert_eq!(node.get_latest_block().header.hash, branch[1].header.hash);
+        node.rebuild_state();
+        assert_eq!(node.finality.finalized_hash, Some(node.chain[1].header.hash.clone()));
+
+        // 最终确认的区块不在链上时重建状态会重置最终性
+        node.finality.finalized_height = 2;
+        node.finality.finalized_hash = Some(replaced.header.hash.clone());
+        node.rebuild_state();
+        assert_eq!(node.finality.finalized_height, 0);
+        assert!(node.finality.finalized_hash.is_none());
+        assert!(node.is_chain_valid());
+    }
+
+    #[test]
+    fn governance_changes_are_derived_from_the_chain() {
+        let mut node = regtest();
+        let mut other = regtest();
+        let change = ParamChange { param: GovernanceParam::MiningReward, value: 50 };
+        let proposal = Transaction::new_proposal("dev".to_string(), change, "降低出块奖励".to_string());
+        node.add_transaction(proposal.clone()).unwrap();
+        let mut blocks = vec![mine(&mut node, "alice")];
+        node.add_transaction(Transaction::new_vote("dev".to_string(), proposal.id.clone(), true)).unwrap();
+        blocks.push(mine(&mut node, "alice"));
+
+        let activation_height = node.governance.proposals[&proposal.id].activation_height;
+        while (node.get_length() as u64) <= activation_height {
+            blocks.push(mine(&mut node, "alice"));
+        }
+        assert_eq!(node.governance.proposals[&proposal.id].status, ProposalStatus::Applied);
+        assert_eq!(node.chain[activation_height as usize - 1].transactions[0].amount, 100);
+        assert_eq!(node.chain[activation_height as usize].transactions[0].amount, 50);
+
+        // 其他节点和重放从链上的治理交易得到相同的参数修改
+        for block in blocks {
+            other.accept_block(block).unwrap();
+        }
+        assert_eq!(other.governance.proposals[&proposal.id].status, ProposalStatus::Applied);
+        assert_eq!(other.block_subsidy(), 50);
+        node.rebuild_state();
+        assert_eq!(node.governance.proposals[&proposal.id].status, ProposalStatus::Applied);
+        assert_eq!(node.block_subsidy(), 50);
+    }
+
+    #[test]
+    fn staking_transactions_lock_and_release_funds() {
+        let mut node = regtest();
+        let mut other = regtest();
+        node.schedule_consensus_fork(ConsensusType::DPoS, 1, BTreeMap::new()).unwrap();
+        other.schedule_consensus_fork(ConsensusType::DPoS, 1, BTreeMap::new()).unwrap();
+        with_key(&mut node, "dev");
+
+        node.add_transaction(Transaction::new_staking("dev".to_string(), TransactionKind::RegisterCandidate, 2_000)).unwrap();
+        let mut blocks = vec![mine(&mut node, "dev")];
+        assert_eq!(node.get_balance(STAKING_ADDRESS), 2_000);
+        assert_eq!(node.dpos_consensus.as_ref().unwrap().calculate_candidate_weight("dev"), 2_000);
+
+        node.add_transaction(Transaction::new_staking("dev".to_string(), TransactionKind::DeregisterCandidate, 0)).unwrap();
+        blocks.push(mine(&mut node, "dev"));
+        let release_height = node.dpos_consensus.as_ref().unwrap().unbonding[0].release_height;
+        while (node.get_length() as u64) <= release_height {
+            assert_eq!(node.get_balance(STAKING_ADDRESS), 2_000);
+            blocks.push(mine(&mut node, "dev"));
+        }
+        assert_eq!(node.get_balance(STAKING_ADDRESS), 0);
+        assert!(node.dpos_consensus.as_ref().unwrap().unbonding.is_empty());
+        assert!(node.supply_audit().is_ok());
+
+        // 其他节点和重放得到相同的质押状态
+        for block in blocks {
+            other.accept_block(block).unwrap();
+        }
+        assert_eq!(other.balances, node.balances);
+        let balances = node.balances.clone();
+        node.rebuild_state();
+        assert_eq!(node.balances, balances);
+    }
+
+    #[test]
+    fn rejects_block_with_invalid_staking_transaction() {
+        let mut node = regtest();
+        let mut other = regtest();
+        node.schedule_consensus_fork(ConsensusType::PoS, 1, BTreeMap::new()).unwrap();
+        other.schedule_consensus_fork(ConsensusType::PoS, 1, BTreeMap::new()).unwrap();
+
+        let unstake = Transaction::new_staking("dev".to_string(), TransactionKind::Unstake, 0);
+        assert!(node.add_transaction(unstake.clone()).is_err());
+
+        let key = with_key(&mut node, "dev");
+        let mut template = node.create_block_template("dev".to_string()).unwrap();
+        template.block.transactions.push(unstake);
+        template.block.header.timestamp = node.get_latest_block().header.timestamp + chrono::Duration::seconds(10);
+        assert!(template.block.mine_range(0, 1, &AtomicBool::new(false), &AtomicU64::new(0)));
+        template.block.sign(&key.keypair());
+        let error = other.accept_block(template.block).unwrap_err();
+        assert!(error.contains("质押交易"), "{}", error);
+    }
+
+    #[test]
+    fn staking_rewards_are_derived_from_coinbase_outputs() {
+        let mut node = regtest();
+        let mut other = regtest();
+        node.schedule_consensus_fork(ConsensusType::DPoS, 1, BTreeMap::new()).unwrap();
+        other.schedule_consensus_fork(ConsensusType::DPoS, 1, BTreeMap::new()).unwrap();
+        with_key(&mut node, "dev");
+
+        node.add_transaction(Transaction::new("dev".to_string(), "bob".to_string(), 500)).unwrap();
+        node.add_transaction(Transaction::new_staking("dev".to_string(), TransactionKind::RegisterCandidate, 2_000)).unwrap();
+        let mut blocks = vec![mine(&mut node, "dev")];
+        let delegate = TransactionKind::Delegate { candidate: "dev".to_string() };
+        node.add_transaction(Transaction::new_staking("bob".to_string(), delegate, 200)).unwrap();
+        node.add_transaction(Transaction::new_staking("dev".to_string(), TransactionKind::SetCommission { rate: 20 }, 0)).unwrap();
+        blocks.push(mine(&mut node, "dev"));
+        blocks.push(mine(&mut node, "dev"));
+
+        // 佣金 20%，剩余 80 按 200 / 2200 分给委托人
+        let rewards = node.get_staking_rewards("bob");
+        assert_eq!(rewards.len(), 1);
+        assert_eq!((rewards[0].amount, &rewards[0].kind), (7, &RewardKind::Delegation));
+        assert_eq!(node.get_staking_rewards("dev").last().map(|r| &r.kind), Some(&RewardKind::Commission));
+
+        for block in blocks {
+            other.accept_block(block).unwrap();
+        }
+        assert_eq!(other.get_total_staking_rewards("bob"), 7);
+        assert_eq!(other.get_total_staking_rewards("dev"), node.get_total_staking_rewards("dev"));
+        node.rebuild_state();
+        assert_eq!(node.get_total_staking_rewards("bob"), 7);
+    }
+
+    #[test]
+    fn rejects_dpos_coinbase_diverting_delegator_share() {
+        let mut node = regtest();
+        let mut other = regtest();
+        node.schedule_consensus_fork(ConsensusType::DPoS, 1, BTreeMap::new()).unwrap();
+        other.schedule_consensus_fork(ConsensusType::DPoS, 1, BTreeMap::new()).unwrap();
+        let key = with_key(&mut node, "dev");
+
+        node.add_transaction(Transaction::new("dev".to_string(), "bob".to_string(), 500)).unwrap();
+        node.add_transaction(Transaction::new_staking("dev".to_string(), TransactionKind::RegisterCandidate, 2_000)).unwrap();
+        other.accept_block(mine(&mut node, "dev")).unwrap();
+        let delegate = TransactionKind::Delegate { candidate: "dev".to_string() };
+        node.add_transaction(Transaction::new_staking("bob".to_string(), delegate, 200)).unwrap();
+        other.accept_block(mine(&mut node, "dev")).unwrap();
+
+        // 出块人把委托人的份额也记到自己名下，coinbase 总额不变
+        let mut template = node.create_block_template("dev".to_string()).unwrap();
+        let Some(TransactionKind::Coinbase { outputs, .. }) = template.block.transactions.first().map(|tx| &tx.kind) else {
+            panic!("区块模板缺少 coinbase 交易");
+        };
+        assert!(outputs.iter().any(|output| output.address == "bob"));
+        let total = outputs.iter().map(|output| output.amount).sum();
+        let diverted = vec![CoinbaseOutput { address: "dev".to_string(), amount: total }];
+        template.block.transactions[0] = Transaction::new_coinbase(template.block.height, "dev".to_string(), diverted);
+        template.block.header.timestamp = node.get_latest_block().header.timestamp + chrono::Duration::seconds(10);
+        template.block.header.hash = template.block.calculate_hash();
+        assert!(template.block.mine_range(0, 1, &AtomicBool::new(false), &AtomicU64::new(0)));
+        template.block.sign(&key.keypair());
+        let error = other.accept_block(template.block).unwrap_err();
+        assert!(error.contains("奖励分配"), "{}", error);
+
+        other.accept_block(mine(&mut node, "dev")).unwrap();
+        assert_eq!(other.get_total_staking_rewards("bob"), node.get_total_staking_rewards("bob"));
+        assert!(other.get_total_staking_rewards("bob") > 0);
+    }
+
+    /// 在高度 1 切换到 DPoS，并在前三个区块中注册候选人 dev 和 bob 并登记签名公钥
+    fn dpos_with_candidates(spec: &ChainSpec) -> (Blockchain, Blockchain, HashMap<String, ValidatorKey>) {
+        let mut node = Blockchain::from_spec(spec);
+        let mut other = Blockchain::from_spec(spec);
+        node.schedule_consensus_fork(ConsensusType::DPoS, 1, BTreeMap::new()).unwrap();
+        other.schedule_consensus_fork(ConsensusType::DPoS, 1, BTreeMap::new()).unwrap();
+
+        // 尚无当选出块人时任何签名有效的区块都可以上链
+        with_key(&mut node, "alice");
+        node.add_transaction(Transaction::new("dev".to_string(), "bob".to_string(), 3_000)).unwrap();
+        other.accept_block(mine(&mut node, "alice")).unwrap();
+
+        let keys: HashMap<String, ValidatorKey> = ["dev", "bob"]
+            .iter()
+            .map(|candidate| (candidate.to_string(), ValidatorKey::generate(candidate.to_string())))
+            .collect();
+        for candidate in ["dev", "bob"] {
+            node.add_transaction(Transaction::new_staking(candidate.to_string(), TransactionKind::RegisterCandidate, 2_000)).unwrap();
+        }
+        other.accept_block(mine(&mut node, "alice")).unwrap();
+        assert!(other.dpos_consensus.as_ref().unwrap().schedule.producers.is_empty());
+        for candidate in ["dev", "bob"] {
+            node.add_transaction(Transaction::new_validator_key(&keys[candidate])).unwrap();
+        }
+        other.accept_block(mine(&mut node, "alice")).unwrap();
+        assert_eq!(other.dpos_consensus.as_ref().unwrap().schedule.producers.len(), 2);
+        (node, other, keys)
+    }
+
+    /// 为指定出块人创建在跳过 skipped 个槽位后的槽位出块的区块（已挖矿，未签名）
+    fn slot_block(node: &Blockchain, producer: &str, skipped: i64) -> Block {
+        let mut scratch = node.clone();
+        scratch.dpos_consensus.as_mut().unwrap().schedule.producers = vec![producer.to_string()];
+        with_key(&mut scratch, producer);
+        let mut block = scratch.create_block_template(producer.to_string()).unwrap().block;
+        block.header.timestamp = node.get_latest_block().header.timestamp + chrono::Duration::seconds(5 + 10 * skipped);
+        block.header.hash = block.calculate_hash();
+        assert!(block.mine_range(0, 1, &AtomicBool::new(false), &AtomicU64::new(0)));
+        block
+    }
+
+    #[test]
+    fn rejects_dpos_block_not_signed_by_scheduled_producer() {
+        let (_, mut other, keys) = dpos_with_candidates(&ChainSpec::regtest());
+        let dpos = other.dpos_consensus.as_ref().unwrap();
+        let scheduled = dpos.producer_at(&dpos.schedule, other.get_length() as u64, 0).unwrap();
+        let unscheduled = if scheduled == "dev" { "bob" } else { "dev" };
+
+        // 未签名、由其他密钥签名、不在该槽位的出块人签名的区块都被拒绝
+        let unsigned = slot_block(&other, &scheduled, 0);
+        assert!(other.accept_block(unsigned.clone()).is_err());
+        let mut forged = unsigned.clone();
+        forged.sign(&ValidatorKey::generate(scheduled.clone()).keypair());
+        assert!(other.accept_block(forged).is_err());
+        let mut out_of_slot = slot_block(&other, unscheduled, 0);
+        out_of_slot.sign(&keys[unscheduled].keypair());
+        let error = other.accept_block(out_of_slot).unwrap_err();
+        assert!(error.contains("槽位"), "{}", error);
+
+        let mut signed = unsigned;
+        signed.sign(&keys[&scheduled].keypair());
+        other.accept_block(signed).unwrap();
+        assert_eq!(other.get_length(), 5);
+    }
+
+    #[test]
+    fn validator_keys_are_registered_on_chain() {
+        let mut node = regtest();
+        let mut other = regtest();
+        node.schedule_consensus_fork(ConsensusType::PoS, 1, BTreeMap::new()).unwrap();
+        other.schedule_consensus_fork(ConsensusType::PoS, 1, BTreeMap::new()).unwrap();
+
+        let key = with_key(&mut node, "dev");
+        node.add_transaction(Transaction::new_staking("dev".to_string(), TransactionKind::Stake, 500)).unwrap();
+        let staked = mine(&mut node, "dev");
+        node.add_transaction(Transaction::new_validator_key(&key)).unwrap();
+        let registered = mine(&mut node, "dev");
+
+        other.accept_block(staked).unwrap();
+        other.accept_block(registered).unwrap();
+        let weights = other.validator_weights();
+        assert_eq!(weights["dev"].public_key, Some(key.public_key_hex()));
+
+        // 第三方用自己的密钥冒充 dev 重新登记，交易池和收到的区块都拒绝替换已登记的公钥
+        let impostor = ValidatorKey::generate("dev".to_string());
+        let rekey = Transaction::new_validator_key(&impostor);
+        let error = node.add_transaction(rekey.clone()).unwrap_err();
+        assert!(error.contains("不能替换"), "{}", error);
+
+        let mut template = node.create_block_template("dev".to_string()).unwrap();
+        template.block.transactions.push(rekey);
+        template.block.header.timestamp = node.get_latest_block().header.timestamp + chrono::Duration::seconds(10);
+        assert!(template.block.mine_range(0, 1, &AtomicBool::new(false), &AtomicU64::new(0)));
+        template.block.sign(&key.keypair());
+        assert!(other.accept_block(template.block).is_err());
+        assert_eq!(other.validator_weights()["dev"].public_key, Some(key.public_key_hex()));
+    }
+
+    #[test]
+    fn double_sign_evidence_slashes_on_every_node() {
+        let mut node = regtest();
+        let mut other = regtest();
+        node.schedule_consensus_fork(ConsensusType::PoS, 1, BTreeMap::new()).unwrap();
+        other.schedule_consensus_fork(ConsensusType::PoS, 1, BTreeMap::new()).unwrap();
+
+        let key = ValidatorKey::generate("dev".to_string());
+        node.validator_key = Some(key.clone());
+        node.add_transaction(Transaction::new_staking("dev".to_string(), TransactionKind::Stake, 1_000)).unwrap();
+        let mut blocks = vec![mine(&mut node, "dev")];
+        node.add_transaction(Transaction::new_validator_key(&key)).unwrap();
+        blocks.push(mine(&mut node, "dev"));
+
+        // 验证者在同一高度签名两个不同的区块
+        let mut fork = node.clone();
+        fork.add_transaction(Transaction::new("dev".to_string(), "bob".to_string(), 1)).unwrap();
+        let conflicting = mine(&mut fork, "dev");
+        blocks.push(mine(&mut node, "dev"));
+        let evidence = node.detect_double_sign(&conflicting).unwrap();
+
+        node.submit_evidence("alice".to_string(), evidence.clone()).unwrap();
+        assert!(node.submit_evidence("alice".to_string(), evidence.clone()).is_err());
+        blocks.push(mine(&mut node, "dev"));
+
+        let pos = node.pos_consensus.as_ref().unwrap();
+        assert_eq!(pos.stakes["dev"].amount, 900);
+        assert!(pos.slashing.is_jailed("dev", node.get_length() as u64));
+        assert_eq!(node.get_balance(STAKING_ADDRESS), 900);
+        assert!(node.supply_audit().balanced);
+        assert!(node.submit_evidence("alice".to_string(), evidence).is_err());
+
+        // 其他节点从链上的证据交易得到相同的罚没结果
+        for block in blocks {
+            other.accept_block(block).unwrap();
+        }
+        assert_eq!(other.pos_consensus.as_ref().unwrap().stakes["dev"].amount, 900);
+        assert_eq!(other.balances, node.balances);
+        assert_eq!(other.supply.burned, node.supply.burned);
+    }
+
+    #[test]
+    fn downtime_is_slashed_from_block_timestamps() {
+        let mut spec = ChainSpec::regtest();
+        spec.slashing.max_missed_slots = 2;
+        spec.slashing.downtime_penalty_percent = 50;
+        let (mut node, mut other, keys) = dpos_with_candidates(&spec);
+
+        // 只有 dev 出块：轮到 bob 的槽位都被跳过
+        while !node.dpos_consensus.as_ref().unwrap().slashing.jailed.contains_key("bob") {
+            assert!(node.get_length() < 20);
+            let dpos = node.dpos_consensus.as_ref().unwrap();
+            let height = node.get_length() as u64;
+            let skipped = if dpos.producer_at(&dpos.schedule, height, 0).as_deref() == Some("dev") { 0 } else { 1 };
+            let mut block = slot_block(&node, "dev", skipped);
+            block.sign(&keys["dev"].keypair());
+            node.accept_block(block.clone()).unwrap();
+            other.accept_block(block).unwrap();
+        }
+
+        let dpos = node.dpos_consensus.as_ref().unwrap();
+        assert_eq!(dpos.stakes["bob"].amount, 1_000);
+        assert_eq!(node.get_balance(STAKING_ADDRESS), 3_000);
+        assert!(node.supply_audit().balanced);
+
+        assert_eq!(other.dpos_consensus.as_ref().unwrap().stakes["bob"].amount, 1_000);
+        assert_eq!(other.balances, node.balances);
+        let balances = node.balances.clone();
+        node.rebuild_state();
+        assert_eq!(node.balances, balances);
+    }
+
+    #[test]
+    fn forged_timestamp_jump_does_not_slash_honest_producers() {
+        let mut spec = ChainSpec::regtest();
+        spec.slashing.max_missed_slots = 1;
+        spec.slashing.downtime_penalty_percent = 50;
+        let (_, mut other, keys) = dpos_with_candidates(&spec);
+
+        // dev 把时间戳提前一小时（仍在通用的两小时超前限制内），声称 bob 的槽位全部被跳过
+        let mut jump = slot_block(&other, "dev", 0);
+        jump.header.timestamp = chrono::Utc::now() + chrono::Duration::hours(1);
+        jump.header.hash = jump.calculate_hash();
+        assert!(jump.mine_range(0, 1, &AtomicBool::new(false), &AtomicU64::new(0)));
+        jump.sign(&keys["dev"].keypair());
+        let error = other.accept_block(jump).unwrap_err();
+        assert!(error.contains("超前本地时间"), "{}", error);
+
+        let slashing = &other.dpos_consensus.as_ref().unwrap().slashing;
+        assert_eq!(slashing.missed_slots.get("bob").copied().unwrap_or(0), 0);
+        assert!(!slashing.jailed.contains_key("bob"));
+        assert_eq!(other.dpos_consensus.as_ref().unwrap().stakes["bob"].amount, 2_000);
+        assert_eq!(other.get_length(), 4);
+    }
+
+    /// 在高度 1 切换到只有 alice 一个授权节点的 PoA 的本地链
+    fn poa_regtest(alice: &ValidatorKey) -> Blockchain {
+        let mut blockchain = regtest();
+        let authority_keys = BTreeMap::from([("alice".to_string(), alice.public_key_hex())]);
+        blockchain.schedule_consensus_fork(ConsensusType::PoA, 1, authority_keys).unwrap();
+        blockchain
+    }
+
+    #[test]
+    fn rejects_poa_block_not_signed_by_registered_key() {
+        let alice = ValidatorKey::generate("alice".to_string());
+        let mut node = poa_regtest(&alice);
+        let mut other = poa_regtest(&alice);
+        node.validator_key = Some(alice.clone());
+        other.accept_block(mine(&mut node, "alice")).unwrap();
+
+        // 冒用 alice 身份但使用未登记的密钥签名
+        let mut template = node.create_block_template("alice".to_string()).unwrap();
+        template.block.header.timestamp = node.get_latest_block().header.timestamp + chrono::Duration::seconds(10);
+        template.block.header.hash = template.block.calculate_hash();
+        template.block.sign(&ValidatorKey::generate("alice".to_string()).keypair());
+        assert!(other.accept_block(template.block.clone()).is_err());
+
+        // 非授权节点签名的区块同样被拒绝
+        let mallory = ValidatorKey::generate("mallory".to_string());
+        template.block.header.validator = Some("mallory".to_string());
+        template.block.header.hash = template.block.calculate_hash();
+        template.block.sign(&mallory.keypair());
+        assert!(other.accept_block(template.block).is_err());
+        assert_eq!(other.get_length(), 2);
+    }
+
+    #[test]
+    fn replays_authority_votes_from_received_blocks() {
+        let alice = ValidatorKey::generate("alice".to_string());
+        let bob = ValidatorKey::generate("bob".to_string());
+        let mut node = poa_regtest(&alice);
+        let mut other = poa_regtest(&alice);
+        node.validator_key = Some(alice);
+        node.poa_consensus.as_mut().unwrap().propose("bob".to_string(), true, Some(bob.public_key_hex())).unwrap();
+
+        other.accept_block(mine(&mut node, "alice")).unwrap();
+        let poa = other.poa_consensus.as_ref().unwrap();
+        assert!(poa.is_authority("bob"));
+        assert_eq!(poa.authority_keys.get("bob"), Some(&bob.public_key_hex()));
+
+        // 重放得到相同的授权节点集合
+        other.rebuild_state();
+        assert!(other.poa_consensus.as_ref().unwrap().is_authority("bob"));
+        assert!(node.poa_consensus.as_ref().unwrap().proposals.is_empty());
+    }
+
+    #[test]
+    fn consensus_switch_refunds_locked_stakes() {
+        let mut node = regtest();
+        node.schedule_consensus_fork(ConsensusType::PoS, 1, BTreeMap::new()).unwrap();
+        with_key(&mut node, "dev");
+        node.add_transaction(Transaction::new_staking("dev".to_string(), TransactionKind::Stake, 500)).unwrap();
+        mine(&mut node, "dev");
+        assert_eq!(node.get_balance(STAKING_ADDRESS), 500);
+        assert_eq!(node.voting_power("dev"), 500);
+
+        node.schedule_consensus_fork(ConsensusType::PoW, 3, BTreeMap::new()).unwrap();
+        mine(&mut node, "dev");
+        assert_eq!(node.consensus_type, ConsensusType::PoW);
+        assert_eq!(node.get_balance(STAKING_ADDRESS), 0);
+        assert!(node.supply_audit().is_ok());
     }
 }
@@ -0,0 +1,215 @@
+use crate::consensus::{ConsensusType, SlashingConfig};
+use crate::monetary::MonetaryPolicy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// 某一高度区间内生效的链规则
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub consensus_type: ConsensusType,
+    pub mining_reward: u64,
+    #[serde(default)]
+    pub authorities: Vec<String>, // PoA 激活时的初始授权节点
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub authority_keys: BTreeMap<String, String>, // PoA 初始授权节点 -> 签名公钥
+    #[serde(default)]
+    pub difficulty_rule: DifficultyRule, // 难度调整规则
+    #[serde(default)]
+    pub coinbase_rule: CoinbaseRule, // 出块奖励交易规则
+}
+
+/// 出块奖励交易规则
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum CoinbaseRule {
+    /// 旧版规则：奖励为普通系统转账，不参与链验证
+    #[default]
+    Legacy,
+    /// 首笔交易必须是 coinbase，奖励不超过出块奖励加手续费，且需经过成熟期才能花费
+    Enforced,
+}
+
+/// 难度调整规则
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum DifficultyRule {
+    /// 旧版规则：仅比较最近两个区块，由本地状态决定，不参与链验证
+    #[default]
+    Legacy,
+    /// 每 RETARGET_INTERVAL 个区块按窗口耗时重新计算，难度可由链上数据独立验证
+    Windowed,
+    /// 与 Windowed 相同的调整窗口，但难度以 256 位目标值表示，按实际耗时比例连续调整
+    CompactTarget,
+}
+
+/// 硬分叉：在指定高度激活一套新的链规则
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HardFork {
+    pub name: String,
+    pub activation_height: u64,
+    pub rules: RuleSet,
+}
+
+/// 区块限制
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BlockLimits {
+    pub max_transactions: usize, // 每个区块最多交易数（含 coinbase）
+    pub max_block_bytes: usize,  // 区块序列化后的最大字节数
+}
+
+impl Default for BlockLimits {
+    fn default() -> Self {
+        Self {
+            max_transactions: 1000,
+            max_block_bytes: 1_000_000,
+        }
+    }
+}
+
+/// 链参数 - 链标识、按激活高度排序的硬分叉计划（第一项从创世区块开始生效）、货币政策、区块限制和罚没参数
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChainParams {
+    #[serde(default)]
+    pub chain_id: String, // 旧版本数据文件为空
+    pub forks: Vec<HardFork>,
+    #[serde(default)]
+    pub monetary: MonetaryPolicy, // 出块奖励减半、供应上限和创世分配
+    #[serde(default)]
+    pub limits: BlockLimits,
+    #[serde(default, skip_serializing_if = "SlashingConfig::is_default")]
+    pub slashing: SlashingConfig, // PoS/DPoS 罚没比例、停机阈值和监禁区块数
+}
+
+impl ChainParams {
+    /// 以创世规则创建链参数
+    pub fn new(genesis_rules: RuleSet) -> Self {
+        Self {
+            chain_id: String::new(),
+            forks: vec![HardFork {
+                name: "genesis".to_string(),
+                activation_height: 0,
+                rules: genesis_rules,
+            }],
+            monetary: MonetaryPolicy::default(),
+            limits: BlockLimits::default(),
+            slashing: SlashingConfig::default(),
+        }
+    }
+
+    /// 获取指定高度生效的硬分叉
+    pub fn fork_at(&self, height: u64) -> Option<&HardFork> {
+        self.forks.iter().rev().find(|fork| fork.activation_height <= height)
+    }
+
+    /// 获取指定高度生效的规则
+    pub fn rules_at(&self, height: u64) -> Option<&RuleSet> {
+        self.fork_at(height).map(|fork| &fork.rules)
+    }
+
+    /// 获取指定高度之后（不含）最近一次计划中的硬分叉
+    pub fn next_fork(&self, height: u64) -> Option<&HardFork> {
+        self.forks.iter().find(|fork| fork.activation_height > height)
+    }
+
+    /// 计划新的硬分叉；激活高度不能早于下一个待出块高度，也不能早于已计划的硬分叉
+    pub fn schedule_fork(&mut self, name: String, activation_height: u64, rules: RuleSet, next_height: u64) -> Result<(), String> {
+        if activation_height < next_height {
+            return Err(format!("激活高度 {} 早于下一个区块高度 {}，不能修改历史规则", activation_height, next_height));
+        }
+        if let Some(last) = self.forks.last()
+            && activation_height <= last.activation_height
+        {
+            return Err(format!("激活高度必须晚于已计划的硬分叉 {} (高度 {})", last.name, last.activation_height));
+        }
+        if rules.consensus_type == ConsensusType::PoA {
+            if rules.authorities.is_empty() {
+                return Err("PoA 硬分叉至少需要一个授权节点".to_string());
+            }
+            if let Some(authority) = rules.authorities.iter().find(|a| !rules.authority_keys.contains_key(*a)) {
+                return Err(format!("授权节点 {} 缺少签名公钥", authority));
+            }
+        }
+
+        self.forks.push(HardFork { name, activation_height, rules });
+        Ok(())
+    }
+
+    /// 链参数哈希 - 节点间比较以拒绝硬分叉计划或货币政策不一致的节点
+    pub fn schedule_hash(&self) -> String {
+        let data = serde_json::to_string(self).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(data.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(consensus_type: ConsensusType) -> RuleSet {
+        RuleSet {
+            consensus_type,
+            mining_reward: 50,
+            authorities: Vec::new(),
+            authority_keys: BTreeMap::new(),
+            difficulty_rule: DifficultyRule::CompactTarget,
+            coinbase_rule: CoinbaseRule::Enforced,
+        }
+    }
+
+    fn schedule() -> ChainParams {
+        let mut params = ChainParams::new(rules(ConsensusType::PoW));
+        params.schedule_fork("pos".to_string(), 10, rules(ConsensusType::PoS), 1).unwrap();
+        params.schedule_fork("dpos".to_string(), 20, rules(ConsensusType::DPoS), 1).unwrap();
+        params
+    }
+
+    #[test]
+    fn rules_switch_exactly_at_activation_height() {
+        let params = schedule();
+        let consensus_at = |height| params.rules_at(height).unwrap().consensus_type.clone();
+        assert_eq!(consensus_at(0), ConsensusType::PoW);
+        assert_eq!(consensus_at(9), ConsensusType::PoW);
+        assert_eq!(consensus_at(10), ConsensusType::PoS);
+        assert_eq!(consensus_at(19), ConsensusType::PoS);
+        assert_eq!(consensus_at(20), ConsensusType::DPoS);
+        assert_eq!(consensus_at(u64::MAX), ConsensusType::DPoS);
+
+        assert_eq!(params.fork_at(10).unwrap().name, "pos");
+        assert_eq!(params.next_fork(9).unwrap().name, "pos");
+        assert_eq!(params.next_fork(10).unwrap().name, "dpos");
+        assert!(params.next_fork(20).is_none());
+    }
+
+    #[test]
+    fn rejects_forks_that_rewrite_history_or_lack_authority_keys() {
+        let mut params = schedule();
+        assert!(params.schedule_fork("past".to_string(), 5, rules(ConsensusType::PoW), 6).is_err());
+        assert!(params.schedule_fork("earlier".to_string(), 15, rules(ConsensusType::PoW), 1).is_err());
+        assert!(params.schedule_fork("same".to_string(), 20, rules(ConsensusType::PoW), 1).is_err());
+
+        let mut poa = rules(ConsensusType::PoA);
+        poa.authorities = vec!["alice".to_string()];
+        assert!(params.schedule_fork("poa".to_string(), 30, poa.clone(), 1).is_err());
+        poa.authority_keys.insert("alice".to_string(), "00".to_string());
+        params.schedule_fork("poa".to_string(), 30, poa, 1).unwrap();
+        assert_eq!(params.forks.len(), 4);
+    }
+
+    #[test]
+    fn schedule_hash_is_stable_and_covers_the_schedule() {
+        let params = schedule();
+        assert_eq!(params.schedule_hash(), schedule().schedule_hash());
+        let decoded: ChainParams = serde_json::from_str(&serde_json::to_string(&params).unwrap()).unwrap();
+        assert_eq!(decoded.schedule_hash(), params.schedule_hash());
+
+        let mut later = ChainParams::new(rules(ConsensusType::PoW));
+        later.schedule_fork("pos".to_string(), 11, rules(ConsensusType::PoS), 1).unwrap();
+        later.schedule_fork("dpos".to_string(), 20, rules(ConsensusType::DPoS), 1).unwrap();
+        assert_ne!(later.schedule_hash(), params.schedule_hash());
+
+        let mut other_chain = schedule();
+        other_chain.chain_id = "other".to_string();
+        assert_ne!(other_chain.schedule_hash(), params.schedule_hash());
+    }
+}
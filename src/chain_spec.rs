@@ -0,0 +1,323 @@
+use crate::block::Block;
+use crate::chain_params::{BlockLimits, ChainParams, CoinbaseRule, DifficultyRule, HardFork, RuleSet};
+use crate::consensus::{ConsensusType, SlashingConfig};
+use crate::monetary::{GenesisAllocation, MonetaryPolicy};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// 内置的链配置预设
+pub const PRESETS: [&str; 3] = ["mainnet", "testnet", "regtest"];
+
+/// 链配置 - 从 JSON 文件或内置预设加载，决定创世区块和初始链参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainSpec {
+    pub chain_id: String,
+    pub genesis_timestamp: DateTime<Utc>,
+    pub difficulty: u32, // 创世难度（十六进制前导零个数）
+    pub consensus: ConsensusType,
+    #[serde(default)]
+    pub authorities: Vec<String>, // PoA 初始授权节点
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub authority_keys: BTreeMap<String, String>, // PoA 初始授权节点 -> 签名公钥（十六进制）
+    pub monetary: MonetaryPolicy,
+    #[serde(default)]
+    pub limits: BlockLimits,
+    #[serde(default, skip_serializing_if = "SlashingConfig::is_default")]
+    pub slashing: SlashingConfig, // PoS/DPoS 罚没参数（默认配置不写入文件，不影响创世区块）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forks: Vec<HardFork>, // 创世之后的硬分叉计划（按激活高度排序），写入创世区块
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seeds: Vec<String>, // 种子节点地址（host:port），不影响创世区块
+}
+
+impl ChainSpec {
+    /// 主网：难度 4，每 210000 个区块奖励减半，无创世分配
+    pub fn mainnet() -> Self {
+        let mut monetary = MonetaryPolicy::new(100, 210_000, Vec::new());
+        monetary.coinbase_maturity = 100;
+        Self {
+            chain_id: "rust-chain-mainnet".to_string(),
+            genesis_timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            difficulty: 4,
+            consensus: ConsensusType::PoW,
+            authorities: Vec::new(),
+            authority_keys: BTreeMap::new(),
+            monetary,
+            limits: BlockLimits::default(),
+            slashing: SlashingConfig::default(),
+            forks: Vec::new(),
+            seeds: Vec::new(),
+        }
+    }
+
+    /// 测试网：难度 3，水龙头账户预分配代币
+    pub fn testnet() -> Self {
+        let monetary = MonetaryPolicy::new(100, 10_000, vec![GenesisAllocation {
+            address: "faucet".to_string(),
+            amount: 1_000_000,
+        }]);
+        Self {
+            chain_id: "rust-chain-testnet".to_string(),
+            genesis_timestamp: Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap(),
+            difficulty: 3,
+            consensus: ConsensusType::PoW,
+            authorities: Vec::new(),
+            authority_keys: BTreeMap::new(),
+            monetary,
+            limits: BlockLimits::default(),
+            slashing: SlashingConfig::default(),
+            forks: Vec::new(),
+            seeds: Vec::new(),
+        }
+    }
+
+    /// 本地回归测试网：难度 1，奖励快速减半且立即成熟，便于本地开发
+    pub fn regtest() -> Self {
+        let mut monetary = MonetaryPolicy::new(100, 150, vec![GenesisAllocation {
+            address: "dev".to_string(),
+            amount: 1_000_000,
+        }]);
+        monetary.coinbase_maturity = 1;
+        Self {
+            chain_id: "rust-chain-regtest".to_string(),
+            genesis_timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            difficulty: 1,
+            consensus: ConsensusType::PoW,
+            authorities: Vec::new(),
+            authority_keys: BTreeMap::new(),
+            monetary,
+            limits: BlockLimits::default(),
+            slashing: SlashingConfig::default(),
+            forks: Vec::new(),
+            seeds: Vec::new(),
+        }
+    }
+
+    /// 按名称获取内置预设
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "mainnet" => Some(Self::mainnet()),
+            "testnet" => Some(Self::testnet()),
+            "regtest" => Some(Self::regtest()),
+            _ => None,
+        }
+    }
+
+    /// 从 JSON 文件加载链配置
+    pub fn load_from_file(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = fs::read_to_string(filename)?;
+        let spec: ChainSpec = serde_json::from_str(&json)?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// 按预设名称或配置文件路径加载链配置
+    pub fn resolve(name_or_path: &str) -> Result<Self, String> {
+        match Self::preset(name_or_path) {
+            Some(spec) => Ok(spec),
+            None => Self::load_from_file(name_or_path)
+                .map_err(|e| format!("加载链配置 {} 失败: {} (可用预设: {})", name_or_path, e, PRESETS.join(", "))),
+        }
+    }
+
+    /// 校验链配置
+    pub fn validate(&self) -> Result<(), String> {
+        if self.chain_id.is_empty() {
+            return Err("chain_id 不能为空".to_string());
+        }
+        if self.consensus == ConsensusType::PoA {
+            if self.authorities.is_empty() {
+                return Err("PoA 链至少需要一个授权节点".to_string());
+            }
+            if let Some(authority) = self.authorities.iter().find(|a| !self.authority_keys.contains_key(*a)) {
+                return Err(format!("授权节点 {} 缺少签名公钥", authority));
+            }
+        }
+        if self.monetary.halving_interval == 0 {
+            return Err("减半周期必须大于 0".to_string());
+        }
+        if self.monetary.genesis_supply() > self.monetary.max_supply {
+            return Err("创世分配超过供应上限".to_string());
+        }
+        if self.limits.max_transactions == 0 || self.limits.max_block_bytes == 0 {
+            return Err("区块限制必须大于 0".to_string());
+        }
+        if self.slashing.double_sign_penalty_percent > 100 || self.slashing.downtime_penalty_percent > 100 {
+            return Err("罚没比例不能超过 100%".to_string());
+        }
+        if self.slashing.max_missed_slots == 0 {
+            return Err("停机漏块阈值必须大于 0".to_string());
+        }
+        // 硬分叉按计划顺序逐个加入，激活高度必须在创世之后且递增
+        let mut params = ChainParams::new(self.genesis_rules());
+        for fork in &self.forks {
+            params
+                .schedule_fork(fork.name.clone(), fork.activation_height, fork.rules.clone(), 1)
+                .map_err(|e| format!("硬分叉 {} 无效: {}", fork.name, e))?;
+        }
+        Ok(())
+    }
+
+    /// 检查数据文件中的链是否由本配置创建（旧版本数据文件没有链标识，不做检查）
+    pub fn check_data(&self, chain_id: &str, genesis_hash: &str) -> Result<(), String> {
+        if chain_id.is_empty() {
+            return Ok(());
+        }
+        if chain_id != self.chain_id {
+            return Err(format!("数据文件属于链 {}，与链配置 {} 不一致", chain_id, self.chain_id));
+        }
+        let expected = self.genesis_block().header.hash;
+        if genesis_hash != expected {
+            return Err(format!("数据文件的创世区块 {} 与链配置的创世区块 {} 不一致", genesis_hash, expected));
+        }
+        Ok(())
+    }
+
+    /// 链配置哈希（写入创世区块，配置不同的链创世哈希不同；种子地址不参与计算）
+    pub fn hash(&self) -> String {
+        let spec = Self { seeds: Vec::new(), ..self.clone() };
+        let data = serde_json::to_string(&spec).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(data.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// 确定性的创世区块
+    pub fn genesis_block(&self) -> Block {
+        Block::create_genesis(self.difficulty, self.genesis_timestamp, &self.hash())
+    }
+
+    /// 创世区块开始生效的规则
+    fn genesis_rules(&self) -> RuleSet {
+        RuleSet {
+            consensus_type: self.consensus.clone(),
+            mining_reward: self.monetary.initial_subsidy,
+            authorities: self.authorities.clone(),
+            authority_keys: self.authority_keys.clone(),
+            difficulty_rule: DifficultyRule::CompactTarget,
+            coinbase_rule: CoinbaseRule::Enforced,
+        }
+    }
+
+    /// 初始链参数：创世规则加上链配置中的硬分叉计划
+    pub fn chain_params(&self) -> ChainParams {
+        let mut params = ChainParams::new(self.genesis_rules());
+        params.forks.extend(self.forks.iter().cloned());
+        params.chain_id = self.chain_id.clone();
+        params.monetary = self.monetary.clone();
+        params.limits = self.limits;
+        params.slashing = self.slashing.clone();
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("chain_spec_{}_{}.json", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn dpos_fork(activation_height: u64) -> HardFork {
+        let mut rules = ChainSpec::regtest().genesis_rules();
+        rules.consensus_type = ConsensusType::DPoS;
+        HardFork { name: format!("dpos-{}", activation_height), activation_height, rules }
+    }
+
+    #[test]
+    fn preset_genesis_hashes_are_deterministic() {
+        let expected = [
+            ("mainnet", "63faed1c0e99ecc2b5fa9d76163932f0d3ec56645a4d5f61bbe66f5ba9330dc0"),
+            ("testnet", "a91aacdfd1bd504a3eba481cd25f742e72e50561164e4cf591a498f63ccd7d6e"),
+            ("regtest", "dbffbe43afaa5a7b1c3bbd65e13f00f87f789f275a27e8e94451faedfe5cf29d"),
+        ];
+        for (name, hash) in expected {
+            let spec = ChainSpec::preset(name).unwrap();
+            assert_eq!(spec.genesis_block().header.hash, hash, "{}", name);
+            assert_eq!(ChainSpec::preset(name).unwrap().hash(), spec.hash());
+        }
+
+        // 种子地址不影响创世区块，其他参数不同则创世区块不同
+        let mut spec = ChainSpec::regtest();
+        spec.seeds.push("127.0.0.1:7878".to_string());
+        assert_eq!(spec.genesis_block().header.hash, expected[2].1);
+        spec.monetary.coinbase_maturity = 2;
+        assert_ne!(spec.genesis_block().header.hash, expected[2].1);
+    }
+
+    #[test]
+    fn loads_spec_from_json_file() {
+        let mut spec = ChainSpec::testnet();
+        spec.chain_id = "custom-chain".to_string();
+        spec.seeds.push("127.0.0.1:7878".to_string());
+        let path = temp_file("valid", &serde_json::to_string_pretty(&spec).unwrap());
+        let loaded = ChainSpec::resolve(&path);
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded, spec);
+        assert_eq!(loaded.genesis_block().header.hash, spec.genesis_block().header.hash);
+        assert_eq!(loaded.chain_params().chain_id, "custom-chain");
+    }
+
+    #[test]
+    fn rejects_unknown_or_malformed_specs() {
+        let error = ChainSpec::resolve("devnet").unwrap_err();
+        assert!(error.contains("可用预设"), "{}", error);
+
+        let path = temp_file("malformed", "{ \"chain_id\": \"broken\"");
+        assert!(ChainSpec::resolve(&path).is_err());
+        fs::remove_file(&path).unwrap();
+
+        let mut missing_id = ChainSpec::regtest();
+        missing_id.chain_id = String::new();
+        let mut keyless_poa = ChainSpec::regtest();
+        keyless_poa.consensus = ConsensusType::PoA;
+        keyless_poa.authorities = vec!["alice".to_string()];
+        let mut genesis_fork = ChainSpec::regtest();
+        genesis_fork.forks.push(dpos_fork(0));
+        let mut unordered_forks = ChainSpec::regtest();
+        unordered_forks.forks = vec![dpos_fork(10), dpos_fork(5)];
+        for invalid in [missing_id, keyless_poa, genesis_fork, unordered_forks] {
+            let path = temp_file("invalid", &serde_json::to_string(&invalid).unwrap());
+            assert!(ChainSpec::resolve(&path).is_err());
+            fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn fork_schedule_is_part_of_the_spec() {
+        let mut spec = ChainSpec::regtest();
+        spec.forks.push(dpos_fork(3));
+        spec.validate().unwrap();
+        assert_ne!(spec.genesis_block().header.hash, ChainSpec::regtest().genesis_block().header.hash);
+
+        let path = temp_file("forks", &serde_json::to_string(&spec).unwrap());
+        let loaded = ChainSpec::resolve(&path);
+        fs::remove_file(&path).unwrap();
+        let params = loaded.unwrap().chain_params();
+        assert_eq!(params.forks.len(), 2);
+        assert_eq!(params.rules_at(2).unwrap().consensus_type, ConsensusType::PoW);
+        assert_eq!(params.rules_at(3).unwrap().consensus_type, ConsensusType::DPoS);
+        assert_eq!(params.schedule_hash(), spec.chain_params().schedule_hash());
+        assert_ne!(params.schedule_hash(), ChainSpec::regtest().chain_params().schedule_hash());
+    }
+
+    #[test]
+    fn data_file_must_belong_to_spec() {
+        let spec = ChainSpec::regtest();
+        let genesis_hash = spec.genesis_block().header.hash;
+        assert!(spec.check_data(&spec.chain_id, &genesis_hash).is_ok());
+        assert!(spec.check_data("", "旧版数据文件").is_ok());
+
+        assert!(spec.check_data("rust-chain-mainnet", &genesis_hash).is_err());
+        let mainnet_genesis = ChainSpec::mainnet().genesis_block().header.hash;
+        assert!(spec.check_data(&spec.chain_id, &mainnet_genesis).is_err());
+    }
+}
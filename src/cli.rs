@@ -1,11 +1,13 @@
 use crate::blockchain::Blockchain;
 use crate::block::{Transaction};
-use crate::p2p_node::P2PNode;
-use ring::signature::{Ed25519KeyPair, KeyPair};
+use crate::miner::{self, MiningService};
+use crate::misbehavior::DEFAULT_BAN_HOURS;
+use crate::p2p_node::{describe_services, P2PNode};
+use ring::signature::KeyPair;
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::io::{self, Write};
-use std::collections::HashMap;
 
 /// 生成密钥对 CLI
 pub fn generate_keypair_cli() {
@@ -63,13 +65,28 @@ pub fn add_signed_transaction_cli(blockchain: &Arc<Mutex<Blockchain>>) {
         }
     };
 
+    print!("输入手续费 (默认 0): ");
+    io::stdout().flush().unwrap();
+    let mut fee_str = String::new();
+    io::stdin().read_line(&mut fee_str).unwrap();
+    let fee: u64 = match fee_str.trim() {
+        "" => 0,
+        fee_str => match fee_str.parse() {
+            Ok(num) => num,
+            Err(_) => {
+                println!("❌ 无效手续费");
+                return;
+            }
+        },
+    };
+
     // 生成临时的密钥对用于签名（实际项目中应该从安全存储中获取）
     let keypair = Transaction::generate_keypair();
 
     println!("🔐 已生成临时密钥对用于签名");
     println!("公钥: {}", hex::encode(keypair.public_key().as_ref()));
 
-    let transaction = Transaction::new_signed(sender, receiver, amount, &keypair);
+    let transaction = Transaction::new_signed(sender, receiver, amount, fee, &keypair);
     match blockchain.lock().unwrap().add_transaction(transaction) {
         Ok(_) => println!("✅ 签名交易添加成功!"),
         Err(e) => println!("❌ 签名交易添加失败: {}", e),
@@ -158,7 +175,22 @@ pub fn add_transaction_cli(blockchain: &Arc<Mutex<Blockchain>>) {
         }
     };
 
-    let transaction = Transaction::new(sender, receiver, amount);
+    print!("输入手续费 (默认 0): ");
+    io::stdout().flush().unwrap();
+    let mut fee_str = String::new();
+    io::stdin().read_line(&mut fee_str).unwrap();
+    let fee: u64 = match fee_str.trim() {
+        "" => 0,
+        fee_str => match fee_str.parse() {
+            Ok(num) => num,
+            Err(_) => {
+                println!("❌ 无效手续费");
+                return;
+            }
+        },
+    };
+
+    let transaction = Transaction::new(sender, receiver, amount).with_fee(fee);
     match blockchain.lock().unwrap().add_transaction(transaction) {
         Ok(_) => println!("✅ 交易添加成功!"),
         Err(e) => println!("❌ 交易添加失败: {}", e),
@@ -173,15 +205,79 @@ pub fn mine_block_cli(blockchain: &Arc<Mutex<Blockchain>>) {
     io::stdin().read_line(&mut miner).unwrap();
     let miner = miner.trim().to_string();
 
-    match blockchain.lock().unwrap().mine_pending_transactions(miner) {
-        Ok(block) => {
+    match miner::mine_block(blockchain, miner, miner::default_threads(), &AtomicBool::new(false)) {
+        Ok((block, stats)) => {
             println!("✅ 新区块挖矿成功!");
             println!("区块信息: {}", block);
+            println!("⛏️ 算力: {:.0} H/s ({} 线程, {} 次哈希, 耗时 {:.2} 秒)",
+                     stats.hashrate(), stats.threads, stats.hashes, stats.elapsed.as_secs_f64());
         }
         Err(e) => println!("❌ 挖矿失败: {}", e),
     }
 }
 
+/// 挖矿服务菜单 CLI
+pub fn mining_service_menu(mining_service: &Arc<MiningService>) {
+    loop {
+        let status = mining_service.status();
+        println!("\n⛏️ 挖矿服务");
+        println!("=====================================");
+        println!("状态: {}", if status.running { "运行中" } else { "已停止" });
+        println!("奖励地址: {}", if status.coinbase_address.is_empty() { "未设置" } else { &status.coinbase_address });
+        println!("\n1. 启动挖矿服务");
+        println!("2. 停止挖矿服务");
+        println!("3. 设置奖励地址");
+        println!("4. 查看挖矿统计");
+        println!("5. 返回主菜单");
+        print!("输入选择 (1-5): ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+        let choice = input.trim();
+
+        match choice {
+            "1" => {
+                print!("输入挖矿线程数 (回车默认 {}): ", miner::default_threads());
+                io::stdout().flush().unwrap();
+                let mut threads_input = String::new();
+                io::stdin().read_line(&mut threads_input).unwrap();
+                let threads = threads_input.trim().parse().unwrap_or_else(|_| miner::default_threads());
+
+                if let Err(e) = mining_service.start(threads) {
+                    println!("❌ 启动挖矿服务失败: {}", e);
+                }
+            }
+            "2" => match mining_service.stop() {
+                Ok(_) => println!("✅ 已请求停止挖矿服务"),
+                Err(e) => println!("❌ 停止挖矿服务失败: {}", e),
+            },
+            "3" => {
+                print!("输入奖励地址: ");
+                io::stdout().flush().unwrap();
+                let mut address = String::new();
+                io::stdin().read_line(&mut address).unwrap();
+
+                match mining_service.set_coinbase_address(address.trim().to_string()) {
+                    Ok(_) => println!("✅ 奖励地址已设置"),
+                    Err(e) => println!("❌ 设置失败: {}", e),
+                }
+            }
+            "4" => {
+                println!("📊 挖矿统计:");
+                println!("  线程数: {}", status.threads);
+                println!("  已挖出区块: {}", status.blocks_mined);
+                if let Some(height) = status.last_block_height {
+                    println!("  最近区块高度: {}", height);
+                }
+                println!("  最近算力: {:.0} H/s", status.last_hashrate);
+            }
+            "5" => break,
+            _ => println!("❌ 无效选择，请重新输入."),
+        }
+    }
+}
+
 /// 查看余额 CLI
 pub fn view_balance_cli(blockchain: &Arc<Mutex<Blockchain>>) {
     print!("输入地址: ");
@@ -190,8 +286,21 @@ pub fn view_balance_cli(blockchain: &Arc<Mutex<Blockchain>>) {
     io::stdin().read_line(&mut address).unwrap();
     let address = address.trim();
 
-    let balance = blockchain.lock().unwrap().get_balance(address);
+    let blockchain = blockchain.lock().unwrap();
+    let balance = blockchain.get_balance(address);
     println!("{} 的余额: {}", address, balance);
+    let immature = blockchain.immature_balance(address);
+    if immature > 0 {
+        println!("⏳ 未成熟的出块奖励: {} (可花费: {})", immature, balance.saturating_sub(immature));
+    }
+
+    let rewards = blockchain.get_staking_rewards(address);
+    if !rewards.is_empty() {
+        println!("💰 累计质押奖励: {}", blockchain.get_total_staking_rewards(address));
+        for reward in rewards.iter().rev().take(10) {
+            println!("  高度 {}: {:?} {} (验证者: {})", reward.height, reward.kind, reward.amount, reward.validator);
+        }
+    }
 }
 
 /// Solana 演示 CLI
@@ -218,7 +327,7 @@ pub fn solana_demo() {
 }
 
 /// P2P 菜单 CLI
-pub fn p2p_menu(_blockchain: &Arc<Mutex<Blockchain>>, p2p_node: &mut P2PNode) {
+pub fn p2p_menu(blockchain: &Arc<Mutex<Blockchain>>, p2p_node: &mut P2PNode) {
     loop {
         println!("\n🌐 P2P 网络操作");
         println!("=====================================");
@@ -229,8 +338,13 @@ pub fn p2p_menu(_blockchain: &Arc<Mutex<Blockchain>>, p2p_node: &mut P2PNode) {
         println!("5. 广播区块");
         println!("6. 同步区块链");
         println!("7. 广播同步状态");
-        println!("8. 返回主菜单");
-        print!("输入选择 (1-8): ");
+        println!("8. 广播最终性投票");
+        println!("9. 查看已知节点地址");
+        println!("10. 封禁节点");
+        println!("11. 解除封禁");
+        println!("12. 停止 P2P 节点");
+        println!("13. 返回主菜单");
+        print!("输入选择 (1-13): ");
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
@@ -265,13 +379,20 @@ pub fn p2p_menu(_blockchain: &Arc<Mutex<Blockchain>>, p2p_node: &mut P2PNode) {
                 }
             }
             "3" => {
-                let peers = p2p_node.get_peers();
+                let peers = p2p_node.peer_infos();
                 if peers.is_empty() {
                     println!("📭 没有连接的对等节点");
                 } else {
                     println!("🔗 连接的对等节点:");
                     for peer in peers {
-                        println!("  - {}", peer);
+                        let direction = if peer.outbound { "出站" } else { "入站" };
+                        let latency = peer.latency
+                            .map(|latency| format!("{:.1} ms", latency.as_secs_f64() * 1000.0))
+                            .unwrap_or_else(|| "未知".to_string());
+                        println!("  - {} ({}，协议版本 {}，高度 {}，服务 {}，延迟 {}，{} 秒前活跃，违规分数 {})",
+                                 peer.address, direction, peer.version.protocol_version, peer.version.best_height,
+                                 describe_services(peer.version.services), latency, peer.last_seen.elapsed().as_secs(),
+                                 peer.misbehavior);
                     }
                 }
             }
@@ -308,7 +429,13 @@ pub fn p2p_menu(_blockchain: &Arc<Mutex<Blockchain>>, p2p_node: &mut P2PNode) {
                 }
             }
             "5" => {
-                println!("💡 区块广播功能需要进一步实现");
+                let latest_block = blockchain.lock().unwrap().get_latest_block().clone();
+                let height = latest_block.height;
+                if let Err(e) = p2p_node.broadcast_block(latest_block) {
+                    println!("❌ 广播区块失败: {}", e);
+                } else {
+                    println!("✅ 最新区块 {} 已广播到网络", height);
+                }
             }
             "6" => {
                 let peers = p2p_node.get_peers();
@@ -348,7 +475,106 @@ pub fn p2p_menu(_blockchain: &Arc<Mutex<Blockchain>>, p2p_node: &mut P2PNode) {
                     println!("✅ 同步状态已广播到所有节点");
                 }
             }
-            "8" => break,
+            "8" => {
+                match p2p_node.broadcast_finality_votes() {
+                    Ok(0) => println!("ℹ️ 本节点不是验证者或已对最新区块投票"),
+                    Ok(count) => println!("✅ 已广播 {} 条最终性投票", count),
+                    Err(e) => println!("❌ 广播投票失败: {}", e),
+                }
+            }
+            "9" => {
+                let address_book = p2p_node.address_book.lock().unwrap();
+                if address_book.is_empty() {
+                    println!("📭 地址簿为空");
+                } else {
+                    println!("📇 已知节点地址 ({} 个):", address_book.len());
+                    let format_time = |time: Option<chrono::DateTime<chrono::Utc>>| {
+                        time.map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "从未".to_string())
+                    };
+                    let now = chrono::Utc::now();
+                    for known in address_book.entries() {
+                        let banned = if known.is_banned(now) {
+                            format!("，封禁至 {}", format_time(known.banned_until))
+                        } else {
+                            String::new()
+                        };
+                        println!("  - {}{} (最近成功 {}，最近失败 {}，连续失败 {} 次{})",
+                                 known.address, if known.seed { " [种子]" } else { "" },
+                                 format_time(known.last_success), format_time(known.last_failure), known.failures, banned);
+                    }
+                }
+            }
+            "10" => {
+                print!("输入要封禁的节点地址 (例如: 127.0.0.1:7879): ");
+                io::stdout().flush().unwrap();
+                let mut peer_input = String::new();
+                io::stdin().read_line(&mut peer_input).unwrap();
+                let peer_addr: SocketAddr = match peer_input.trim().parse() {
+                    Ok(addr) => addr,
+                    Err(_) => {
+                        println!("❌ 无效地址格式");
+                        continue;
+                    }
+                };
+
+                print!("输入封禁时长（小时，默认 {}）: ", DEFAULT_BAN_HOURS);
+                io::stdout().flush().unwrap();
+                let mut hours_input = String::new();
+                io::stdin().read_line(&mut hours_input).unwrap();
+                let hours: i64 = match hours_input.trim() {
+                    "" => DEFAULT_BAN_HOURS,
+                    hours => match hours.parse() {
+                        Ok(hours) if hours > 0 => hours,
+                        _ => {
+                            println!("❌ 无效时长");
+                            continue;
+                        }
+                    },
+                };
+
+                p2p_node.ban_peer(peer_addr, chrono::Duration::hours(hours));
+            }
+            "11" => {
+                let banned = p2p_node.address_book.lock().unwrap().banned();
+                if banned.is_empty() {
+                    println!("📭 没有被封禁的节点");
+                    continue;
+                }
+                println!("🚫 被封禁的节点:");
+                for known in &banned {
+                    if let Some(banned_until) = known.banned_until {
+                        println!("  - {} (封禁至 {})", known.address, banned_until.format("%Y-%m-%d %H:%M:%S"));
+                    }
+                }
+
+                print!("输入要解除封禁的 IP 或节点地址: ");
+                io::stdout().flush().unwrap();
+                let mut ip_input = String::new();
+                io::stdin().read_line(&mut ip_input).unwrap();
+                let ip_input = ip_input.trim();
+                let ip: IpAddr = match ip_input.parse::<SocketAddr>() {
+                    Ok(addr) => addr.ip(),
+                    Err(_) => match ip_input.parse() {
+                        Ok(ip) => ip,
+                        Err(_) => {
+                            println!("❌ 无效地址格式");
+                            continue;
+                        }
+                    },
+                };
+
+                if !p2p_node.unban_peer(ip) {
+                    println!("ℹ️ {} 未被封禁", ip);
+                }
+            }
+            "12" => {
+                if p2p_node.is_running() {
+                    p2p_node.stop();
+                } else {
+                    println!("ℹ️ P2P 节点未运行");
+                }
+            }
+            "13" => break,
             _ => println!("❌ 无效选择，请重新输入."),
         }
     }
@@ -0,0 +1,192 @@
+use sha2::{Digest, Sha256};
+
+/// 帧起始魔数
+pub const MAGIC: [u8; 4] = *b"RCHN";
+/// 帧头长度：魔数(4) + 消息类型(1) + 负载长度(4) + 校验和(4)
+pub const HEADER_LEN: usize = 13;
+/// 单个消息负载的最大字节数
+pub const MAX_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+
+/// 一个完整的消息帧
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub message_type: u8,
+    pub payload: Vec<u8>,
+}
+
+/// 负载校验和（SHA-256 前 4 字节）
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let digest = Sha256::digest(payload);
+    [digest[0], digest[1], digest[2], digest[3]]
+}
+
+/// 编码消息帧：魔数 | 消息类型 | 负载长度（大端）| 校验和 | 负载
+pub fn encode_frame(message_type: u8, payload: &[u8]) -> Result<Vec<u8>, String> {
+    if payload.len() > MAX_MESSAGE_SIZE {
+        return Err(format!("消息大小 {} 超过上限 {}", payload.len(), MAX_MESSAGE_SIZE));
+    }
+
+    let mut data = Vec::with_capacity(HEADER_LEN + payload.len());
+    data.extend_from_slice(&MAGIC);
+    data.push(message_type);
+    data.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    data.extend_from_slice(&checksum(payload));
+    data.extend_from_slice(payload);
+    Ok(data)
+}
+
+/// 流式帧解码器 - 累积任意切分的读取数据，逐个取出完整的帧
+///
+/// 解码出错（魔数错误、超长、校验失败）后流已无法重新对齐，调用方应断开连接
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    /// 创建空的解码器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加从连接读取到的数据
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// 取出下一个完整的帧；数据不足时返回 None
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, String> {
+        // 魔数不完整时也尽早校验已收到的部分
+        let magic_len = self.buffer.len().min(MAGIC.len());
+        if self.buffer[..magic_len] != MAGIC[..magic_len] {
+            return Err("消息帧魔数错误".to_string());
+        }
+        if self.buffer.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let message_type = self.buffer[4];
+        let length = u32::from_be_bytes([self.buffer[5], self.buffer[6], self.buffer[7], self.buffer[8]]) as usize;
+        if length > MAX_MESSAGE_SIZE {
+            return Err(format!("消息大小 {} 超过上限 {}", length, MAX_MESSAGE_SIZE));
+        }
+        if self.buffer.len() < HEADER_LEN + length {
+            return Ok(None);
+        }
+
+        let expected_checksum = [self.buffer[9], self.buffer[10], self.buffer[11], self.buffer[12]];
+        let payload = self.buffer[HEADER_LEN..HEADER_LEN + length].to_vec();
+        if checksum(&payload) != expected_checksum {
+            return Err("消息帧校验和错误".to_string());
+        }
+
+        self.buffer.drain(..HEADER_LEN + length);
+        Ok(Some(Frame { message_type, payload }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(decoder: &mut FrameDecoder) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.next_frame().unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn decodes_single_frame() {
+        let data = encode_frame(7, b"hello").unwrap();
+        let mut decoder = FrameDecoder::new();
+        decoder.extend(&data);
+
+        assert_eq!(decode_all(&mut decoder), vec![Frame { message_type: 7, payload: b"hello".to_vec() }]);
+    }
+
+    #[test]
+    fn decodes_frame_fed_byte_by_byte() {
+        let payload: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        let data = encode_frame(4, &payload).unwrap();
+        let mut decoder = FrameDecoder::new();
+
+        for (i, byte) in data.iter().enumerate() {
+            decoder.extend(std::slice::from_ref(byte));
+            let frame = decoder.next_frame().unwrap();
+            if i + 1 < data.len() {
+                assert!(frame.is_none());
+            } else {
+                assert_eq!(frame, Some(Frame { message_type: 4, payload: payload.clone() }));
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_multiple_frames_across_uneven_chunks() {
+        let payloads: Vec<Vec<u8>> = vec![Vec::new(), vec![1; 5000], b"abc".to_vec(), vec![9; 70_000]];
+        let stream: Vec<u8> = payloads
+            .iter()
+            .enumerate()
+            .flat_map(|(i, payload)| encode_frame(i as u8, payload).unwrap())
+            .collect();
+
+        for chunk_size in [1, 3, 13, 14, 4096, 100_000] {
+            let mut decoder = FrameDecoder::new();
+            let mut frames = Vec::new();
+            for chunk in stream.chunks(chunk_size) {
+                decoder.extend(chunk);
+                frames.extend(decode_all(&mut decoder));
+            }
+
+            let expected: Vec<Frame> = payloads
+                .iter()
+                .enumerate()
+                .map(|(i, payload)| Frame { message_type: i as u8, payload: payload.clone() })
+                .collect();
+            assert_eq!(frames, expected, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn keeps_partial_frame_after_complete_one() {
+        let first = encode_frame(1, b"first").unwrap();
+        let second = encode_frame(2, b"second").unwrap();
+        let mut decoder = FrameDecoder::new();
+        decoder.extend(&first);
+        decoder.extend(&second[..HEADER_LEN + 2]);
+
+        assert_eq!(decode_all(&mut decoder), vec![Frame { message_type: 1, payload: b"first".to_vec() }]);
+        decoder.extend(&second[HEADER_LEN + 2..]);
+        assert_eq!(decode_all(&mut decoder), vec![Frame { message_type: 2, payload: b"second".to_vec() }]);
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut decoder = FrameDecoder::new();
+        decoder.extend(b"RX");
+        assert!(decoder.next_frame().is_err());
+    }
+
+    #[test]
+    fn rejects_corrupted_payload() {
+        let mut data = encode_frame(1, b"payload").unwrap();
+        *data.last_mut().unwrap() ^= 0xff;
+        let mut decoder = FrameDecoder::new();
+        decoder.extend(&data);
+        assert!(decoder.next_frame().is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_length_before_payload_arrives() {
+        let mut header = MAGIC.to_vec();
+        header.push(1);
+        header.extend_from_slice(&(MAX_MESSAGE_SIZE as u32 + 1).to_be_bytes());
+        header.extend_from_slice(&[0; 4]);
+        let mut decoder = FrameDecoder::new();
+        decoder.extend(&header);
+        assert!(decoder.next_frame().is_err());
+        assert!(encode_frame(1, &vec![0; MAX_MESSAGE_SIZE + 1]).is_err());
+    }
+}
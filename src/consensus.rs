@@ -1,17 +1,23 @@
-use crate::block::{Block, Transaction};
+use crate::block::{AuthorityVote, Block, BlockHeader, Transaction, TransactionKind};
 use crate::blockchain::Blockchain;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+
+/// 质押交易的接收地址（锁定中的质押和委托记在该地址名下）
+pub const STAKING_ADDRESS: &str = "staking";
 
 /// 共识算法类型
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ConsensusType {
     PoW,  // 工作量证明
     PoS,  // 权益证明
     DPoS, // 委托权益证明
+    PoA,  // 权威证明
 }
 
 /// 共识算法 trait
+#[allow(dead_code)]
 pub trait Consensus {
     /// 验证区块
     fn validate_block(&self, block: &Block, previous_block: &Block) -> bool;
@@ -35,6 +41,8 @@ pub struct StakeInfo {
     pub amount: u64,
     pub start_time: chrono::DateTime<chrono::Utc>,
     pub validator: String,
+    #[serde(default)]
+    pub public_key: Option<String>, // 验证者签名公钥（用于核验作恶证据）
 }
 
 /// 委托信息
@@ -45,11 +53,305 @@ pub struct DelegationInfo {
     pub candidate: String,
 }
 
+/// 罚没处理方式
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SlashMode {
+    Burn,         // 直接销毁
+    Redistribute, // 按质押比例分配给其他验证者
+}
+
+/// 罚没参数配置（由链配置决定，所有节点一致）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlashingConfig {
+    pub double_sign_penalty_percent: u64, // 双签罚没比例（百分比）
+    pub downtime_penalty_percent: u64,    // 停机罚没比例（百分比）
+    pub max_missed_slots: u64,            // 连续漏块达到该数量视为停机
+    pub jail_blocks: u64,                 // 监禁的区块数
+    pub mode: SlashMode,
+}
+
+impl Default for SlashingConfig {
+    fn default() -> Self {
+        Self {
+            double_sign_penalty_percent: 10,
+            downtime_penalty_percent: 1,
+            max_missed_slots: 10,
+            jail_blocks: 100,
+            mode: SlashMode::Burn,
+        }
+    }
+}
+
+impl SlashingConfig {
+    /// 是否为默认配置（默认配置不写入链配置文件，保持旧链的创世哈希不变）
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 作恶证据
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Evidence {
+    /// 双签：同一验证者在同一高度签名了两个不同的区块头
+    DoubleSign {
+        validator: String,
+        height: u64,
+        first: Box<BlockHeader>,
+        second: Box<BlockHeader>,
+    },
+    /// 停机：验证者连续错过出块槽位（由链上出块记录推导，不能作为交易提交）
+    Downtime {
+        validator: String,
+        missed_slots: u64,
+    },
+}
+
+impl Evidence {
+    /// 证据涉及的验证者
+    pub fn validator(&self) -> &str {
+        match self {
+            Evidence::DoubleSign { validator, .. } => validator,
+            Evidence::Downtime { validator, .. } => validator,
+        }
+    }
+
+    /// 证据唯一标识（用于防止重复处罚）
+    pub fn id(&self) -> String {
+        let data = match self {
+            Evidence::DoubleSign { validator, height, first, second } => {
+                // 两个区块头的顺序不影响标识
+                let (a, b) = if first.hash <= second.hash {
+                    (&first.hash, &second.hash)
+                } else {
+                    (&second.hash, &first.hash)
+                };
+                format!("double_sign:{}:{}:{}:{}", validator, height, a, b)
+            }
+            Evidence::Downtime { validator, missed_slots } => {
+                format!("downtime:{}:{}", validator, missed_slots)
+            }
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(data.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// 罚没记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlashRecord {
+    pub validator: String,
+    pub reason: String,
+    pub amount: u64,
+    pub height: u64,
+    pub jailed_until: u64,
+}
+
+/// 罚没模块 - 漏块统计、监禁名单和证据处理
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Slashing {
+    pub config: SlashingConfig,
+    pub missed_slots: HashMap<String, u64>,
+    pub jailed: HashMap<String, u64>, // 验证者 -> 解除监禁的区块高度
+    pub processed_evidence: HashSet<String>,
+    pub records: Vec<SlashRecord>,
+    pub total_burned: u64,
+}
+
+impl Slashing {
+    /// 验证者在指定高度是否处于监禁状态
+    pub fn is_jailed(&self, validator: &str, height: u64) -> bool {
+        self.jailed.get(validator).is_some_and(|until| height < *until)
+    }
+
+    /// 记录一个出块槽位的结果，连续漏块达到阈值时返回停机证据
+    pub fn record_slot(&mut self, expected: &str, producer: &str) -> Option<Evidence> {
+        self.missed_slots.remove(producer);
+        if expected == producer {
+            return None;
+        }
+
+        let missed = self.missed_slots.entry(expected.to_string()).or_insert(0);
+        *missed += 1;
+        if *missed >= self.config.max_missed_slots {
+            Some(Evidence::Downtime {
+                validator: expected.to_string(),
+                missed_slots: *missed,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// 核验证据并执行罚没，返回被罚没的金额
+    pub fn handle_evidence(
+        &mut self,
+        stakes: &mut HashMap<String, StakeInfo>,
+        evidence: &Evidence,
+        height: u64,
+    ) -> Result<u64, String> {
+        let evidence_id = evidence.id();
+        if self.processed_evidence.contains(&evidence_id) {
+            return Err("证据已处理".to_string());
+        }
+
+        let validator = evidence.validator().to_string();
+        let stake = stakes.get(&validator).ok_or("未找到验证者质押信息")?;
+
+        let (percent, reason) = match evidence {
+            Evidence::DoubleSign { height: evidence_height, first, second, .. } => {
+                let public_key = stake.public_key.as_ref().ok_or("验证者未登记签名公钥")?;
+                if first.hash == second.hash {
+                    return Err("两个区块头相同，不构成双签".to_string());
+                }
+                for header in [first, second] {
+                    if header.validator.as_deref() != Some(validator.as_str())
+                        || header.public_key.as_ref() != Some(public_key)
+                        || !header.verify_signature(*evidence_height)
+                    {
+                        return Err("区块头签名无效".to_string());
+                    }
+                }
+                (self.config.double_sign_penalty_percent, "双签")
+            }
+            Evidence::Downtime { missed_slots, .. } => {
+                let recorded = self.missed_slots.get(&validator).copied().unwrap_or(0);
+                if *missed_slots < self.config.max_missed_slots || recorded < *missed_slots {
+                    return Err("漏块数量未达到停机罚没阈值".to_string());
+                }
+                self.missed_slots.remove(&validator);
+                (self.config.downtime_penalty_percent, "停机")
+            }
+        };
+
+        let amount = self.slash(stakes, &validator, percent);
+        let jailed_until = height + self.config.jail_blocks;
+        self.jailed.insert(validator.clone(), jailed_until);
+        self.processed_evidence.insert(evidence_id);
+        self.records.push(SlashRecord {
+            validator,
+            reason: reason.to_string(),
+            amount,
+            height,
+            jailed_until,
+        });
+
+        Ok(amount)
+    }
+
+    /// 按比例罚没质押，根据配置销毁或分配给其他验证者
+    fn slash(&mut self, stakes: &mut HashMap<String, StakeInfo>, validator: &str, percent: u64) -> u64 {
+        let amount = match stakes.get_mut(validator) {
+            Some(stake) => {
+                let amount = (stake.amount as u128 * percent.min(100) as u128 / 100) as u64;
+                stake.amount -= amount;
+                amount
+            }
+            None => return 0,
+        };
+
+        let mut remaining = amount;
+        if self.config.mode == SlashMode::Redistribute {
+            let total: u64 = stakes
+                .iter()
+                .filter(|(v, _)| v.as_str() != validator && !self.jailed.contains_key(*v))
+                .map(|(_, s)| s.amount)
+                .sum();
+
+            for (v, stake) in stakes.iter_mut() {
+                if v.as_str() == validator || self.jailed.contains_key(v) {
+                    continue;
+                }
+                let share = (amount as u128 * stake.amount as u128)
+                    .checked_div(total as u128)
+                    .unwrap_or(0) as u64;
+                stake.amount += share;
+                remaining -= share;
+            }
+        }
+
+        // 无法分配的部分（含取整余数）直接销毁
+        self.total_burned += remaining;
+        amount
+    }
+
+    /// 监禁期满后解除监禁
+    pub fn unjail(&mut self, validator: &str, height: u64) -> Result<(), String> {
+        match self.jailed.get(validator) {
+            None => Err("验证者未被监禁".to_string()),
+            Some(until) if height < *until => {
+                Err(format!("监禁期未满，需到区块高度 {} 才能解除", until))
+            }
+            Some(_) => {
+                self.jailed.remove(validator);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// 区块头是否由出块验证者登记的签名公钥签名（未登记公钥的验证者不能出块）
+pub fn signed_by_registered_key(stakes: &HashMap<String, StakeInfo>, block: &Block) -> bool {
+    block.header.validator
+        .as_ref()
+        .and_then(|validator| stakes.get(validator))
+        .and_then(|stake| stake.public_key.as_ref())
+        .is_some_and(|public_key| {
+            block.header.public_key.as_ref() == Some(public_key) && block.header.verify_signature(block.height)
+        })
+}
+
+/// 由区块哈希推导确定性的随机种子，保证所有节点选出相同的验证者
+pub fn seed_from_hash(hash: &str) -> u64 {
+    let mut hasher = Sha256::new();
+    hasher.update(hash.as_bytes());
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// 解绑中的委托（解绑期满前不计入权重，也不能取回）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnbondingEntry {
+    pub delegator: String,
+    pub candidate: String,
+    pub amount: u64,
+    pub release_height: u64,
+}
+
+/// 质押奖励类型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RewardKind {
+    Block,      // 出块奖励
+    Commission, // 候选人佣金
+    Delegation, // 委托收益
+}
+
+/// 一笔区块奖励的分配结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RewardShare {
+    pub address: String,
+    pub amount: u64,
+    pub kind: RewardKind,
+}
+
+/// 质押奖励历史记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StakingReward {
+    pub height: u64,
+    pub validator: String,
+    pub amount: u64,
+    pub kind: RewardKind,
+}
+
 /// PoS 共识实现
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProofOfStake {
     pub stakes: HashMap<String, StakeInfo>,
     pub minimum_stake: u64,
+    #[serde(default)]
+    pub slashing: Slashing,
 }
 
 impl ProofOfStake {
@@ -57,33 +359,80 @@ impl ProofOfStake {
         Self {
             stakes: HashMap::new(),
             minimum_stake,
+            slashing: Slashing::default(),
         }
     }
 
-    /// 质押代币
-    pub fn stake(&mut self, validator: String, amount: u64) -> Result<(), String> {
-        if amount < self.minimum_stake {
+    /// 质押代币（对同一验证者的重复质押累加金额）
+    pub fn stake(&mut self, validator: String, amount: u64, time: chrono::DateTime<chrono::Utc>) -> Result<(), String> {
+        let current = self.stakes.get(&validator).map(|stake| stake.amount).unwrap_or(0);
+        if current.saturating_add(amount) < self.minimum_stake {
             return Err(format!("质押金额不足，最小需要 {}", self.minimum_stake));
         }
 
-        let stake_info = StakeInfo {
-            amount,
-            start_time: chrono::Utc::now(),
-            validator: validator.clone(),
-        };
-
-        self.stakes.insert(validator, stake_info);
+        self.stakes
+            .entry(validator.clone())
+            .or_insert(StakeInfo {
+                amount: 0,
+                start_time: time,
+                validator,
+                public_key: None,
+            })
+            .amount += amount;
         Ok(())
     }
 
-    /// 取消质押
-    pub fn unstake(&mut self, validator: String) -> Result<(), String> {
-        if let Some(_) = self.stakes.remove(&validator) {
-            Ok(())
-        } else {
-            Err("未找到质押信息".to_string())
+    /// 执行质押交易，返回需要立即退还给发送者的金额
+    pub fn apply_transaction(&mut self, transaction: &Transaction, height: u64) -> Result<Option<u64>, String> {
+        match &transaction.kind {
+            TransactionKind::Stake => {
+                self.stake(transaction.sender.clone(), transaction.amount, transaction.timestamp)?;
+                Ok(None)
+            }
+            TransactionKind::Unstake => self.unstake(transaction.sender.clone()).map(Some),
+            TransactionKind::RegisterValidatorKey { public_key } => {
+                self.set_validator_key(&transaction.sender, public_key.clone())?;
+                Ok(None)
+            }
+            TransactionKind::Evidence { evidence } => {
+                self.submit_evidence(evidence, height)?;
+                Ok(None)
+            }
+            TransactionKind::Unjail => {
+                self.slashing.unjail(&transaction.sender, height)?;
+                Ok(None)
+            }
+            _ => Err("不是 PoS 质押交易".to_string()),
         }
     }
+
+    /// 锁定中的全部质押（切换共识时退还）
+    pub fn locked_funds(&self) -> Vec<(String, u64)> {
+        self.stakes.iter().map(|(validator, stake)| (validator.clone(), stake.amount)).collect()
+    }
+
+    /// 登记验证者签名公钥（只能为交易发送者自己的质押登记一次，已登记的公钥不能被替换）
+    pub fn set_validator_key(&mut self, validator: &str, public_key: String) -> Result<(), String> {
+        let stake = self.stakes.get_mut(validator).ok_or("未找到质押信息")?;
+        if stake.public_key.is_some() {
+            return Err(format!("验证者 {} 已登记签名公钥，不能替换", validator));
+        }
+        stake.public_key = Some(public_key);
+        Ok(())
+    }
+
+    /// 提交作恶证据，返回被罚没的金额
+    pub fn submit_evidence(&mut self, evidence: &Evidence, height: u64) -> Result<u64, String> {
+        self.slashing.handle_evidence(&mut self.stakes, evidence, height)
+    }
+
+    /// 取消质押，返回退还的金额
+    pub fn unstake(&mut self, validator: String) -> Result<u64, String> {
+        self.stakes
+            .remove(&validator)
+            .map(|stake| stake.amount)
+            .ok_or_else(|| "未找到质押信息".to_string())
+    }
 }
 
 impl Consensus for ProofOfStake {
@@ -93,27 +442,44 @@ impl Consensus for ProofOfStake {
             return false;
         }
 
-        // 验证验证者
-        if let Some(validator) = &block.header.validator {
-            if !self.stakes.contains_key(validator) {
-                return false;
-            }
+        // 尚无验证者登记签名公钥（如刚切换到 PoS）时任何签名有效的区块都可以上链
+        if self.stakes.values().all(|stake| stake.public_key.is_none()) {
+            return true;
         }
 
-        true
+        // 否则验证者必须已质押、未被监禁，且区块由其登记的签名公钥签名
+        match &block.header.validator {
+            Some(validator) => {
+                !self.slashing.is_jailed(validator, block.height) && signed_by_registered_key(&self.stakes, block)
+            }
+            None => false,
+        }
     }
 
-    fn select_validator(&self, _blockchain: &Blockchain) -> Option<String> {
-        if self.stakes.is_empty() {
+    fn select_validator(&self, blockchain: &Blockchain) -> Option<String> {
+        let height = blockchain.get_length() as u64;
+        let mut validators: Vec<(&String, u64)> = self.stakes
+            .iter()
+            .filter(|(v, s)| s.public_key.is_some() && !self.slashing.is_jailed(v, height))
+            .map(|(v, s)| (v, s.amount))
+            .collect();
+        validators.sort_by(|a, b| a.0.cmp(b.0));
+
+        let total: u64 = validators.iter().map(|(_, w)| w).sum();
+        if total == 0 {
             return None;
         }
 
-        // 简单的随机选择（实际应该使用更复杂的算法）
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        let validators: Vec<String> = self.stakes.keys().cloned().collect();
-        let index = rng.gen_range(0..validators.len());
-        Some(validators[index].clone())
+        // 以最新区块哈希为种子按质押权重选择，其他节点可复现同一结果
+        let mut point = seed_from_hash(&blockchain.get_latest_block().header.hash) % total;
+        for (validator, weight) in validators {
+            if point < weight {
+                return Some(validator.clone());
+            }
+            point -= weight;
+        }
+
+        None
     }
 
     fn calculate_validator_weight(&self, _blockchain: &Blockchain, validator: &str) -> u64 {
@@ -127,8 +493,8 @@ impl Consensus for ProofOfStake {
         }
 
         // 检查发送者余额
-        let sender_balance = blockchain.get_balance(&transaction.sender);
-        sender_balance >= transaction.amount
+        let sender_balance = blockchain.spendable_balance(&transaction.sender);
+        sender_balance >= transaction.amount.saturating_add(transaction.fee)
     }
 
     fn get_type(&self) -> ConsensusType {
@@ -136,6 +502,59 @@ impl Consensus for ProofOfStake {
     }
 }
 
+/// 使用确定性种子洗牌（xorshift64），所有节点得到相同顺序
+pub fn shuffle_with_seed<T>(items: &mut [T], seed: u64) {
+    let mut state = seed | 1;
+    for i in (1..items.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let j = (state % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+/// DPoS 出块排班 - 每个纪元选出前 N 名候选人并按洗牌顺序轮流出块
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProducerSchedule {
+    pub producer_count: usize,   // 每个纪元当选的出块人数量
+    pub epoch_length: u64,       // 每个纪元包含的区块数
+    pub slot_duration_secs: i64, // 每个出块槽位的时长（秒）
+    pub epoch_start: u64,        // 当前纪元起始高度
+    pub producers: Vec<String>,  // 当前纪元的出块顺序
+}
+
+impl Default for ProducerSchedule {
+    fn default() -> Self {
+        Self {
+            producer_count: 21,
+            epoch_length: 63,
+            slot_duration_secs: 10,
+            epoch_start: 0,
+            producers: Vec::new(),
+        }
+    }
+}
+
+impl ProducerSchedule {
+    /// 指定高度所在纪元的起始高度
+    pub fn epoch_start_for(&self, height: u64) -> u64 {
+        let epoch_length = self.epoch_length.max(1);
+        height - height % epoch_length
+    }
+
+    /// 排班是否覆盖指定高度
+    pub fn covers(&self, height: u64) -> bool {
+        !self.producers.is_empty() && self.epoch_start == self.epoch_start_for(height)
+    }
+
+    /// 距上一区块经过的时间内被跳过的槽位数
+    pub fn skipped_slots(&self, previous_block: &Block, now: chrono::DateTime<chrono::Utc>) -> u64 {
+        let elapsed = (now - previous_block.header.timestamp).num_seconds();
+        (elapsed.max(0) / self.slot_duration_secs.max(1)) as u64
+    }
+}
+
 /// DPoS 共识实现
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DelegatedProofOfStake {
@@ -144,6 +563,26 @@ pub struct DelegatedProofOfStake {
     pub candidates: Vec<String>,
     pub minimum_stake: u64,
     pub minimum_delegation: u64,
+    #[serde(default)]
+    pub slashing: Slashing,
+    #[serde(default)]
+    pub schedule: ProducerSchedule,
+    #[serde(default)]
+    pub unbonding: Vec<UnbondingEntry>,
+    #[serde(default = "default_unbonding_period")]
+    pub unbonding_period: u64, // 解绑期（区块数）
+    #[serde(default)]
+    pub commission_rates: HashMap<String, u64>, // 候选人佣金比例（百分比）
+    #[serde(default = "default_commission_rate")]
+    pub default_commission_rate: u64,
+}
+
+fn default_unbonding_period() -> u64 {
+    100
+}
+
+fn default_commission_rate() -> u64 {
+    10
 }
 
 impl DelegatedProofOfStake {
@@ -154,19 +593,97 @@ impl DelegatedProofOfStake {
             candidates: Vec::new(),
             minimum_stake,
             minimum_delegation,
+            slashing: Slashing::default(),
+            schedule: ProducerSchedule::default(),
+            unbonding: Vec::new(),
+            unbonding_period: default_unbonding_period(),
+            commission_rates: HashMap::new(),
+            default_commission_rate: default_commission_rate(),
+        }
+    }
+
+    /// 设置候选人佣金比例（百分比）
+    pub fn set_commission_rate(&mut self, candidate: &str, rate: u64) -> Result<(), String> {
+        if rate > 100 {
+            return Err("佣金比例不能超过 100%".to_string());
+        }
+
+        if !self.candidates.iter().any(|c| c == candidate) {
+            return Err(format!("候选人 {} 未注册", candidate));
+        }
+
+        self.commission_rates.insert(candidate.to_string(), rate);
+        Ok(())
+    }
+
+    /// 获取候选人佣金比例（百分比）
+    pub fn commission_rate(&self, candidate: &str) -> u64 {
+        self.commission_rates
+            .get(candidate)
+            .copied()
+            .unwrap_or(self.default_commission_rate)
+    }
+
+    /// 分配区块奖励：出块人先提取佣金，剩余部分按自有质押和委托金额比例分配
+    pub fn distribute_reward(&self, producer: &str, reward: u64) -> Vec<RewardShare> {
+        let commission = (reward as u128 * self.commission_rate(producer) as u128 / 100) as u64;
+        let pool = reward - commission;
+
+        let own_stake = self.stakes.get(producer).map(|s| s.amount).unwrap_or(0);
+        let mut delegations: Vec<&DelegationInfo> = self.delegations
+            .values()
+            .filter(|d| d.candidate == producer)
+            .collect();
+        delegations.sort_by(|a, b| a.delegator.cmp(&b.delegator));
+
+        let total_stake = own_stake + delegations.iter().map(|d| d.amount).sum::<u64>();
+        let mut shares = Vec::new();
+        let mut distributed = 0;
+
+        for delegation in delegations {
+            // 奖励和委托金额的乘积可能超过 u64，按 u128 计算（结果不超过 pool）
+            let amount = (pool as u128 * delegation.amount as u128)
+                .checked_div(total_stake as u128)
+                .unwrap_or(0) as u64;
+            if amount > 0 {
+                distributed += amount;
+                shares.push(RewardShare {
+                    address: delegation.delegator.clone(),
+                    amount,
+                    kind: RewardKind::Delegation,
+                });
+            }
+        }
+
+        // 出块人获得佣金、自有质押对应的收益以及取整余数
+        let producer_reward = reward - distributed;
+        if producer_reward > 0 {
+            let kind = if commission > 0 { RewardKind::Commission } else { RewardKind::Block };
+            shares.insert(0, RewardShare {
+                address: producer.to_string(),
+                amount: producer_reward,
+                kind,
+            });
         }
+
+        shares
     }
 
     /// 注册候选人
-    pub fn register_candidate(&mut self, candidate: String, amount: u64) -> Result<(), String> {
+    pub fn register_candidate(&mut self, candidate: String, amount: u64, time: chrono::DateTime<chrono::Utc>) -> Result<(), String> {
+        if self.candidates.contains(&candidate) {
+            return Err(format!("候选人 {} 已注册", candidate));
+        }
+
         if amount < self.minimum_stake {
             return Err(format!("候选人质押金额不足，最小需要 {}", self.minimum_stake));
         }
 
         let stake_info = StakeInfo {
             amount,
-            start_time: chrono::Utc::now(),
+            start_time: time,
             validator: candidate.clone(),
+            public_key: None,
         };
 
         self.stakes.insert(candidate.clone(), stake_info);
@@ -174,23 +691,173 @@ impl DelegatedProofOfStake {
         Ok(())
     }
 
+    /// 执行质押交易（候选人注册、注销、佣金设置、委托、作恶证据和解除监禁）
+    pub fn apply_transaction(&mut self, transaction: &Transaction, height: u64) -> Result<(), String> {
+        let sender = transaction.sender.clone();
+        match &transaction.kind {
+            TransactionKind::RegisterCandidate => self.register_candidate(sender, transaction.amount, transaction.timestamp),
+            TransactionKind::DeregisterCandidate => self.deregister_candidate(&sender, height),
+            TransactionKind::Delegate { candidate } => self.delegate(sender, candidate.clone(), transaction.amount),
+            TransactionKind::Undelegate { candidate, amount } => {
+                self.undelegate(sender, candidate.clone(), *amount, height).map(|_| ())
+            }
+            TransactionKind::Redelegate { from, to, amount } => self.redelegate(sender, from.clone(), to.clone(), *amount),
+            TransactionKind::RegisterValidatorKey { public_key } => self.set_validator_key(&sender, public_key.clone()),
+            TransactionKind::SetCommission { rate } => self.set_commission_rate(&sender, *rate),
+            TransactionKind::Evidence { evidence } => self.submit_evidence(evidence, height).map(|_| ()),
+            TransactionKind::Unjail => self.slashing.unjail(&sender, height),
+            _ => Err("不是 DPoS 质押交易".to_string()),
+        }
+    }
+
+    /// 锁定中的全部自有质押、委托和解绑中的金额（切换共识时退还）
+    pub fn locked_funds(&self) -> Vec<(String, u64)> {
+        let stakes = self.stakes.iter().map(|(candidate, stake)| (candidate.clone(), stake.amount));
+        let delegations = self.delegations.values().map(|d| (d.delegator.clone(), d.amount));
+        let unbonding = self.unbonding.iter().map(|entry| (entry.delegator.clone(), entry.amount));
+        stakes.chain(delegations).chain(unbonding).collect()
+    }
+
     /// 委托投票
     pub fn delegate(&mut self, delegator: String, candidate: String, amount: u64) -> Result<(), String> {
         if amount < self.minimum_delegation {
             return Err(format!("委托金额不足，最小需要 {}", self.minimum_delegation));
         }
 
-        let delegation_info = DelegationInfo {
-            delegator: delegator.clone(),
-            amount,
-            candidate: candidate.clone(),
-        };
+        if !self.candidates.contains(&candidate) {
+            return Err(format!("候选人 {} 未注册", candidate));
+        }
 
+        // 对同一候选人的重复委托累加金额
         let key = format!("{}:{}", delegator, candidate);
-        self.delegations.insert(key, delegation_info);
+        self.delegations
+            .entry(key)
+            .or_insert(DelegationInfo {
+                delegator,
+                amount: 0,
+                candidate,
+            })
+            .amount += amount;
+        Ok(())
+    }
+
+    /// 从委托中扣减金额，剩余金额必须为 0 或不低于最小委托额
+    fn reduce_delegation(&mut self, delegator: &str, candidate: &str, amount: u64) -> Result<(), String> {
+        let key = format!("{}:{}", delegator, candidate);
+        let delegation = self.delegations
+            .get_mut(&key)
+            .ok_or_else(|| format!("未找到 {} 对 {} 的委托", delegator, candidate))?;
+
+        if amount == 0 || amount > delegation.amount {
+            return Err(format!("撤销金额无效，当前委托金额: {}", delegation.amount));
+        }
+
+        let remaining = delegation.amount - amount;
+        if remaining > 0 && remaining < self.minimum_delegation {
+            return Err(format!("剩余委托金额不能低于 {}", self.minimum_delegation));
+        }
+
+        if remaining == 0 {
+            self.delegations.remove(&key);
+        } else {
+            delegation.amount = remaining;
+        }
+        Ok(())
+    }
+
+    /// 撤销委托，金额进入解绑期，返回解绑完成的区块高度
+    pub fn undelegate(&mut self, delegator: String, candidate: String, amount: u64, height: u64) -> Result<u64, String> {
+        self.reduce_delegation(&delegator, &candidate, amount)?;
+
+        let release_height = height + self.unbonding_period;
+        self.unbonding.push(UnbondingEntry {
+            delegator,
+            candidate,
+            amount,
+            release_height,
+        });
+        Ok(release_height)
+    }
+
+    /// 将委托从一个候选人转移到另一个候选人（立即生效，无需解绑）
+    pub fn redelegate(&mut self, delegator: String, from: String, to: String, amount: u64) -> Result<(), String> {
+        if from == to {
+            return Err("转出和转入的候选人不能相同".to_string());
+        }
+
+        if !self.candidates.contains(&to) {
+            return Err(format!("候选人 {} 未注册", to));
+        }
+
+        if amount < self.minimum_delegation {
+            return Err(format!("委托金额不足，最小需要 {}", self.minimum_delegation));
+        }
+
+        self.reduce_delegation(&delegator, &from, amount)?;
+        self.delegate(delegator, to, amount)
+    }
+
+    /// 注销候选人，自有质押及其收到的全部委托进入解绑期
+    pub fn deregister_candidate(&mut self, candidate: &str, height: u64) -> Result<(), String> {
+        let index = self.candidates
+            .iter()
+            .position(|c| c == candidate)
+            .ok_or_else(|| format!("候选人 {} 未注册", candidate))?;
+        self.candidates.remove(index);
+
+        let release_height = height + self.unbonding_period;
+        if let Some(stake) = self.stakes.remove(candidate) {
+            self.unbonding.push(UnbondingEntry {
+                delegator: candidate.to_string(),
+                candidate: candidate.to_string(),
+                amount: stake.amount,
+                release_height,
+            });
+        }
+
+        let keys: Vec<String> = self.delegations
+            .iter()
+            .filter(|(_, d)| d.candidate == candidate)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            if let Some(delegation) = self.delegations.remove(&key) {
+                self.unbonding.push(UnbondingEntry {
+                    delegator: delegation.delegator,
+                    candidate: delegation.candidate,
+                    amount: delegation.amount,
+                    release_height,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 取出已到期的解绑记录
+    pub fn process_unbonding(&mut self, height: u64) -> Vec<UnbondingEntry> {
+        let (released, pending) = self.unbonding
+            .drain(..)
+            .partition(|entry| entry.release_height <= height);
+        self.unbonding = pending;
+        released
+    }
+
+    /// 登记候选人签名公钥（只能为交易发送者自己的质押登记一次，已登记的公钥不能被替换）
+    pub fn set_validator_key(&mut self, candidate: &str, public_key: String) -> Result<(), String> {
+        let stake = self.stakes.get_mut(candidate).ok_or("未找到候选人质押信息")?;
+        if stake.public_key.is_some() {
+            return Err(format!("候选人 {} 已登记签名公钥，不能替换", candidate));
+        }
+        stake.public_key = Some(public_key);
         Ok(())
     }
 
+    /// 提交作恶证据，返回被罚没的金额
+    pub fn submit_evidence(&mut self, evidence: &Evidence, height: u64) -> Result<u64, String> {
+        self.slashing.handle_evidence(&mut self.stakes, evidence, height)
+    }
+
     /// 计算候选人总权重（自有质押 + 委托）
     pub fn calculate_candidate_weight(&self, candidate: &str) -> u64 {
         let own_stake = self.stakes.get(candidate).map(|s| s.amount).unwrap_or(0);
@@ -203,6 +870,82 @@ impl DelegatedProofOfStake {
 
         own_stake + delegated_amount
     }
+
+    /// 选举出块人：按权重取前 N 名登记了签名公钥且未被监禁的候选人
+    pub fn elect_producers(&self, height: u64) -> Vec<String> {
+        let mut ranked: Vec<(&String, u64)> = self.candidates
+            .iter()
+            .filter(|c| self.stakes.get(*c).is_some_and(|stake| stake.public_key.is_some()))
+            .filter(|c| !self.slashing.is_jailed(c, height))
+            .map(|c| (c, self.calculate_candidate_weight(c)))
+            .filter(|(_, weight)| *weight > 0)
+            .collect();
+
+        // 权重相同时按地址排序，保证各节点结果一致
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        ranked
+            .into_iter()
+            .take(self.schedule.producer_count.max(1))
+            .map(|(c, _)| c.clone())
+            .collect()
+    }
+
+    /// 生成指定高度所在纪元的排班，以纪元前一区块的哈希作为洗牌种子
+    pub fn build_schedule(&self, height: u64, seed_hash: &str) -> ProducerSchedule {
+        let mut producers = self.elect_producers(height);
+        shuffle_with_seed(&mut producers, seed_from_hash(seed_hash));
+
+        ProducerSchedule {
+            epoch_start: self.schedule.epoch_start_for(height),
+            producers,
+            ..self.schedule.clone()
+        }
+    }
+
+    /// 获取覆盖当前区块链下一高度的排班
+    pub fn current_schedule(&self, blockchain: &Blockchain) -> ProducerSchedule {
+        let height = blockchain.get_length() as u64;
+        if self.schedule.covers(height) {
+            return self.schedule.clone();
+        }
+
+        let epoch_start = self.schedule.epoch_start_for(height);
+        let seed_block = blockchain.chain
+            .get((epoch_start as usize).saturating_sub(1))
+            .unwrap_or_else(|| blockchain.get_latest_block());
+        self.build_schedule(height, &seed_block.header.hash)
+    }
+
+    /// 指定高度、跳过若干槽位后轮到的出块人（监禁或已注销的出块人顺延给下一位）
+    pub fn producer_at(&self, schedule: &ProducerSchedule, height: u64, skipped: u64) -> Option<String> {
+        let count = schedule.producers.len() as u64;
+        if count == 0 {
+            return None;
+        }
+
+        let slot = height - schedule.epoch_start + skipped;
+        (0..count)
+            .map(|offset| &schedule.producers[((slot + offset) % count) as usize])
+            .find(|producer| self.candidates.contains(producer) && !self.slashing.is_jailed(producer, height))
+            .cloned()
+    }
+
+    /// 上一区块到新区块头时间戳之间被跳过槽位的出块人
+    ///
+    /// 区块时间戳在上链前已限定在中位时间之后、本地时间一个槽位之内，且每个区块最多计入一轮排班，
+    /// 伪造的时间跳跃不能让诚实出块人累计漏块
+    pub fn skipped_producers(&self, previous_block: &Block, block: &Block) -> Vec<String> {
+        if !self.schedule.covers(block.height) {
+            return Vec::new();
+        }
+        let skipped = self.schedule
+            .skipped_slots(previous_block, block.header.timestamp)
+            .min(self.schedule.producers.len() as u64);
+
+        (0..skipped)
+            .filter_map(|slot| self.producer_at(&self.schedule, block.height, slot))
+            .collect()
+    }
 }
 
 impl Consensus for DelegatedProofOfStake {
@@ -212,14 +955,19 @@ impl Consensus for DelegatedProofOfStake {
             return false;
         }
 
-        // 验证验证者是否为候选人
-        if let Some(validator) = &block.header.validator {
-            if !self.candidates.contains(&validator) {
-                return false;
-            }
+        // 尚无当选出块人（如刚切换到 DPoS）或排班中的出块人均已注销、被监禁时任何签名有效的区块都可以上链
+        if !self.schedule.covers(block.height) {
+            return true;
         }
 
-        true
+        // 否则验证者必须是按区块头时间戳所在槽位排定的出块人，且区块由其登记的签名公钥签名
+        let skipped = self.schedule.skipped_slots(previous_block, block.header.timestamp);
+        match self.producer_at(&self.schedule, block.height, skipped) {
+            Some(producer) => {
+                block.header.validator.as_ref() == Some(&producer) && signed_by_registered_key(&self.stakes, block)
+            }
+            None => true,
+        }
     }
 
     fn select_validator(&self, blockchain: &Blockchain) -> Option<String> {
@@ -227,23 +975,212 @@ impl Consensus for DelegatedProofOfStake {
             return None;
         }
 
-        // 选择权重最高的候选人
-        let mut max_weight = 0;
-        let mut selected_validator = None;
+        // 按排班轮流出块，超时未出块的槽位顺延给下一位出块人
+        let height = blockchain.get_length() as u64;
+        let schedule = self.current_schedule(blockchain);
+        let skipped = schedule.skipped_slots(blockchain.get_latest_block(), chrono::Utc::now());
+        self.producer_at(&schedule, height, skipped)
+    }
+
+    fn calculate_validator_weight(&self, _blockchain: &Blockchain, validator: &str) -> u64 {
+        self.calculate_candidate_weight(validator)
+    }
+
+    fn validate_transaction(&self, transaction: &Transaction, blockchain: &Blockchain) -> bool {
+        // 基本交易验证
+        if transaction.amount == 0 {
+            return false;
+        }
+
+        // 检查发送者余额
+        let sender_balance = blockchain.spendable_balance(&transaction.sender);
+        sender_balance >= transaction.amount.saturating_add(transaction.fee)
+    }
+
+    fn get_type(&self) -> ConsensusType {
+        ConsensusType::DPoS
+    }
+}
 
-        for candidate in &self.candidates {
-            let weight = self.calculate_candidate_weight(candidate);
-            if weight > max_weight {
-                max_weight = weight;
-                selected_validator = Some(candidate.clone());
+/// PoA 共识实现（类似 Clique）- 授权节点轮流签名出块，通过区块头投票增删授权节点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofOfAuthority {
+    pub authorities: Vec<String>,                        // 授权节点（按地址排序）
+    pub authority_keys: HashMap<String, String>,         // 授权节点 -> 签名公钥
+    pub period_secs: i64,                                // 出块间隔（秒）
+    pub epoch_length: u64,                               // 每个纪元开始时清空投票
+    pub tallies: HashMap<String, HashMap<String, bool>>, // 投票目标 -> (投票人 -> 是否授权)
+    pub proposals: HashMap<String, AuthorityVote>,       // 本节点待写入区块头的提议
+}
+
+impl ProofOfAuthority {
+    pub fn new(mut authorities: Vec<String>) -> Self {
+        authorities.sort();
+        authorities.dedup();
+        Self {
+            authorities,
+            authority_keys: HashMap::new(),
+            period_secs: 5,
+            epoch_length: 30000,
+            tallies: HashMap::new(),
+            proposals: HashMap::new(),
+        }
+    }
+
+    /// 是否为授权节点
+    pub fn is_authority(&self, address: &str) -> bool {
+        self.authorities.iter().any(|a| a == address)
+    }
+
+    /// 指定高度轮值（in-turn）的授权节点
+    pub fn in_turn_signer(&self, height: u64) -> Option<String> {
+        if self.authorities.is_empty() {
+            return None;
+        }
+        let index = (height % self.authorities.len() as u64) as usize;
+        Some(self.authorities[index].clone())
+    }
+
+    /// 授权节点两次签名之间至少间隔的区块数（floor(N/2) + 1）
+    pub fn signer_limit(&self) -> usize {
+        self.authorities.len() / 2 + 1
+    }
+
+    /// 检查签名者能否在当前链之后签名新区块：
+    /// 不能在最近 floor(N/2) 个区块内签过名，非轮值节点需额外等待一个出块间隔
+    pub fn check_signer(&self, chain: &[Block], signer: &str, timestamp: chrono::DateTime<chrono::Utc>) -> Result<(), String> {
+        if !self.is_authority(signer) {
+            return Err(format!("{} 不是授权节点", signer));
+        }
+
+        let recent = self.signer_limit() - 1;
+        if chain
+            .iter()
+            .rev()
+            .take(recent)
+            .any(|block| block.header.validator.as_deref() == Some(signer))
+        {
+            return Err(format!("授权节点 {} 最近已签名，需等待其他节点出块", signer));
+        }
+
+        let height = chain.len() as u64;
+        if self.in_turn_signer(height).as_deref() != Some(signer)
+            && let Some(previous) = chain.last()
+            && (timestamp - previous.header.timestamp).num_seconds() < self.period_secs * 2
+        {
+            return Err(format!("非轮值节点需在上一区块 {} 秒后才能出块", self.period_secs * 2));
+        }
+
+        Ok(())
+    }
+
+    /// 提议加入或移除授权节点，提议会写入本节点之后签名的区块头
+    pub fn propose(&mut self, target: String, authorize: bool, public_key: Option<String>) -> Result<(), String> {
+        if authorize && self.is_authority(&target) {
+            return Err(format!("{} 已是授权节点", target));
+        }
+        if !authorize && !self.is_authority(&target) {
+            return Err(format!("{} 不是授权节点", target));
+        }
+        if authorize && public_key.is_none() {
+            return Err("加入授权节点需提供签名公钥".to_string());
+        }
+
+        self.proposals.insert(target.clone(), AuthorityVote {
+            target,
+            authorize,
+            public_key,
+        });
+        Ok(())
+    }
+
+    /// 撤回提议
+    pub fn discard_proposal(&mut self, target: &str) -> Result<(), String> {
+        self.proposals
+            .remove(target)
+            .map(|_| ())
+            .ok_or_else(|| format!("没有针对 {} 的提议", target))
+    }
+
+    /// 选择下一个写入区块头的投票（跳过已失效或本节点已投过的提议）
+    pub fn next_vote(&self, signer: &str) -> Option<AuthorityVote> {
+        let mut proposals: Vec<&AuthorityVote> = self.proposals
+            .values()
+            .filter(|vote| vote.authorize != self.is_authority(&vote.target))
+            .filter(|vote| {
+                self.tallies
+                    .get(&vote.target)
+                    .and_then(|votes| votes.get(signer))
+                    != Some(&vote.authorize)
+            })
+            .collect();
+        proposals.sort_by(|a, b| a.target.cmp(&b.target));
+        proposals.first().map(|vote| (*vote).clone())
+    }
+
+    /// 应用区块头中的投票，超过半数授权节点同意时变更授权节点集合
+    pub fn apply_block(&mut self, block: &Block) -> Option<String> {
+        if block.height.is_multiple_of(self.epoch_length.max(1)) {
+            self.tallies.clear();
+        }
+
+        let signer = block.header.validator.as_ref()?;
+        let vote = block.header.authority_vote.as_ref()?;
+        if vote.authorize == self.is_authority(&vote.target) {
+            return None; // 投票已失效
+        }
+
+        let votes = self.tallies.entry(vote.target.clone()).or_default();
+        votes.insert(signer.clone(), vote.authorize);
+        let agreed = votes.values().filter(|a| **a == vote.authorize).count();
+        if agreed * 2 <= self.authorities.len() {
+            return None;
+        }
+
+        // 达到多数，变更授权节点集合并清理相关投票
+        self.tallies.remove(&vote.target);
+        self.proposals.remove(&vote.target);
+        if vote.authorize {
+            self.authorities.push(vote.target.clone());
+            self.authorities.sort();
+            if let Some(public_key) = &vote.public_key {
+                self.authority_keys.insert(vote.target.clone(), public_key.clone());
+            }
+            Some(format!("{} 已加入授权节点", vote.target))
+        } else {
+            self.authorities.retain(|a| a != &vote.target);
+            self.authority_keys.remove(&vote.target);
+            for votes in self.tallies.values_mut() {
+                votes.remove(&vote.target);
             }
+            Some(format!("{} 已移出授权节点", vote.target))
         }
+    }
+}
 
-        selected_validator
+impl Consensus for ProofOfAuthority {
+    fn validate_block(&self, block: &Block, previous_block: &Block) -> bool {
+        if !block.is_valid(&previous_block.header.hash) {
+            return false;
+        }
+
+        // 必须由登记了公钥的授权节点签名
+        match &block.header.validator {
+            Some(signer) => {
+                self.is_authority(signer)
+                    && self.authority_keys.get(signer) == block.header.public_key.as_ref()
+                    && block.header.verify_signature(block.height)
+            }
+            None => false,
+        }
+    }
+
+    fn select_validator(&self, blockchain: &Blockchain) -> Option<String> {
+        self.in_turn_signer(blockchain.get_length() as u64)
     }
 
     fn calculate_validator_weight(&self, _blockchain: &Blockchain, validator: &str) -> u64 {
-        self.calculate_candidate_weight(validator)
+        if self.is_authority(validator) { 1 } else { 0 }
     }
 
     fn validate_transaction(&self, transaction: &Transaction, blockchain: &Blockchain) -> bool {
@@ -253,11 +1190,427 @@ impl Consensus for DelegatedProofOfStake {
         }
 
         // 检查发送者余额
-        let sender_balance = blockchain.get_balance(&transaction.sender);
-        sender_balance >= transaction.amount
+        let sender_balance = blockchain.spendable_balance(&transaction.sender);
+        sender_balance >= transaction.amount.saturating_add(transaction.fee)
     }
 
     fn get_type(&self) -> ConsensusType {
-        ConsensusType::DPoS
+        ConsensusType::PoA
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::ValidatorKey;
+
+    /// 验证者签名的指定高度区块头（金额不同则区块头不同）
+    fn signed_header(key: &ValidatorKey, height: u64, amount: u64) -> Box<BlockHeader> {
+        let transaction = Transaction::new("alice".to_string(), "bob".to_string(), amount);
+        let mut block = Block::new("prev".to_string(), vec![transaction], height, 1);
+        block.header.validator = Some(key.address.clone());
+        block.header.hash = block.calculate_hash();
+        block.sign(&key.keypair());
+        Box::new(block.header)
+    }
+
+    fn pos_with_key(validator: &str) -> (ProofOfStake, ValidatorKey) {
+        let mut pos = ProofOfStake::new(100);
+        let key = ValidatorKey::generate(validator.to_string());
+        pos.stake(validator.to_string(), 1000, chrono::Utc::now()).unwrap();
+        pos.set_validator_key(validator, key.public_key_hex()).unwrap();
+        (pos, key)
+    }
+
+    #[test]
+    fn double_sign_evidence_slashes_and_jails_once() {
+        let (mut pos, key) = pos_with_key("alice");
+        let evidence = Evidence::DoubleSign {
+            validator: "alice".to_string(),
+            height: 5,
+            first: signed_header(&key, 5, 1),
+            second: signed_header(&key, 5, 2),
+        };
+
+        assert_eq!(pos.submit_evidence(&evidence, 7), Ok(100));
+        assert_eq!(pos.stakes["alice"].amount, 900);
+        assert_eq!(pos.slashing.total_burned, 100);
+        assert!(pos.slashing.is_jailed("alice", 106));
+        assert!(!pos.slashing.is_jailed("alice", 107));
+
+        // 交换两个区块头的顺序仍是同一份证据，不能重复罚没
+        let swapped = match evidence {
+            Evidence::DoubleSign { validator, height, first, second } => {
+                Evidence::DoubleSign { validator, height, first: second, second: first }
+            }
+            _ => unreachable!(),
+        };
+        assert!(pos.submit_evidence(&swapped, 8).is_err());
+        assert_eq!(pos.stakes["alice"].amount, 900);
+    }
+
+    #[test]
+    fn rejects_double_sign_evidence_not_signed_by_registered_key() {
+        let (mut pos, key) = pos_with_key("alice");
+        let forger = ValidatorKey::generate("alice".to_string());
+        let header = signed_header(&key, 5, 1);
+
+        let same_header = Evidence::DoubleSign {
+            validator: "alice".to_string(),
+            height: 5,
+            first: header.clone(),
+            second: header.clone(),
+        };
+        assert!(pos.submit_evidence(&same_header, 6).is_err());
+
+        let forged = Evidence::DoubleSign {
+            validator: "alice".to_string(),
+            height: 5,
+            first: header,
+            second: signed_header(&forger, 5, 2),
+        };
+        assert!(pos.submit_evidence(&forged, 6).is_err());
+        assert_eq!(pos.stakes["alice"].amount, 1000);
+        assert!(!pos.slashing.is_jailed("alice", 6));
+    }
+
+    #[test]
+    fn downtime_needs_consecutive_missed_slots() {
+        let (mut pos, _) = pos_with_key("alice");
+        pos.slashing.config.max_missed_slots = 3;
+
+        assert_eq!(pos.slashing.record_slot("alice", "bob"), None);
+        assert_eq!(pos.slashing.record_slot("alice", "bob"), None);
+        // 出块后漏块计数清零
+        assert_eq!(pos.slashing.record_slot("alice", "alice"), None);
+        assert_eq!(pos.slashing.missed_slots.get("alice"), None);
+
+        let early = Evidence::Downtime { validator: "alice".to_string(), missed_slots: 3 };
+        assert!(pos.submit_evidence(&early, 10).is_err());
+
+        pos.slashing.record_slot("alice", "bob");
+        pos.slashing.record_slot("alice", "bob");
+        assert_eq!(pos.slashing.record_slot("alice", "bob"), Some(early.clone()));
+        assert_eq!(pos.submit_evidence(&early, 10), Ok(10));
+        assert_eq!(pos.stakes["alice"].amount, 990);
+        assert_eq!(pos.slashing.missed_slots.get("alice"), None);
+    }
+
+    #[test]
+    fn redistributes_slashed_stake_to_unjailed_validators() {
+        let (mut pos, key) = pos_with_key("alice");
+        pos.slashing.config.mode = SlashMode::Redistribute;
+        pos.stake("bob".to_string(), 2000, chrono::Utc::now()).unwrap();
+        pos.stake("carol".to_string(), 1000, chrono::Utc::now()).unwrap();
+        pos.stake("dave".to_string(), 5000, chrono::Utc::now()).unwrap();
+        pos.slashing.jailed.insert("dave".to_string(), 1000);
+
+        let evidence = Evidence::DoubleSign {
+            validator: "alice".to_string(),
+            height: 5,
+            first: signed_header(&key, 5, 1),
+            second: signed_header(&key, 5, 2),
+        };
+        assert_eq!(pos.submit_evidence(&evidence, 6), Ok(100));
+
+        // 100 按 2000:1000 分给 bob 和 carol，取整余数销毁，被监禁的 dave 不参与分配
+        assert_eq!(pos.stakes["bob"].amount, 2066);
+        assert_eq!(pos.stakes["carol"].amount, 1033);
+        assert_eq!(pos.stakes["dave"].amount, 5000);
+        assert_eq!(pos.slashing.total_burned, 1);
+    }
+
+    #[test]
+    fn unjail_only_after_jail_period() {
+        let mut slashing = Slashing::default();
+        assert!(slashing.unjail("alice", 10).is_err());
+
+        slashing.jailed.insert("alice".to_string(), 100);
+        assert!(slashing.is_jailed("alice", 99));
+        assert!(slashing.unjail("alice", 99).is_err());
+        assert_eq!(slashing.unjail("alice", 100), Ok(()));
+        assert!(!slashing.is_jailed("alice", 100));
+    }
+
+    /// 注册候选人并登记签名公钥
+    fn dpos_with_candidates(candidates: &[(&str, u64)]) -> DelegatedProofOfStake {
+        let mut dpos = DelegatedProofOfStake::new(1000, 100);
+        for (candidate, amount) in candidates {
+            dpos.register_candidate(candidate.to_string(), *amount, chrono::Utc::now()).unwrap();
+            let key = ValidatorKey::generate(candidate.to_string());
+            dpos.set_validator_key(candidate, key.public_key_hex()).unwrap();
+        }
+        dpos
+    }
+
+    #[test]
+    fn elects_top_weighted_candidates_with_keys() {
+        let mut dpos = dpos_with_candidates(&[("alice", 3000), ("bob", 2000), ("carol", 1000)]);
+        dpos.schedule.producer_count = 2;
+        dpos.register_candidate("dave".to_string(), 9000, chrono::Utc::now()).unwrap();
+        assert_eq!(dpos.elect_producers(1), vec!["alice".to_string(), "bob".to_string()]);
+
+        // 委托计入权重，未登记签名公钥的 dave 和被监禁的候选人不能当选
+        dpos.delegate("erin".to_string(), "carol".to_string(), 2500).unwrap();
+        assert_eq!(dpos.elect_producers(1), vec!["carol".to_string(), "alice".to_string()]);
+        dpos.slashing.jailed.insert("carol".to_string(), 10);
+        assert_eq!(dpos.elect_producers(1), vec!["alice".to_string(), "bob".to_string()]);
+        assert_eq!(dpos.elect_producers(10), vec!["carol".to_string(), "alice".to_string()]);
+    }
+
+    #[test]
+    fn schedule_is_deterministic_per_epoch_seed() {
+        let dpos = dpos_with_candidates(&[("alice", 1000), ("bob", 1000), ("carol", 1000), ("dave", 1000)]);
+
+        let schedule = dpos.build_schedule(70, "seed");
+        assert_eq!(schedule.epoch_start, 63);
+        assert!(schedule.covers(63) && schedule.covers(125));
+        assert!(!schedule.covers(62) && !schedule.covers(126));
+        assert_eq!(schedule.producers, dpos.build_schedule(100, "seed").producers);
+
+        let mut sorted = schedule.producers.clone();
+        sorted.sort();
+        assert_eq!(sorted, vec!["alice", "bob", "carol", "dave"]);
+        let reshuffled = (0..10).any(|i| dpos.build_schedule(70, &format!("seed{}", i)).producers != schedule.producers);
+        assert!(reshuffled);
+    }
+
+    #[test]
+    fn producers_rotate_by_slot_and_skip_jailed() {
+        let mut dpos = dpos_with_candidates(&[("alice", 1000), ("bob", 1000), ("carol", 1000)]);
+        let schedule = dpos.build_schedule(0, "seed");
+        let order = schedule.producers.clone();
+
+        assert_eq!(dpos.producer_at(&schedule, 0, 0), Some(order[0].clone()));
+        assert_eq!(dpos.producer_at(&schedule, 1, 0), Some(order[1].clone()));
+        assert_eq!(dpos.producer_at(&schedule, 1, 1), Some(order[2].clone()));
+        assert_eq!(dpos.producer_at(&schedule, 4, 0), Some(order[1].clone()));
+
+        // 被监禁或已注销的出块人的槽位顺延给下一位
+        dpos.slashing.jailed.insert(order[1].clone(), 100);
+        assert_eq!(dpos.producer_at(&schedule, 1, 0), Some(order[2].clone()));
+        dpos.candidates.retain(|c| *c != order[2]);
+        assert_eq!(dpos.producer_at(&schedule, 1, 0), Some(order[0].clone()));
+        dpos.candidates.retain(|c| *c != order[0]);
+        assert_eq!(dpos.producer_at(&schedule, 1, 0), None);
+    }
+
+    #[test]
+    fn skipped_producers_are_bounded_by_one_round() {
+        let mut dpos = dpos_with_candidates(&[("alice", 1000), ("bob", 1000), ("carol", 1000)]);
+        dpos.schedule = dpos.build_schedule(1, "seed");
+
+        let previous = Block::new("prev".to_string(), Vec::new(), 0, 1);
+        let mut block = Block::new(previous.header.hash.clone(), Vec::new(), 1, 1);
+        block.header.timestamp = previous.header.timestamp + chrono::Duration::seconds(25);
+        assert_eq!(dpos.schedule.skipped_slots(&previous, block.header.timestamp), 2);
+        assert_eq!(dpos.skipped_producers(&previous, &block), vec![
+            dpos.producer_at(&dpos.schedule, 1, 0).unwrap(),
+            dpos.producer_at(&dpos.schedule, 1, 1).unwrap(),
+        ]);
+
+        block.header.timestamp = previous.header.timestamp + chrono::Duration::days(1);
+        assert_eq!(dpos.skipped_producers(&previous, &block).len(), 3);
+
+        // 时间戳早于上一区块不算跳过槽位
+        block.header.timestamp = previous.header.timestamp - chrono::Duration::seconds(30);
+        assert!(dpos.skipped_producers(&previous, &block).is_empty());
+    }
+
+    #[test]
+    fn rejects_duplicate_or_underfunded_candidates() {
+        let mut dpos = dpos_with_candidates(&[("alice", 1000)]);
+        assert!(dpos.register_candidate("alice".to_string(), 5000, chrono::Utc::now()).is_err());
+        assert!(dpos.register_candidate("bob".to_string(), 999, chrono::Utc::now()).is_err());
+        assert_eq!(dpos.candidates, vec!["alice".to_string()]);
+        assert_eq!(dpos.stakes["alice"].amount, 1000);
+    }
+
+    #[test]
+    fn undelegated_funds_unbond_until_release_height() {
+        let mut dpos = dpos_with_candidates(&[("alice", 1000)]);
+        dpos.delegate("bob".to_string(), "alice".to_string(), 500).unwrap();
+
+        // 剩余委托不能低于最小委托额，也不能超额撤销
+        assert!(dpos.undelegate("bob".to_string(), "alice".to_string(), 450, 10).is_err());
+        assert!(dpos.undelegate("bob".to_string(), "alice".to_string(), 600, 10).is_err());
+        assert_eq!(dpos.undelegate("bob".to_string(), "alice".to_string(), 200, 10), Ok(110));
+        assert_eq!(dpos.calculate_candidate_weight("alice"), 1300);
+
+        assert!(dpos.process_unbonding(109).is_empty());
+        let released = dpos.process_unbonding(110);
+        assert_eq!(released.len(), 1);
+        assert_eq!((released[0].delegator.as_str(), released[0].amount), ("bob", 200));
+        assert!(dpos.unbonding.is_empty());
+    }
+
+    #[test]
+    fn redelegation_moves_weight_immediately() {
+        let mut dpos = dpos_with_candidates(&[("alice", 1000), ("carol", 1000)]);
+        dpos.delegate("bob".to_string(), "alice".to_string(), 500).unwrap();
+
+        assert!(dpos.redelegate("bob".to_string(), "alice".to_string(), "alice".to_string(), 200).is_err());
+        assert!(dpos.redelegate("bob".to_string(), "alice".to_string(), "dave".to_string(), 200).is_err());
+        assert!(dpos.redelegate("bob".to_string(), "alice".to_string(), "carol".to_string(), 50).is_err());
+
+        dpos.redelegate("bob".to_string(), "alice".to_string(), "carol".to_string(), 500).unwrap();
+        assert_eq!(dpos.calculate_candidate_weight("alice"), 1000);
+        assert_eq!(dpos.calculate_candidate_weight("carol"), 1500);
+        assert!(dpos.unbonding.is_empty());
+    }
+
+    #[test]
+    fn deregistration_unbonds_stake_and_delegations() {
+        let mut dpos = dpos_with_candidates(&[("alice", 1000), ("carol", 1000)]);
+        dpos.delegate("bob".to_string(), "alice".to_string(), 500).unwrap();
+        dpos.delegate("bob".to_string(), "carol".to_string(), 300).unwrap();
+
+        dpos.deregister_candidate("alice", 20).unwrap();
+        assert!(dpos.deregister_candidate("alice", 20).is_err());
+        assert!(dpos.delegate("erin".to_string(), "alice".to_string(), 500).is_err());
+        assert_eq!(dpos.candidates, vec!["carol".to_string()]);
+        assert_eq!(dpos.calculate_candidate_weight("alice"), 0);
+        assert_eq!(dpos.calculate_candidate_weight("carol"), 1300);
+
+        let mut unbonding: Vec<(String, u64, u64)> = dpos.unbonding
+            .iter()
+            .map(|entry| (entry.delegator.clone(), entry.amount, entry.release_height))
+            .collect();
+        unbonding.sort();
+        assert_eq!(unbonding, vec![("alice".to_string(), 1000, 120), ("bob".to_string(), 500, 120)]);
+
+        // 解绑中的金额仍计入锁定资金，切换共识时一并退还
+        let locked: u64 = dpos.locked_funds().iter().map(|(_, amount)| amount).sum();
+        assert_eq!(locked, 1000 + 300 + 1500);
+    }
+
+    /// 授权节点签名的区块（可附带投票）
+    fn poa_block(chain: &[Block], key: &ValidatorKey, vote: Option<AuthorityVote>, delay_secs: i64) -> Block {
+        let previous = chain.last().unwrap();
+        let mut block = Block::new(previous.header.hash.clone(), Vec::new(), chain.len() as u64, 0);
+        block.header.timestamp = previous.header.timestamp + chrono::Duration::seconds(delay_secs);
+        block.header.validator = Some(key.address.clone());
+        block.header.authority_vote = vote;
+        block.header.hash = block.calculate_hash();
+        block.sign(&key.keypair());
+        block
+    }
+
+    fn vote(target: &str, authorize: bool) -> Option<AuthorityVote> {
+        Some(AuthorityVote {
+            target: target.to_string(),
+            authorize,
+            public_key: authorize.then(|| format!("{}_key", target)),
+        })
+    }
+
+    #[test]
+    fn authorities_sign_in_turn_with_recent_signer_limit() {
+        let keys: Vec<ValidatorKey> = ["alice", "bob", "carol"]
+            .iter()
+            .map(|a| ValidatorKey::generate(a.to_string()))
+            .collect();
+        let poa = ProofOfAuthority::new(vec!["carol".to_string(), "alice".to_string(), "bob".to_string(), "alice".to_string()]);
+        assert_eq!(poa.authorities, vec!["alice", "bob", "carol"]);
+        assert_eq!(poa.signer_limit(), 2);
+        assert_eq!(poa.in_turn_signer(1), Some("bob".to_string()));
+        assert_eq!(poa.in_turn_signer(3), Some("alice".to_string()));
+
+        let mut chain = vec![Block::new("0".to_string(), Vec::new(), 0, 1)];
+        chain.push(poa_block(&chain, &keys[1], None, 5));
+        let now = chain[1].header.timestamp + chrono::Duration::seconds(5);
+
+        assert!(poa.check_signer(&chain, "dave", now).is_err());
+        // 刚签过名的节点需等待其他节点出块
+        assert!(poa.check_signer(&chain, "bob", now).is_err());
+        assert_eq!(poa.check_signer(&chain, "carol", now), Ok(()));
+        // 非轮值节点需在上一区块两个出块间隔之后才能出块
+        assert!(poa.check_signer(&chain, "alice", now).is_err());
+        assert_eq!(poa.check_signer(&chain, "alice", now + chrono::Duration::seconds(5)), Ok(()));
+    }
+
+    #[test]
+    fn authority_votes_need_a_majority() {
+        let keys: Vec<ValidatorKey> = ["alice", "bob", "carol"]
+            .iter()
+            .map(|a| ValidatorKey::generate(a.to_string()))
+            .collect();
+        let mut poa = ProofOfAuthority::new(vec!["alice".to_string(), "bob".to_string(), "carol".to_string()]);
+        let mut chain = vec![Block::new("0".to_string(), Vec::new(), 0, 1)];
+
+        // 同一节点重复投票只计一次
+        for key in [&keys[0], &keys[0]] {
+            let block = poa_block(&chain, key, vote("dave", true), 5);
+            assert_eq!(poa.apply_block(&block), None);
+            chain.push(block);
+        }
+        let block = poa_block(&chain, &keys[1], vote("dave", true), 5);
+        assert!(poa.apply_block(&block).is_some());
+        chain.push(block);
+        assert!(poa.is_authority("dave"));
+        assert_eq!(poa.authority_keys.get("dave"), Some(&"dave_key".to_string()));
+        assert!(poa.tallies.is_empty());
+
+        // 四个授权节点需要三票才能移除，被移除节点的投票随之作废
+        for key in [&keys[0], &keys[1]] {
+            let block = poa_block(&chain, key, vote("carol", false), 5);
+            assert_eq!(poa.apply_block(&block), None);
+            chain.push(block);
+        }
+        let block = poa_block(&chain, &keys[2], vote("carol", false), 5);
+        assert!(poa.apply_block(&block).is_some());
+        assert_eq!(poa.authorities, vec!["alice", "bob", "dave"]);
+        assert!(!poa.authority_keys.contains_key("carol"));
+    }
+
+    #[test]
+    fn poa_blocks_must_be_signed_with_authority_key() {
+        let alice = ValidatorKey::generate("alice".to_string());
+        let mut poa = ProofOfAuthority::new(vec!["alice".to_string()]);
+        let chain = vec![Block::new("0".to_string(), Vec::new(), 0, 1)];
+        let block = poa_block(&chain, &alice, None, 5);
+        assert!(!poa.validate_block(&block, &chain[0]));
+
+        poa.authority_keys.insert("alice".to_string(), alice.public_key_hex());
+        assert!(poa.validate_block(&block, &chain[0]));
+
+        let mallory = ValidatorKey::generate("alice".to_string());
+        assert!(!poa.validate_block(&poa_block(&chain, &mallory, None, 5), &chain[0]));
+        let mut unsigned = Block::new(chain[0].header.hash.clone(), Vec::new(), 1, 0);
+        unsigned.header.validator = Some("alice".to_string());
+        unsigned.header.hash = unsigned.calculate_hash();
+        assert!(!poa.validate_block(&unsigned, &chain[0]));
+    }
+
+    #[test]
+    fn proposals_are_voted_until_applied() {
+        let mut poa = ProofOfAuthority::new(vec!["alice".to_string(), "bob".to_string()]);
+        assert!(poa.propose("alice".to_string(), true, Some("key".to_string())).is_err());
+        assert!(poa.propose("dave".to_string(), false, None).is_err());
+        assert!(poa.propose("dave".to_string(), true, None).is_err());
+
+        poa.propose("dave".to_string(), true, Some("dave_key".to_string())).unwrap();
+        poa.propose("bob".to_string(), false, None).unwrap();
+        assert_eq!(poa.next_vote("alice").map(|v| v.target), Some("bob".to_string()));
+
+        poa.tallies.entry("bob".to_string()).or_default().insert("alice".to_string(), false);
+        assert_eq!(poa.next_vote("alice").map(|v| v.target), Some("dave".to_string()));
+        poa.discard_proposal("dave").unwrap();
+        assert!(poa.discard_proposal("dave").is_err());
+        assert_eq!(poa.next_vote("alice"), None);
+    }
+
+    #[test]
+    fn distributes_large_rewards_without_overflow() {
+        let mut dpos = DelegatedProofOfStake::new(1000, 100);
+        dpos.register_candidate("alice".to_string(), u64::MAX / 4, chrono::Utc::now()).unwrap();
+        dpos.delegate("bob".to_string(), "alice".to_string(), u64::MAX / 4).unwrap();
+        dpos.set_commission_rate("alice", 0).unwrap();
+
+        let reward = u64::MAX / 2;
+        let shares = dpos.distribute_reward("alice", reward);
+        let bob = shares.iter().find(|share| share.address == "bob").unwrap();
+        assert_eq!(bob.amount, reward / 2);
+        assert_eq!(shares.iter().map(|share| share.amount).sum::<u64>(), reward);
     }
 }
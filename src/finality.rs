@@ -0,0 +1,264 @@
+use crate::block::verify_hex_signature;
+use crate::wallet::ValidatorKey;
+use ring::signature::KeyPair;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// 投票类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VoteType {
+    Prevote,   // 预投票
+    Precommit, // 预提交
+}
+
+/// 验证者对区块的签名投票
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Vote {
+    pub vote_type: VoteType,
+    pub height: u64,
+    pub block_hash: String,
+    pub validator: String,
+    pub public_key: String,
+    pub signature: String,
+}
+
+impl Vote {
+    /// 使用验证者密钥创建签名投票
+    pub fn new_signed(vote_type: VoteType, height: u64, block_hash: String, key: &ValidatorKey) -> Self {
+        let keypair = key.keypair();
+        let mut vote = Self {
+            vote_type,
+            height,
+            block_hash,
+            validator: key.address.clone(),
+            public_key: hex::encode(keypair.public_key().as_ref()),
+            signature: String::new(),
+        };
+
+        let signature = keypair.sign(vote.signing_message().as_bytes());
+        vote.signature = hex::encode(signature.as_ref());
+        vote
+    }
+
+    /// 计算签名消息（包含投票类型，防止预投票被当作预提交重放）
+    pub fn signing_message(&self) -> String {
+        format!("{:?}:{}:{}:{}", self.vote_type, self.height, self.block_hash, self.validator)
+    }
+
+    /// 验证投票签名
+    pub fn verify_signature(&self) -> bool {
+        verify_hex_signature(&self.public_key, &self.signing_message(), &self.signature)
+    }
+}
+
+/// 验证者集合中的一员：权重和登记的签名公钥
+#[derive(Debug, Clone)]
+pub struct ValidatorWeight {
+    pub weight: u64,
+    pub public_key: Option<String>,
+}
+
+/// 处理投票的结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum VoteOutcome {
+    /// 已记录，尚未达到阈值
+    Recorded,
+    /// 预投票超过 2/3 权重，验证者可以预提交
+    Polka { height: u64, block_hash: String },
+    /// 预提交超过 2/3 权重，区块已最终确认
+    Finalized { height: u64, block_hash: String },
+}
+
+/// 单个高度收集到的投票
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HeightVotes {
+    pub prevotes: HashMap<String, Vote>,   // 验证者 -> 预投票
+    pub precommits: HashMap<String, Vote>, // 验证者 -> 预提交
+}
+
+impl HeightVotes {
+    fn votes(&self, vote_type: VoteType) -> &HashMap<String, Vote> {
+        match vote_type {
+            VoteType::Prevote => &self.prevotes,
+            VoteType::Precommit => &self.precommits,
+        }
+    }
+
+    fn votes_mut(&mut self, vote_type: VoteType) -> &mut HashMap<String, Vote> {
+        match vote_type {
+            VoteType::Prevote => &mut self.prevotes,
+            VoteType::Precommit => &mut self.precommits,
+        }
+    }
+}
+
+/// BFT 最终性组件 - 收集预投票/预提交，超过 2/3 权重的预提交使区块最终确认
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FinalityGadget {
+    pub finalized_height: u64,
+    pub finalized_hash: Option<String>,
+    pub votes: BTreeMap<u64, HeightVotes>,
+}
+
+impl FinalityGadget {
+    /// 指定高度的区块是否已最终确认（创世区块始终视为最终确认）
+    pub fn is_finalized(&self, height: u64) -> bool {
+        height <= self.finalized_height
+    }
+
+    /// 验证者是否已在指定高度投过该类型的票
+    pub fn has_voted(&self, height: u64, vote_type: VoteType, validator: &str) -> bool {
+        self.votes
+            .get(&height)
+            .is_some_and(|votes| votes.votes(vote_type).contains_key(validator))
+    }
+
+    /// 统计指定高度、类型、区块哈希获得的投票权重
+    pub fn tally(&self, height: u64, vote_type: VoteType, block_hash: &str, validators: &HashMap<String, ValidatorWeight>) -> u64 {
+        self.votes
+            .get(&height)
+            .map(|votes| {
+                votes.votes(vote_type)
+                    .values()
+                    .filter(|vote| vote.block_hash == block_hash)
+                    .filter_map(|vote| validators.get(&vote.validator))
+                    .map(|v| v.weight)
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    /// 核验并记录投票；local_hash 为本地链在该高度的区块哈希
+    pub fn add_vote(
+        &mut self,
+        vote: Vote,
+        validators: &HashMap<String, ValidatorWeight>,
+        local_hash: Option<&str>,
+    ) -> Result<VoteOutcome, String> {
+        if vote.height <= self.finalized_height {
+            return Err(format!("高度 {} 已最终确认", vote.height));
+        }
+
+        let member = validators
+            .get(&vote.validator)
+            .ok_or_else(|| format!("{} 不在验证者集合中", vote.validator))?;
+        if member.public_key.as_ref() != Some(&vote.public_key) {
+            return Err("投票公钥与登记的验证者公钥不一致".to_string());
+        }
+        if !vote.verify_signature() {
+            return Err("投票签名无效".to_string());
+        }
+
+        let (height, vote_type, block_hash) = (vote.height, vote.vote_type, vote.block_hash.clone());
+        let votes = self.votes.entry(height).or_default().votes_mut(vote_type);
+        match votes.get(&vote.validator) {
+            Some(existing) if existing.block_hash != block_hash => {
+                return Err(format!("验证者 {} 在高度 {} 投出冲突的票", vote.validator, height));
+            }
+            Some(_) => return Ok(VoteOutcome::Recorded),
+            None => {
+                votes.insert(vote.validator.clone(), vote);
+            }
+        }
+
+        let total: u64 = validators.values().map(|v| v.weight).sum();
+        let power = self.tally(height, vote_type, &block_hash, validators);
+        if power * 3 <= total * 2 {
+            return Ok(VoteOutcome::Recorded);
+        }
+
+        match vote_type {
+            VoteType::Prevote => Ok(VoteOutcome::Polka { height, block_hash }),
+            VoteType::Precommit => {
+                // 只能确认本地链上的区块，其他分叉上的确认需先同步
+                if local_hash != Some(block_hash.as_str()) {
+                    return Ok(VoteOutcome::Recorded);
+                }
+                self.finalized_height = height;
+                self.finalized_hash = Some(block_hash.clone());
+                self.votes = self.votes.split_off(&(height + 1));
+                Ok(VoteOutcome::Finalized { height, block_hash })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 权重 40/30/30 的三个验证者
+    fn validators() -> (Vec<ValidatorKey>, HashMap<String, ValidatorWeight>) {
+        let keys: Vec<ValidatorKey> = ["alice", "bob", "carol"]
+            .iter()
+            .map(|name| ValidatorKey::generate(name.to_string()))
+            .collect();
+        let weights = keys
+            .iter()
+            .zip([40, 30, 30])
+            .map(|(key, weight)| {
+                (key.address.clone(), ValidatorWeight { weight, public_key: Some(key.public_key_hex()) })
+            })
+            .collect();
+        (keys, weights)
+    }
+
+    #[test]
+    fn two_thirds_of_precommits_finalize_local_block() {
+        let (keys, validators) = validators();
+        let mut gadget = FinalityGadget::default();
+        let vote = |vote_type, key| Vote::new_signed(vote_type, 5, "h5".to_string(), key);
+
+        assert_eq!(gadget.add_vote(vote(VoteType::Prevote, &keys[0]), &validators, Some("h5")), Ok(VoteOutcome::Recorded));
+        assert_eq!(
+            gadget.add_vote(vote(VoteType::Prevote, &keys[1]), &validators, Some("h5")),
+            Ok(VoteOutcome::Polka { height: 5, block_hash: "h5".to_string() })
+        );
+
+        // 恰好 2/3 不足以确认
+        gadget.add_vote(vote(VoteType::Precommit, &keys[1]), &validators, Some("h5")).unwrap();
+        assert_eq!(gadget.add_vote(vote(VoteType::Precommit, &keys[2]), &validators, Some("h5")), Ok(VoteOutcome::Recorded));
+        assert_eq!(
+            gadget.add_vote(vote(VoteType::Precommit, &keys[0]), &validators, Some("h5")),
+            Ok(VoteOutcome::Finalized { height: 5, block_hash: "h5".to_string() })
+        );
+        assert!(gadget.is_finalized(5));
+        assert_eq!(gadget.finalized_hash.as_deref(), Some("h5"));
+        assert!(gadget.votes.is_empty());
+        assert!(gadget.add_vote(vote(VoteType::Prevote, &keys[2]), &validators, Some("h5")).is_err());
+    }
+
+    #[test]
+    fn precommits_for_unknown_block_do_not_finalize() {
+        let (keys, validators) = validators();
+        let mut gadget = FinalityGadget::default();
+        for key in &keys {
+            let vote = Vote::new_signed(VoteType::Precommit, 3, "fork".to_string(), key);
+            assert_eq!(gadget.add_vote(vote, &validators, Some("local")), Ok(VoteOutcome::Recorded));
+        }
+        assert!(!gadget.is_finalized(3));
+        assert_eq!(gadget.tally(3, VoteType::Precommit, "fork", &validators), 100);
+    }
+
+    #[test]
+    fn rejects_conflicting_and_unauthorized_votes() {
+        let (keys, validators) = validators();
+        let mut gadget = FinalityGadget::default();
+        let first = Vote::new_signed(VoteType::Prevote, 2, "a".to_string(), &keys[0]);
+        gadget.add_vote(first.clone(), &validators, None).unwrap();
+        assert_eq!(gadget.add_vote(first, &validators, None), Ok(VoteOutcome::Recorded));
+        assert!(gadget.add_vote(Vote::new_signed(VoteType::Prevote, 2, "b".to_string(), &keys[0]), &validators, None).is_err());
+        assert!(gadget.has_voted(2, VoteType::Prevote, "alice"));
+        assert!(!gadget.has_voted(2, VoteType::Precommit, "alice"));
+
+        // 不在验证者集合中 / 公钥与登记的不一致 / 签名被篡改
+        let outsider = ValidatorKey::generate("mallory".to_string());
+        assert!(gadget.add_vote(Vote::new_signed(VoteType::Prevote, 2, "a".to_string(), &outsider), &validators, None).is_err());
+        let impostor = ValidatorKey::generate("bob".to_string());
+        assert!(gadget.add_vote(Vote::new_signed(VoteType::Prevote, 2, "a".to_string(), &impostor), &validators, None).is_err());
+        let mut tampered = Vote::new_signed(VoteType::Prevote, 2, "a".to_string(), &keys[1]);
+        tampered.vote_type = VoteType::Precommit;
+        assert!(gadget.add_vote(tampered, &validators, None).is_err());
+        assert_eq!(gadget.tally(2, VoteType::Prevote, "a", &validators), 40);
+    }
+}
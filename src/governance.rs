@@ -0,0 +1,297 @@
+use crate::block::{Block, Transaction, TransactionKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// 治理交易的接收地址
+pub const GOVERNANCE_ADDRESS: &str = "governance";
+
+/// 可通过治理修改的链参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GovernanceParam {
+    MiningReward,      // 挖矿奖励
+    MinimumStake,      // 最小质押（PoS 验证者 / DPoS 候选人）
+    MinimumDelegation, // DPoS 最小委托
+}
+
+impl GovernanceParam {
+    /// 从名称解析参数
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "mining_reward" => Some(GovernanceParam::MiningReward),
+            "minimum_stake" => Some(GovernanceParam::MinimumStake),
+            "minimum_delegation" => Some(GovernanceParam::MinimumDelegation),
+            _ => None,
+        }
+    }
+}
+
+/// 参数修改内容
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParamChange {
+    pub param: GovernanceParam,
+    pub value: u64,
+}
+
+/// 提案状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProposalStatus {
+    Voting,   // 投票中
+    Passed,   // 已通过，等待激活
+    Rejected, // 未通过
+    Applied,  // 已生效
+}
+
+/// 治理提案
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Proposal {
+    pub id: String,
+    pub proposer: String,
+    pub change: ParamChange,
+    pub description: String,
+    pub created_height: u64,
+    pub voting_end_height: u64,
+    pub activation_height: u64,
+    pub votes: HashMap<String, bool>, // 投票人 -> 是否赞成
+    pub yes_power: u64,
+    pub no_power: u64,
+    pub status: ProposalStatus,
+}
+
+impl Proposal {
+    /// 指定高度是否仍在投票期内
+    pub fn is_voting(&self, height: u64) -> bool {
+        self.status == ProposalStatus::Voting && height <= self.voting_end_height
+    }
+}
+
+/// 治理参数配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GovernanceConfig {
+    pub voting_period: u64,    // 投票期（区块数）
+    pub activation_delay: u64, // 投票结束到生效的延迟（区块数）
+    pub quorum_percent: u64,   // 最低参与率（占总投票权重百分比）
+    pub pass_percent: u64,     // 通过所需赞成比例（占已投票权重百分比）
+}
+
+impl Default for GovernanceConfig {
+    fn default() -> Self {
+        Self {
+            voting_period: 20,
+            activation_delay: 10,
+            quorum_percent: 33,
+            pass_percent: 50,
+        }
+    }
+}
+
+/// 链上治理 - 记录提案和投票，投票期结束时按质押权重统计，通过后在未来高度生效
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Governance {
+    pub config: GovernanceConfig,
+    pub proposals: BTreeMap<String, Proposal>,
+}
+
+impl Governance {
+    /// 校验治理交易能否进入交易池
+    pub fn validate_transaction(&self, transaction: &Transaction, voting_power: u64, height: u64) -> Result<(), String> {
+        if voting_power == 0 {
+            return Err(format!("{} 没有投票权重", transaction.sender));
+        }
+
+        match &transaction.kind {
+            TransactionKind::GovernanceProposal { change, .. } => {
+                if change.param == GovernanceParam::MinimumStake && change.value == 0 {
+                    return Err("最小质押不能为 0".to_string());
+                }
+                Ok(())
+            }
+            TransactionKind::GovernanceVote { proposal_id, .. } => {
+                let proposal = self.proposals
+                    .get(proposal_id)
+                    .ok_or_else(|| format!("提案 {} 不存在", proposal_id))?;
+                if !proposal.is_voting(height) {
+                    return Err(format!("提案 {} 不在投票期内", proposal_id));
+                }
+                if proposal.votes.contains_key(&transaction.sender) {
+                    return Err(format!("{} 已对提案 {} 投票", transaction.sender, proposal_id));
+                }
+                Ok(())
+            }
+            _ => Err("不是治理交易".to_string()),
+        }
+    }
+
+    /// 记录区块中的治理交易
+    pub fn apply_block(&mut self, block: &Block) {
+        for transaction in &block.transactions {
+            match &transaction.kind {
+                TransactionKind::GovernanceProposal { change, description } => {
+                    let voting_end_height = block.height + self.config.voting_period;
+                    self.proposals.insert(transaction.id.clone(), Proposal {
+                        id: transaction.id.clone(),
+                        proposer: transaction.sender.clone(),
+                        change: change.clone(),
+                        description: description.clone(),
+                        created_height: block.height,
+                        voting_end_height,
+                        activation_height: voting_end_height + self.config.activation_delay,
+                        votes: HashMap::new(),
+                        yes_power: 0,
+                        no_power: 0,
+                        status: ProposalStatus::Voting,
+                    });
+                }
+                TransactionKind::GovernanceVote { proposal_id, approve } => {
+                    if let Some(proposal) = self.proposals.get_mut(proposal_id)
+                        && proposal.is_voting(block.height)
+                    {
+                        proposal.votes.entry(transaction.sender.clone()).or_insert(*approve);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// 统计投票期已结束的提案，返回本次结束投票的提案 ID
+    pub fn tally<F>(&mut self, height: u64, total_power: u64, voting_power: F) -> Vec<String>
+    where
+        F: Fn(&str) -> u64,
+    {
+        let mut tallied = Vec::new();
+        for proposal in self.proposals.values_mut() {
+            if proposal.status != ProposalStatus::Voting || height <= proposal.voting_end_height {
+                continue;
+            }
+
+            // 按统计时的质押权重计票
+            let (yes_power, no_power) = proposal.votes.iter().fold((0, 0), |(yes, no), (voter, approve)| {
+                let power = voting_power(voter);
+                if *approve { (yes + power, no) } else { (yes, no + power) }
+            });
+            proposal.yes_power = yes_power;
+            proposal.no_power = no_power;
+
+            let turnout = yes_power + no_power;
+            let quorum_reached = turnout > 0 && turnout * 100 >= total_power * self.config.quorum_percent;
+            let passed = quorum_reached && yes_power * 100 > turnout * self.config.pass_percent;
+            proposal.status = if passed { ProposalStatus::Passed } else { ProposalStatus::Rejected };
+            tallied.push(proposal.id.clone());
+        }
+        tallied
+    }
+
+    /// 取出到达激活高度的已通过提案并标记为已生效
+    pub fn activate(&mut self, height: u64) -> Vec<ParamChange> {
+        self.proposals
+            .values_mut()
+            .filter(|proposal| proposal.status == ProposalStatus::Passed && proposal.activation_height <= height)
+            .map(|proposal| {
+                proposal.status = ProposalStatus::Applied;
+                proposal.change.clone()
+            })
+            .collect()
+    }
+
+    /// 获取指定高度已生效的最近一次参数修改 (激活高度, 值)
+    pub fn applied_at(&self, param: GovernanceParam, height: u64) -> Option<(u64, u64)> {
+        self.proposals
+            .values()
+            .filter(|proposal| proposal.status == ProposalStatus::Applied && proposal.change.param == param)
+            .filter(|proposal| proposal.activation_height <= height)
+            .map(|proposal| (proposal.activation_height, proposal.change.value))
+            .max_by_key(|(activation_height, _)| *activation_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voting_power(voter: &str) -> u64 {
+        match voter {
+            "alice" => 50,
+            "bob" => 30,
+            "carol" => 20,
+            _ => 0,
+        }
+    }
+
+    /// 在高度 1 创建提案，返回提案 ID
+    fn propose(governance: &mut Governance) -> String {
+        let change = ParamChange { param: GovernanceParam::MiningReward, value: 50 };
+        let proposal = Transaction::new_proposal("alice".to_string(), change, String::new());
+        governance.apply_block(&Block::new("0".to_string(), vec![proposal.clone()], 1, 0));
+        proposal.id
+    }
+
+    fn vote(governance: &mut Governance, height: u64, votes: &[(&str, &str, bool)]) {
+        let transactions = votes
+            .iter()
+            .map(|(voter, id, approve)| Transaction::new_vote(voter.to_string(), id.to_string(), *approve))
+            .collect();
+        governance.apply_block(&Block::new("0".to_string(), transactions, height, 0));
+    }
+
+    #[test]
+    fn passed_proposal_activates_after_delay() {
+        let mut governance = Governance::default();
+        let id = propose(&mut governance);
+        let proposal = &governance.proposals[&id];
+        assert_eq!((proposal.voting_end_height, proposal.activation_height), (21, 31));
+
+        // 重复投票只记第一票，投票期结束后的投票不计
+        vote(&mut governance, 2, &[("alice", &id, true), ("bob", &id, false), ("alice", &id, false)]);
+        vote(&mut governance, 22, &[("carol", &id, false)]);
+        assert!(governance.tally(21, 100, voting_power).is_empty());
+        assert_eq!(governance.tally(22, 100, voting_power), vec![id.clone()]);
+
+        let proposal = &governance.proposals[&id];
+        assert_eq!((proposal.yes_power, proposal.no_power), (50, 30));
+        assert_eq!(proposal.status, ProposalStatus::Passed);
+        assert!(governance.tally(23, 100, voting_power).is_empty());
+
+        assert!(governance.activate(30).is_empty());
+        assert_eq!(governance.applied_at(GovernanceParam::MiningReward, 31), None);
+        assert_eq!(governance.activate(31), vec![ParamChange { param: GovernanceParam::MiningReward, value: 50 }]);
+        assert!(governance.activate(32).is_empty());
+        assert_eq!(governance.applied_at(GovernanceParam::MiningReward, 30), None);
+        assert_eq!(governance.applied_at(GovernanceParam::MiningReward, 31), Some((31, 50)));
+        assert_eq!(governance.applied_at(GovernanceParam::MinimumStake, 31), None);
+    }
+
+    #[test]
+    fn proposal_without_quorum_or_majority_is_rejected() {
+        let mut governance = Governance::default();
+
+        // 参与率 30% 低于 33%
+        let low_turnout = propose(&mut governance);
+        vote(&mut governance, 2, &[("bob", &low_turnout, true)]);
+        governance.tally(22, 100, voting_power);
+        assert_eq!(governance.proposals[&low_turnout].status, ProposalStatus::Rejected);
+
+        // 赞成恰好 50% 不算通过
+        let mut governance = Governance::default();
+        let tie = propose(&mut governance);
+        vote(&mut governance, 2, &[("bob", &tie, true), ("carol", &tie, false)]);
+        governance.tally(22, 60, |voter| if voter == "bob" { 20 } else { voting_power(voter) });
+        assert_eq!(governance.proposals[&tie].status, ProposalStatus::Rejected);
+        assert!(governance.activate(100).is_empty());
+    }
+
+    #[test]
+    fn validates_votes_against_proposal_state() {
+        let mut governance = Governance::default();
+        let id = propose(&mut governance);
+        vote(&mut governance, 2, &[("alice", &id, true)]);
+
+        let bob_vote = Transaction::new_vote("bob".to_string(), id.clone(), true);
+        assert!(governance.validate_transaction(&bob_vote, 30, 21).is_ok());
+        assert!(governance.validate_transaction(&bob_vote, 0, 21).is_err());
+        assert!(governance.validate_transaction(&bob_vote, 30, 22).is_err());
+        let again = Transaction::new_vote("alice".to_string(), id, false);
+        assert!(governance.validate_transaction(&again, 50, 3).is_err());
+        let unknown = Transaction::new_vote("bob".to_string(), "missing".to_string(), true);
+        assert!(governance.validate_transaction(&unknown, 30, 3).is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+/// 每个节点记录的已知库存条目上限
+pub const KNOWN_INVENTORY_CAPACITY: usize = 10_000;
+/// 单条 Inv/GetData 消息最多包含的条目数
+pub const MAX_INVENTORY_ITEMS: usize = 1000;
+
+/// 库存条目 - 以哈希标识的交易或区块，先公告再由对方按需获取
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InventoryItem {
+    Transaction(String),
+    Block(String),
+}
+
+impl fmt::Display for InventoryItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InventoryItem::Transaction(id) => write!(f, "交易 {}", id),
+            InventoryItem::Block(hash) => write!(f, "区块 {}", hash),
+        }
+    }
+}
+
+/// 已知库存集合 - 记录对方已拥有或已被告知的条目，超过容量时淘汰最早记录的条目
+#[derive(Debug, Clone, Default)]
+pub struct KnownInventory {
+    items: HashSet<InventoryItem>,
+    order: VecDeque<InventoryItem>,
+}
+
+impl KnownInventory {
+    /// 记录条目，返回是否为新条目
+    pub fn insert(&mut self, item: InventoryItem) -> bool {
+        if !self.items.insert(item.clone()) {
+            return false;
+        }
+        self.order.push_back(item);
+        if self.order.len() > KNOWN_INVENTORY_CAPACITY
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.items.remove(&oldest);
+        }
+        true
+    }
+}
@@ -1,43 +1,121 @@
+mod address_book;
 mod block;
 mod blockchain;
 mod p2p_node;
 mod cli;
 mod consensus;
+mod chain_params;
+mod chain_spec;
+mod codec;
+mod finality;
+mod governance;
+mod inventory;
+mod misbehavior;
+mod miner;
+mod monetary;
+mod pow;
 mod wallet;
 mod web;
 
 use blockchain::Blockchain;
+use chain_spec::ChainSpec;
+use miner::MiningService;
 use p2p_node::P2PNode;
-use cli::{add_transaction_cli, mine_block_cli, view_balance_cli, solana_demo, p2p_menu,
+use cli::{add_transaction_cli, mine_block_cli, mining_service_menu, view_balance_cli, solana_demo, p2p_menu,
           generate_keypair_cli, view_public_key_cli, add_signed_transaction_cli, verify_transaction_signature_cli};
-use consensus::{ConsensusType, ProofOfStake, DelegatedProofOfStake};
-use wallet::WalletManager;
+use consensus::{ConsensusType, Evidence};
+use governance::{GovernanceParam, ParamChange};
+use block::{Transaction, TransactionKind};
+use wallet::{ValidatorKey, WalletManager};
 use web::start_web_server;
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 use std::io::{self, Write};
-use std::thread;
+use tokio::runtime::{Handle, Runtime};
 
-/// 初始化区块链
-fn initialize_blockchain() -> Blockchain {
+/// 本节点验证者签名密钥文件（启动时自动加载）
+const VALIDATOR_KEY_FILE: &str = "validator_key.json";
+
+/// 读取启动参数 `name` 后面的值
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1)).cloned()
+}
+
+/// 解析启动参数中的链配置：--chain <mainnet|testnet|regtest|配置文件路径>，默认 mainnet
+fn load_chain_spec() -> ChainSpec {
+    let chain = arg_value("--chain").unwrap_or_else(|| "mainnet".to_string());
+
+    match ChainSpec::resolve(&chain) {
+        Ok(spec) => spec,
+        Err(e) => {
+            println!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 初始化区块链：加载 blockchain.json，数据文件属于其他链时拒绝启动；文件不存在时按链配置创建
+fn initialize_blockchain(spec: &ChainSpec) -> Blockchain {
     match Blockchain::load_from_file("blockchain.json") {
         Ok(loaded_blockchain) => {
+            // 数据文件与启动参数指定的链不一致时拒绝启动，避免在错误的链上继续出块和同步
+            let genesis_hash = &loaded_blockchain.chain[0].header.hash;
+            if let Err(e) = spec.check_data(&loaded_blockchain.chain_params.chain_id, genesis_hash) {
+                println!("❌ {}", e);
+                println!("💡 请用 --chain 指定创建 blockchain.json 时的链配置，或移走 blockchain.json 后按新链配置重新创建");
+                std::process::exit(1);
+            }
             println!("✅ 从文件加载区块链成功!");
             loaded_blockchain
         }
         Err(_) => {
-            println!("📁 区块链文件不存在，创建新区块链...");
-            Blockchain::new(4, 100)
+            println!("📁 区块链文件不存在，按链配置 {} 创建新区块链...", spec.chain_id);
+            Blockchain::from_spec(spec)
         }
     }
 }
 
-/// 初始化 P2P 节点
-fn initialize_p2p_node(blockchain: &Arc<Mutex<Blockchain>>) -> P2PNode {
-    P2PNode::new("127.0.0.1:7878".parse().unwrap(), blockchain.clone())
+/// 初始化 P2P 节点，监听地址由 --p2p <地址> 指定，默认 127.0.0.1:7878；种子地址来自链配置和 --seeds 参数
+///
+/// 已知节点地址保存在 peers.json 中，有已知地址时自动启动节点并重新连接网络
+fn initialize_p2p_node(blockchain: &Arc<Mutex<Blockchain>>, spec: &ChainSpec, runtime: &Handle) -> P2PNode {
+    let address = arg_value("--p2p").unwrap_or_else(|| "127.0.0.1:7878".to_string());
+    let mut p2p_node = match address.parse() {
+        Ok(address) => P2PNode::new(address, blockchain.clone(), runtime.clone(), "peers.json"),
+        Err(_) => {
+            println!("❌ 无效的 P2P 监听地址: {}", address);
+            std::process::exit(1);
+        }
+    };
+
+    // 种子地址：链配置中的 seeds 加上 --seeds 参数（逗号分隔）
+    let mut seeds = spec.seeds.clone();
+    if let Some(extra) = arg_value("--seeds") {
+        seeds.extend(extra.split(',').map(str::trim).filter(|seed| !seed.is_empty()).map(str::to_string));
+    }
+    if !seeds.is_empty() {
+        let added = p2p_node.add_seeds(&seeds);
+        println!("🌱 已加载 {} 个种子节点地址", added);
+    }
+
+    if !p2p_node.address_book.lock().unwrap().is_empty() {
+        println!("🌐 存在已知节点地址，自动启动 P2P 节点");
+        if let Err(e) = p2p_node.start() {
+            println!("❌ 启动 P2P 节点失败: {}", e);
+        }
+    }
+    p2p_node
 }
 
 /// 主循环
-fn run_main_loop(blockchain: &Arc<Mutex<Blockchain>>, wallet_manager: &Arc<WalletManager>, p2p_node: &mut P2PNode) {
+fn run_main_loop(
+    blockchain: &Arc<Mutex<Blockchain>>,
+    wallet_manager: &Arc<WalletManager>,
+    p2p_node: &mut P2PNode,
+    mining_service: &Arc<MiningService>,
+    runtime: &Handle,
+) {
     loop {
         println!("\n请选择操作:");
         println!("1. 添加交易");
@@ -55,8 +133,9 @@ fn run_main_loop(blockchain: &Arc<Mutex<Blockchain>>, wallet_manager: &Arc<Walle
         println!("13. Solana 智能合约演示");
         println!("14. P2P 网络操作");
         println!("15. 共识算法管理");
-        println!("16. 退出");
-        print!("输入选择 (1-16): ");
+        println!("16. 挖矿服务");
+        println!("17. 退出");
+        print!("输入选择 (1-17): ");
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
@@ -92,21 +171,21 @@ fn run_main_loop(blockchain: &Arc<Mutex<Blockchain>>, wallet_manager: &Arc<Walle
                 // 启动区块链浏览器
                 let blockchain_clone = blockchain.clone();
                 let wallet_manager_clone = Arc::clone(wallet_manager);
-                thread::spawn(move || {
-                    let rt = tokio::runtime::Runtime::new().unwrap();
-                    rt.block_on(async {
-                        if let Err(e) = start_web_server(blockchain_clone, wallet_manager_clone, 3000).await {
-                            eprintln!("❌ Web服务器启动失败: {}", e);
-                        }
-                    });
+                let mining_service_clone = Arc::clone(mining_service);
+                runtime.spawn(async move {
+                    if let Err(e) = start_web_server(blockchain_clone, wallet_manager_clone, mining_service_clone, 3000).await {
+                        eprintln!("❌ Web服务器启动失败: {}", e);
+                    }
                 });
                 println!("✅ 区块链浏览器已在后台启动!");
                 println!("🌐 请在浏览器中访问: http://127.0.0.1:3000/explorer");
             }
             "13" => solana_demo(),
             "14" => p2p_menu(blockchain, p2p_node),
-            "15" => consensus_menu(blockchain),
-            "16" => {
+            "15" => consensus_menu(blockchain, p2p_node),
+            "16" => mining_service_menu(mining_service),
+            "17" => {
+                p2p_node.stop();
                 println!("👋 再见!");
                 break;
             }
@@ -119,34 +198,61 @@ fn main() {
     println!("🚀 欢迎使用 Rust 区块链 CLI!");
     println!("=====================================\n");
 
-    // 初始化区块链
-    let blockchain = initialize_blockchain();
+    // 加载链配置并初始化区块链
+    let spec = load_chain_spec();
+    let mut blockchain = initialize_blockchain(&spec);
+    let chain_id = match blockchain.chain_params.chain_id.as_str() {
+        "" => "旧版数据文件",
+        chain_id => chain_id,
+    };
+    println!("🧬 链: {}，创世区块: {}", chain_id, blockchain.chain[0].header.hash);
+    if let Ok(key) = ValidatorKey::load_from_file(VALIDATOR_KEY_FILE) {
+        println!("🔑 已加载验证者 {} 的签名密钥", key.address);
+        blockchain.validator_key = Some(key);
+    }
     let blockchain_arc = Arc::new(Mutex::new(blockchain));
 
     // 初始化钱包管理器
     let wallet_manager = WalletManager::new();
     let wallet_manager_arc = Arc::new(wallet_manager);
 
+    // P2P 网络和区块链浏览器共享同一个 tokio 运行时
+    let runtime = Runtime::new().expect("创建 tokio 运行时失败");
+
     // 初始化 P2P 节点
-    let mut p2p_node = initialize_p2p_node(&blockchain_arc);
+    let mut p2p_node = initialize_p2p_node(&blockchain_arc, &spec, runtime.handle());
+
+    // 初始化挖矿服务（挖出的区块通过 P2P 节点广播）
+    let mining_service = Arc::new(MiningService::new(blockchain_arc.clone(), p2p_node.handle(), "blockchain.json"));
 
     // 启动主循环
-    run_main_loop(&blockchain_arc, &wallet_manager_arc, &mut p2p_node);
+    run_main_loop(&blockchain_arc, &wallet_manager_arc, &mut p2p_node, &mining_service, runtime.handle());
 }
 
 /// 共识算法管理菜单
-fn consensus_menu(blockchain: &Arc<Mutex<Blockchain>>) {
+fn consensus_menu(blockchain: &Arc<Mutex<Blockchain>>, p2p_node: &P2PNode) {
     loop {
         println!("\n⚖️ 共识算法管理");
         println!("=====================================");
-        println!("当前共识算法: {:?}", blockchain.lock().unwrap().consensus_type);
-        println!("\n1. 切换到 PoW (工作量证明)");
-        println!("2. 切换到 PoS (权益证明)");
-        println!("3. 切换到 DPoS (委托权益证明)");
-        println!("4. PoS 质押管理");
-        println!("5. DPoS 候选人管理");
-        println!("6. 返回主菜单");
-        print!("输入选择 (1-6): ");
+        {
+            let blockchain = blockchain.lock().unwrap();
+            println!("当前共识算法: {:?}", blockchain.consensus_type);
+            if let Some(fork) = blockchain.chain_params.next_fork(blockchain.get_length() as u64) {
+                println!("下一次硬分叉: 高度 {} 切换到 {:?}", fork.activation_height, fork.rules.consensus_type);
+            }
+        }
+        println!("\n1. 计划切换到 PoW (工作量证明)");
+        println!("2. 计划切换到 PoS (权益证明)");
+        println!("3. 计划切换到 DPoS (委托权益证明)");
+        println!("4. 计划切换到 PoA (权威证明)");
+        println!("5. PoS 质押管理");
+        println!("6. DPoS 候选人管理");
+        println!("7. PoA 授权节点管理");
+        println!("8. 验证者罚没管理");
+        println!("9. 查看硬分叉计划");
+        println!("10. 链上治理");
+        println!("11. 返回主菜单");
+        print!("输入选择 (1-11): ");
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
@@ -154,29 +260,48 @@ fn consensus_menu(blockchain: &Arc<Mutex<Blockchain>>) {
         let choice = input.trim();
 
         match choice {
-            "1" => {
-                blockchain.lock().unwrap().consensus_type = ConsensusType::PoW;
-                blockchain.lock().unwrap().pos_consensus = None;
-                blockchain.lock().unwrap().dpos_consensus = None;
-                println!("✅ 已切换到 PoW 共识算法");
-            }
-            "2" => {
-                let mut blockchain = blockchain.lock().unwrap();
-                blockchain.consensus_type = ConsensusType::PoS;
-                blockchain.pos_consensus = Some(ProofOfStake::new(100)); // 最小质押100
-                blockchain.dpos_consensus = None;
-                println!("✅ 已切换到 PoS 共识算法");
+            "1" => schedule_consensus_fork_cli(blockchain, ConsensusType::PoW, BTreeMap::new()),
+            "2" => schedule_consensus_fork_cli(blockchain, ConsensusType::PoS, BTreeMap::new()),
+            "3" => schedule_consensus_fork_cli(blockchain, ConsensusType::DPoS, BTreeMap::new()),
+            "4" => {
+                print!("输入初始授权节点 (地址:公钥，逗号分隔): ");
+                io::stdout().flush().unwrap();
+                let mut authorities_input = String::new();
+                io::stdin().read_line(&mut authorities_input).unwrap();
+                let authority_keys: Option<BTreeMap<String, String>> = authorities_input
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|entry| !entry.is_empty())
+                    .map(|entry| {
+                        let (authority, public_key) = entry.split_once(':')?;
+                        Some((authority.trim().to_string(), public_key.trim().to_string()))
+                    })
+                    .collect();
+
+                match authority_keys {
+                    Some(authority_keys) if !authority_keys.is_empty() => {
+                        schedule_consensus_fork_cli(blockchain, ConsensusType::PoA, authority_keys);
+                    }
+                    Some(_) => println!("❌ 至少需要一个授权节点"),
+                    None => println!("❌ 格式错误，每个授权节点需写成 地址:公钥"),
+                }
             }
-            "3" => {
-                let mut blockchain = blockchain.lock().unwrap();
-                blockchain.consensus_type = ConsensusType::DPoS;
-                blockchain.pos_consensus = None;
-                blockchain.dpos_consensus = Some(DelegatedProofOfStake::new(1000, 100)); // 最小质押1000，委托100
-                println!("✅ 已切换到 DPoS 共识算法");
+            "5" => pos_stake_menu(blockchain),
+            "6" => dpos_candidate_menu(blockchain),
+            "7" => poa_authority_menu(blockchain),
+            "8" => slashing_menu(blockchain, p2p_node),
+            "9" => {
+                let blockchain = blockchain.lock().unwrap();
+                let next_height = blockchain.get_length() as u64;
+                println!("📋 硬分叉计划 (计划哈希: {}):", blockchain.chain_params.schedule_hash());
+                for fork in &blockchain.chain_params.forks {
+                    let status = if fork.activation_height <= next_height { "已激活" } else { "待激活" };
+                    println!("  高度 {} - {} - {:?} - 奖励 {} - {}",
+                             fork.activation_height, fork.name, fork.rules.consensus_type, fork.rules.mining_reward, status);
+                }
             }
-            "4" => pos_stake_menu(blockchain),
-            "5" => dpos_candidate_menu(blockchain),
-            "6" => break,
+            "10" => governance_menu(blockchain),
+            "11" => break,
             _ => println!("❌ 无效选择，请重新输入."),
         }
     }
@@ -218,11 +343,10 @@ fn pos_stake_menu(blockchain: &Arc<Mutex<Blockchain>>) {
                     }
                 };
 
-                if let Some(ref mut pos) = blockchain.lock().unwrap().pos_consensus {
-                    match pos.stake(validator, amount) {
-                        Ok(_) => println!("✅ 质押成功!"),
-                        Err(e) => println!("❌ 质押失败: {}", e),
-                    }
+                let transaction = Transaction::new_staking(validator, TransactionKind::Stake, amount);
+                match blockchain.lock().unwrap().add_transaction(transaction) {
+                    Ok(_) => println!("✅ 质押交易已加入交易池，打包后锁定质押金额"),
+                    Err(e) => println!("❌ 质押失败: {}", e),
                 }
             }
             "2" => {
@@ -232,11 +356,10 @@ fn pos_stake_menu(blockchain: &Arc<Mutex<Blockchain>>) {
                 io::stdin().read_line(&mut validator).unwrap();
                 let validator = validator.trim().to_string();
 
-                if let Some(ref mut pos) = blockchain.lock().unwrap().pos_consensus {
-                    match pos.unstake(validator) {
-                        Ok(_) => println!("✅ 取消质押成功!"),
-                        Err(e) => println!("❌ 取消质押失败: {}", e),
-                    }
+                let transaction = Transaction::new_staking(validator, TransactionKind::Unstake, 0);
+                match blockchain.lock().unwrap().add_transaction(transaction) {
+                    Ok(_) => println!("✅ 取消质押交易已加入交易池，打包后退还质押金额"),
+                    Err(e) => println!("❌ 取消质押失败: {}", e),
                 }
             }
             "3" => {
@@ -263,10 +386,15 @@ fn dpos_candidate_menu(blockchain: &Arc<Mutex<Blockchain>>) {
         println!("=====================================");
         println!("1. 注册候选人");
         println!("2. 委托投票");
-        println!("3. 查看候选人");
-        println!("4. 查看委托信息");
-        println!("5. 返回上级菜单");
-        print!("输入选择 (1-5): ");
+        println!("3. 撤销委托");
+        println!("4. 转移委托");
+        println!("5. 注销候选人");
+        println!("6. 查看候选人");
+        println!("7. 查看委托信息");
+        println!("8. 查看出块排班");
+        println!("9. 设置候选人佣金比例");
+        println!("10. 返回上级菜单");
+        print!("输入选择 (1-10): ");
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
@@ -293,11 +421,10 @@ fn dpos_candidate_menu(blockchain: &Arc<Mutex<Blockchain>>) {
                     }
                 };
 
-                if let Some(ref mut dpos) = blockchain.lock().unwrap().dpos_consensus {
-                    match dpos.register_candidate(candidate, amount) {
-                        Ok(_) => println!("✅ 候选人注册成功!"),
-                        Err(e) => println!("❌ 候选人注册失败: {}", e),
-                    }
+                let transaction = Transaction::new_staking(candidate, TransactionKind::RegisterCandidate, amount);
+                match blockchain.lock().unwrap().add_transaction(transaction) {
+                    Ok(_) => println!("✅ 注册交易已加入交易池，打包后成为候选人"),
+                    Err(e) => println!("❌ 候选人注册失败: {}", e),
                 }
             }
             "2" => {
@@ -325,36 +452,544 @@ fn dpos_candidate_menu(blockchain: &Arc<Mutex<Blockchain>>) {
                     }
                 };
 
-                if let Some(ref mut dpos) = blockchain.lock().unwrap().dpos_consensus {
-                    match dpos.delegate(delegator, candidate, amount) {
-                        Ok(_) => println!("✅ 委托投票成功!"),
-                        Err(e) => println!("❌ 委托投票失败: {}", e),
-                    }
+                let transaction = Transaction::new_staking(delegator, TransactionKind::Delegate { candidate }, amount);
+                match blockchain.lock().unwrap().add_transaction(transaction) {
+                    Ok(_) => println!("✅ 委托交易已加入交易池，打包后生效"),
+                    Err(e) => println!("❌ 委托投票失败: {}", e),
                 }
             }
             "3" => {
+                print!("输入委托人地址: ");
+                io::stdout().flush().unwrap();
+                let mut delegator = String::new();
+                io::stdin().read_line(&mut delegator).unwrap();
+                let delegator = delegator.trim().to_string();
+
+                print!("输入候选人地址: ");
+                io::stdout().flush().unwrap();
+                let mut candidate = String::new();
+                io::stdin().read_line(&mut candidate).unwrap();
+                let candidate = candidate.trim().to_string();
+
+                print!("输入撤销金额: ");
+                io::stdout().flush().unwrap();
+                let mut amount_str = String::new();
+                io::stdin().read_line(&mut amount_str).unwrap();
+                let amount: u64 = match amount_str.trim().parse() {
+                    Ok(num) => num,
+                    Err(_) => {
+                        println!("❌ 无效金额");
+                        continue;
+                    }
+                };
+
+                let mut blockchain = blockchain.lock().unwrap();
+                let unbonding_period = blockchain.dpos_consensus.as_ref().map(|dpos| dpos.unbonding_period).unwrap_or(0);
+                let transaction = Transaction::new_staking(delegator, TransactionKind::Undelegate { candidate, amount }, 0);
+                match blockchain.add_transaction(transaction) {
+                    Ok(_) => println!("✅ 撤销委托交易已加入交易池，打包后经过 {} 个区块完成解绑", unbonding_period),
+                    Err(e) => println!("❌ 撤销委托失败: {}", e),
+                }
+            }
+            "4" => {
+                print!("输入委托人地址: ");
+                io::stdout().flush().unwrap();
+                let mut delegator = String::new();
+                io::stdin().read_line(&mut delegator).unwrap();
+                let delegator = delegator.trim().to_string();
+
+                print!("输入原候选人地址: ");
+                io::stdout().flush().unwrap();
+                let mut from = String::new();
+                io::stdin().read_line(&mut from).unwrap();
+                let from = from.trim().to_string();
+
+                print!("输入新候选人地址: ");
+                io::stdout().flush().unwrap();
+                let mut to = String::new();
+                io::stdin().read_line(&mut to).unwrap();
+                let to = to.trim().to_string();
+
+                print!("输入转移金额: ");
+                io::stdout().flush().unwrap();
+                let mut amount_str = String::new();
+                io::stdin().read_line(&mut amount_str).unwrap();
+                let amount: u64 = match amount_str.trim().parse() {
+                    Ok(num) => num,
+                    Err(_) => {
+                        println!("❌ 无效金额");
+                        continue;
+                    }
+                };
+
+                let transaction = Transaction::new_staking(delegator, TransactionKind::Redelegate { from, to, amount }, 0);
+                match blockchain.lock().unwrap().add_transaction(transaction) {
+                    Ok(_) => println!("✅ 转移委托交易已加入交易池，打包后生效"),
+                    Err(e) => println!("❌ 转移委托失败: {}", e),
+                }
+            }
+            "5" => {
+                print!("输入候选人地址: ");
+                io::stdout().flush().unwrap();
+                let mut candidate = String::new();
+                io::stdin().read_line(&mut candidate).unwrap();
+                let candidate = candidate.trim().to_string();
+
+                let transaction = Transaction::new_staking(candidate.clone(), TransactionKind::DeregisterCandidate, 0);
+                match blockchain.lock().unwrap().add_transaction(transaction) {
+                    Ok(_) => println!("✅ 注销交易已加入交易池，打包后 {} 的质押和委托进入解绑期", candidate),
+                    Err(e) => println!("❌ 注销候选人失败: {}", e),
+                }
+            }
+            "6" => {
                 if let Some(ref dpos) = blockchain.lock().unwrap().dpos_consensus {
                     println!("📋 候选人列表:");
                     for candidate in &dpos.candidates {
                         let weight = dpos.calculate_candidate_weight(candidate);
-                        println!("  候选人: {} - 权重: {}", candidate, weight);
+                        println!("  候选人: {} - 权重: {} - 佣金: {}%", candidate, weight, dpos.commission_rate(candidate));
                     }
                 } else {
                     println!("❌ 当前未使用 DPoS 共识算法");
                 }
             }
-            "4" => {
+            "7" => {
                 if let Some(ref dpos) = blockchain.lock().unwrap().dpos_consensus {
                     println!("📋 委托信息:");
-                    for (key, delegation) in &dpos.delegations {
+                    for delegation in dpos.delegations.values() {
                         println!("  {} -> {}: {}", delegation.delegator, delegation.candidate, delegation.amount);
                     }
+                    println!("⏳ 解绑队列 (解绑期 {} 个区块):", dpos.unbonding_period);
+                    for entry in &dpos.unbonding {
+                        println!("  {} <- {}: {} (解绑高度 {})",
+                                entry.delegator, entry.candidate, entry.amount, entry.release_height);
+                    }
+                } else {
+                    println!("❌ 当前未使用 DPoS 共识算法");
+                }
+            }
+            "8" => {
+                let blockchain = blockchain.lock().unwrap();
+                if let Some(ref dpos) = blockchain.dpos_consensus {
+                    let schedule = dpos.current_schedule(&blockchain);
+                    println!("📅 纪元起始高度: {} (每纪元 {} 个区块，槽位 {} 秒)",
+                            schedule.epoch_start, schedule.epoch_length, schedule.slot_duration_secs);
+                    println!("📋 出块顺序:");
+                    for (i, producer) in schedule.producers.iter().enumerate() {
+                        println!("  {}. {} - 权重: {}", i + 1, producer, dpos.calculate_candidate_weight(producer));
+                    }
+                    match blockchain.expected_validator() {
+                        Some(producer) => println!("⏱️ 当前槽位出块人: {}", producer),
+                        None => println!("📭 暂无当选出块人"),
+                    }
                 } else {
                     println!("❌ 当前未使用 DPoS 共识算法");
                 }
             }
+            "9" => {
+                print!("输入候选人地址: ");
+                io::stdout().flush().unwrap();
+                let mut candidate = String::new();
+                io::stdin().read_line(&mut candidate).unwrap();
+                let candidate = candidate.trim().to_string();
+
+                print!("输入佣金比例 (0-100): ");
+                io::stdout().flush().unwrap();
+                let mut rate_str = String::new();
+                io::stdin().read_line(&mut rate_str).unwrap();
+                let rate: u64 = match rate_str.trim().parse() {
+                    Ok(num) => num,
+                    Err(_) => {
+                        println!("❌ 无效比例");
+                        continue;
+                    }
+                };
+
+                let transaction = Transaction::new_staking(candidate, TransactionKind::SetCommission { rate }, 0);
+                match blockchain.lock().unwrap().add_transaction(transaction) {
+                    Ok(_) => println!("✅ 佣金设置交易已加入交易池，打包后按 {}% 提取佣金", rate),
+                    Err(e) => println!("❌ 设置佣金比例失败: {}", e),
+                }
+            }
+            "10" => break,
+            _ => println!("❌ 无效选择，请重新输入."),
+        }
+    }
+}
+
+/// 计划共识算法切换硬分叉
+fn schedule_consensus_fork_cli(blockchain: &Arc<Mutex<Blockchain>>, consensus_type: ConsensusType, authority_keys: BTreeMap<String, String>) {
+    let next_height = blockchain.lock().unwrap().get_length() as u64;
+    print!("输入激活高度 (回车默认下一个区块 {}): ", next_height);
+    io::stdout().flush().unwrap();
+    let mut height_input = String::new();
+    io::stdin().read_line(&mut height_input).unwrap();
+    let activation_height = match height_input.trim() {
+        "" => next_height,
+        input => match input.parse::<u64>() {
+            Ok(height) => height,
+            Err(_) => {
+                println!("❌ 无效的激活高度");
+                return;
+            }
+        },
+    };
+
+    let mut blockchain = blockchain.lock().unwrap();
+    match blockchain.schedule_consensus_fork(consensus_type.clone(), activation_height, authority_keys) {
+        Ok(_) if activation_height == next_height => println!("✅ 已切换到 {:?} 共识算法", consensus_type),
+        Ok(_) => println!("✅ 已计划在高度 {} 切换到 {:?} 共识算法", activation_height, consensus_type),
+        Err(e) => println!("❌ 计划硬分叉失败: {}", e),
+    }
+}
+
+/// 链上治理菜单
+fn governance_menu(blockchain: &Arc<Mutex<Blockchain>>) {
+    loop {
+        println!("\n🏛️ 链上治理");
+        println!("=====================================");
+        println!("1. 查看提案");
+        println!("2. 发起参数修改提案");
+        println!("3. 对提案投票");
+        println!("4. 查看投票权重");
+        println!("5. 返回上级菜单");
+        print!("输入选择 (1-5): ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+        let choice = input.trim();
+
+        match choice {
+            "1" => {
+                let blockchain = blockchain.lock().unwrap();
+                let governance = &blockchain.governance;
+                println!("📋 治理提案 (投票期 {} 块, 生效延迟 {} 块, 法定参与率 {}%):",
+                         governance.config.voting_period, governance.config.activation_delay, governance.config.quorum_percent);
+                if governance.proposals.is_empty() {
+                    println!("  暂无提案");
+                }
+                for proposal in governance.proposals.values() {
+                    let approvals = proposal.votes.values().filter(|approve| **approve).count();
+                    println!("  {} - {:?} => {} - {:?}", proposal.id, proposal.change.param, proposal.change.value, proposal.status);
+                    println!("    提案人: {} - 说明: {}", proposal.proposer, proposal.description);
+                    println!("    投票截止: {} - 生效高度: {} - 赞成票: {} - 反对票: {}",
+                             proposal.voting_end_height, proposal.activation_height, approvals, proposal.votes.len() - approvals);
+                }
+            }
+            "2" => {
+                print!("输入提案人地址: ");
+                io::stdout().flush().unwrap();
+                let mut proposer = String::new();
+                io::stdin().read_line(&mut proposer).unwrap();
+                let proposer = proposer.trim().to_string();
+
+                print!("输入参数 (mining_reward / minimum_stake / minimum_delegation): ");
+                io::stdout().flush().unwrap();
+                let mut param = String::new();
+                io::stdin().read_line(&mut param).unwrap();
+                let param = match GovernanceParam::parse(param.trim()) {
+                    Some(param) => param,
+                    None => {
+                        println!("❌ 不支持的参数");
+                        continue;
+                    }
+                };
+
+                print!("输入新值: ");
+                io::stdout().flush().unwrap();
+                let mut value = String::new();
+                io::stdin().read_line(&mut value).unwrap();
+                let value: u64 = match value.trim().parse() {
+                    Ok(value) => value,
+                    Err(_) => {
+                        println!("❌ 无效数值");
+                        continue;
+                    }
+                };
+
+                print!("输入提案说明: ");
+                io::stdout().flush().unwrap();
+                let mut description = String::new();
+                io::stdin().read_line(&mut description).unwrap();
+                let description = description.trim().to_string();
+
+                let transaction = Transaction::new_proposal(proposer, ParamChange { param, value }, description);
+                match blockchain.lock().unwrap().add_transaction(transaction) {
+                    Ok(_) => println!("✅ 提案交易已加入交易池，打包后开始投票"),
+                    Err(e) => println!("❌ 发起提案失败: {}", e),
+                }
+            }
+            "3" => {
+                print!("输入投票人地址: ");
+                io::stdout().flush().unwrap();
+                let mut voter = String::new();
+                io::stdin().read_line(&mut voter).unwrap();
+                let voter = voter.trim().to_string();
+
+                print!("输入提案 ID: ");
+                io::stdout().flush().unwrap();
+                let mut proposal_id = String::new();
+                io::stdin().read_line(&mut proposal_id).unwrap();
+                let proposal_id = proposal_id.trim().to_string();
+
+                print!("是否赞成 (y/n): ");
+                io::stdout().flush().unwrap();
+                let mut approve = String::new();
+                io::stdin().read_line(&mut approve).unwrap();
+                let approve = approve.trim().eq_ignore_ascii_case("y");
+
+                let transaction = Transaction::new_vote(voter, proposal_id, approve);
+                match blockchain.lock().unwrap().add_transaction(transaction) {
+                    Ok(_) => println!("✅ 投票交易已加入交易池"),
+                    Err(e) => println!("❌ 投票失败: {}", e),
+                }
+            }
+            "4" => {
+                print!("输入地址: ");
+                io::stdout().flush().unwrap();
+                let mut address = String::new();
+                io::stdin().read_line(&mut address).unwrap();
+                let address = address.trim().to_string();
+
+                let blockchain = blockchain.lock().unwrap();
+                println!("🗳️ {} 的投票权重: {} / 全网 {}",
+                         address, blockchain.voting_power(&address), blockchain.total_voting_power());
+            }
             "5" => break,
             _ => println!("❌ 无效选择，请重新输入."),
         }
     }
 }
+
+/// PoA 授权节点管理菜单
+fn poa_authority_menu(blockchain: &Arc<Mutex<Blockchain>>) {
+    loop {
+        println!("\n🏛️ PoA 授权节点管理");
+        println!("=====================================");
+        println!("1. 查看授权节点");
+        println!("2. 生成本节点签名密钥");
+        println!("3. 提议加入授权节点");
+        println!("4. 提议移除授权节点");
+        println!("5. 撤回提议");
+        println!("6. 返回上级菜单");
+        print!("输入选择 (1-6): ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+        let choice = input.trim();
+
+        match choice {
+            "1" => {
+                let blockchain = blockchain.lock().unwrap();
+                if let Some(ref poa) = blockchain.poa_consensus {
+                    let in_turn = poa.in_turn_signer(blockchain.get_length() as u64);
+                    println!("📋 授权节点 (出块间隔 {} 秒):", poa.period_secs);
+                    for authority in &poa.authorities {
+                        let key_status = if poa.authority_keys.contains_key(authority) { "已登记公钥" } else { "未登记公钥" };
+                        let turn = if in_turn.as_ref() == Some(authority) { " ⏱️ 轮值" } else { "" };
+                        println!("  {} - {}{}", authority, key_status, turn);
+                    }
+                    println!("🗳️ 投票统计:");
+                    for (target, votes) in &poa.tallies {
+                        let authorize = votes.values().filter(|a| **a).count();
+                        println!("  {} - 赞成加入: {} - 赞成移除: {}", target, authorize, votes.len() - authorize);
+                    }
+                    println!("📝 本节点待提议:");
+                    for vote in poa.proposals.values() {
+                        println!("  {} - {}", vote.target, if vote.authorize { "加入" } else { "移除" });
+                    }
+                } else {
+                    println!("❌ 当前未使用 PoA 共识算法");
+                }
+            }
+            "2" => {
+                print!("输入本节点授权节点地址: ");
+                io::stdout().flush().unwrap();
+                let mut authority = String::new();
+                io::stdin().read_line(&mut authority).unwrap();
+                let authority = authority.trim().to_string();
+
+                // 授权节点公钥只能由 PoA 硬分叉规则或授权投票写入链上，这里只生成并保存本节点密钥
+                let key = ValidatorKey::generate(authority.clone());
+                if let Err(e) = key.save_to_file(VALIDATOR_KEY_FILE) {
+                    println!("❌ 保存验证者密钥失败: {}", e);
+                    continue;
+                }
+                println!("🔑 密钥已保存到 {}，公钥: {}", VALIDATOR_KEY_FILE, key.public_key_hex());
+                println!("💡 将「{}:公钥」写入 PoA 硬分叉，或由现有授权节点提议加入后即可出块", authority);
+                blockchain.lock().unwrap().validator_key = Some(key);
+            }
+            "3" => {
+                print!("输入要加入的节点地址: ");
+                io::stdout().flush().unwrap();
+                let mut target = String::new();
+                io::stdin().read_line(&mut target).unwrap();
+                let target = target.trim().to_string();
+
+                print!("输入该节点公钥 (十六进制): ");
+                io::stdout().flush().unwrap();
+                let mut public_key = String::new();
+                io::stdin().read_line(&mut public_key).unwrap();
+                let public_key = public_key.trim().to_string();
+
+                if let Some(ref mut poa) = blockchain.lock().unwrap().poa_consensus {
+                    match poa.propose(target, true, Some(public_key)) {
+                        Ok(_) => println!("✅ 提议已记录，将写入本节点签名的区块头"),
+                        Err(e) => println!("❌ 提议失败: {}", e),
+                    }
+                } else {
+                    println!("❌ 当前未使用 PoA 共识算法");
+                }
+            }
+            "4" => {
+                print!("输入要移除的授权节点地址: ");
+                io::stdout().flush().unwrap();
+                let mut target = String::new();
+                io::stdin().read_line(&mut target).unwrap();
+                let target = target.trim().to_string();
+
+                if let Some(ref mut poa) = blockchain.lock().unwrap().poa_consensus {
+                    match poa.propose(target, false, None) {
+                        Ok(_) => println!("✅ 提议已记录，将写入本节点签名的区块头"),
+                        Err(e) => println!("❌ 提议失败: {}", e),
+                    }
+                } else {
+                    println!("❌ 当前未使用 PoA 共识算法");
+                }
+            }
+            "5" => {
+                print!("输入提议目标地址: ");
+                io::stdout().flush().unwrap();
+                let mut target = String::new();
+                io::stdin().read_line(&mut target).unwrap();
+                let target = target.trim().to_string();
+
+                if let Some(ref mut poa) = blockchain.lock().unwrap().poa_consensus {
+                    match poa.discard_proposal(&target) {
+                        Ok(_) => println!("✅ 提议已撤回"),
+                        Err(e) => println!("❌ 撤回失败: {}", e),
+                    }
+                } else {
+                    println!("❌ 当前未使用 PoA 共识算法");
+                }
+            }
+            "6" => break,
+            _ => println!("❌ 无效选择，请重新输入."),
+        }
+    }
+}
+
+/// 验证者罚没管理菜单
+fn slashing_menu(blockchain: &Arc<Mutex<Blockchain>>, p2p_node: &P2PNode) {
+    loop {
+        println!("\n⚔️ 验证者罚没管理");
+        println!("=====================================");
+        println!("1. 生成验证者签名密钥并提交登记交易");
+        println!("2. 查看监禁名单");
+        println!("3. 查看罚没记录");
+        println!("4. 解除监禁");
+        println!("5. 提交双签证据 (JSON 文件)");
+        println!("6. 返回上级菜单");
+        print!("输入选择 (1-6): ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+        let choice = input.trim();
+
+        match choice {
+            "1" => {
+                print!("输入验证者地址: ");
+                io::stdout().flush().unwrap();
+                let mut validator = String::new();
+                io::stdin().read_line(&mut validator).unwrap();
+                let validator = validator.trim().to_string();
+
+                // 已有该验证者的密钥时重新提交登记，否则生成新密钥并保存
+                let mut blockchain = blockchain.lock().unwrap();
+                let key = match &blockchain.validator_key {
+                    Some(key) if key.address == validator => key.clone(),
+                    _ => ValidatorKey::generate(validator.clone()),
+                };
+                if let Err(e) = key.save_to_file(VALIDATOR_KEY_FILE) {
+                    println!("❌ 保存验证者密钥失败: {}", e);
+                    continue;
+                }
+                let public_key = key.public_key_hex();
+                blockchain.validator_key = Some(key.clone());
+                println!("🔑 验证者密钥已保存到 {}，公钥: {}", VALIDATOR_KEY_FILE, public_key);
+
+                let result = match blockchain.consensus_type {
+                    ConsensusType::PoS | ConsensusType::DPoS => blockchain.add_transaction(Transaction::new_validator_key(&key)),
+                    ConsensusType::PoA => Err("PoA 授权节点公钥由硬分叉规则或授权投票登记".to_string()),
+                    ConsensusType::PoW => Err("当前未使用 PoS/DPoS 共识算法".to_string()),
+                };
+                match result {
+                    Ok(_) => println!("✅ 公钥登记已提交，生效后本节点将以 {} 的身份签名区块", validator),
+                    Err(e) => println!("❌ 登记失败: {}", e),
+                }
+            }
+            "2" => {
+                let blockchain = blockchain.lock().unwrap();
+                let height = blockchain.get_length() as u64;
+                match blockchain.slashing() {
+                    Some(slashing) => {
+                        println!("📋 监禁名单:");
+                        for (validator, until) in &slashing.jailed {
+                            let status = if slashing.is_jailed(validator, height) { "监禁中" } else { "可解除" };
+                            println!("  验证者: {} - 解禁高度: {} - {}", validator, until, status);
+                        }
+                        println!("📉 漏块统计:");
+                        for (validator, missed) in &slashing.missed_slots {
+                            println!("  验证者: {} - 连续漏块: {}", validator, missed);
+                        }
+                    }
+                    None => println!("❌ 当前未使用 PoS/DPoS 共识算法"),
+                }
+            }
+            "3" => {
+                let blockchain = blockchain.lock().unwrap();
+                match blockchain.slashing() {
+                    Some(slashing) => {
+                        println!("📋 罚没记录 (累计销毁: {}):", slashing.total_burned);
+                        for record in &slashing.records {
+                            println!("  高度 {}: {} 因{}被罚没 {}，监禁至高度 {}",
+                                    record.height, record.validator, record.reason, record.amount, record.jailed_until);
+                        }
+                    }
+                    None => println!("❌ 当前未使用 PoS/DPoS 共识算法"),
+                }
+            }
+            "4" => {
+                print!("输入验证者地址: ");
+                io::stdout().flush().unwrap();
+                let mut validator = String::new();
+                io::stdin().read_line(&mut validator).unwrap();
+                let validator = validator.trim().to_string();
+
+                let transaction = Transaction::new_staking(validator.clone(), TransactionKind::Unjail, 0);
+                match blockchain.lock().unwrap().add_transaction(transaction) {
+                    Ok(_) => println!("✅ 验证者 {} 的解除监禁交易已加入交易池", validator),
+                    Err(e) => println!("❌ 解除监禁失败: {}", e),
+                }
+            }
+            "5" => {
+                print!("输入证据文件路径: ");
+                io::stdout().flush().unwrap();
+                let mut path = String::new();
+                io::stdin().read_line(&mut path).unwrap();
+
+                let evidence = std::fs::read_to_string(path.trim())
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| serde_json::from_str::<Evidence>(&json).map_err(|e| e.to_string()));
+                match evidence.and_then(|evidence| p2p_node.broadcast_evidence(evidence, None)) {
+                    Ok(_) => println!("✅ 证据交易已加入交易池并广播，上链后罚没验证者"),
+                    Err(e) => println!("❌ 提交证据失败: {}", e),
+                }
+            }
+            "6" => break,
+            _ => println!("❌ 无效选择，请重新输入."),
+        }
+    }
+}
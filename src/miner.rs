@@ -0,0 +1,347 @@
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::p2p_node::P2PNode;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 监视线程检查链顶和停止标志的间隔
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+/// 挖矿过程中报告算力的间隔
+const HASHRATE_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 挖矿统计
+#[derive(Debug, Clone, Copy)]
+pub struct MiningStats {
+    pub hashes: u64,
+    pub elapsed: Duration,
+    pub threads: usize,
+}
+
+impl MiningStats {
+    /// 平均算力（次/秒）
+    pub fn hashrate(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 { self.hashes as f64 / secs } else { 0.0 }
+    }
+}
+
+/// 默认挖矿线程数（可用 CPU 核心数）
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// 将请求的线程数限制在 1 到可用 CPU 核心数之间，避免外部请求创建大量线程
+pub fn clamp_threads(threads: usize) -> usize {
+    threads.clamp(1, default_threads())
+}
+
+/// 多线程搜索满足难度要求的 nonce，不持有区块链锁
+///
+/// 每个线程从不同起点按线程数步长搜索；监视线程在链顶变化（收到网络新区块）或 stop 置位时取消挖矿
+pub fn solve(
+    template: &Block,
+    threads: usize,
+    blockchain: &Arc<Mutex<Blockchain>>,
+    stop: &AtomicBool,
+) -> (Option<Block>, MiningStats) {
+    let threads = threads.max(1);
+    let done = AtomicBool::new(false);
+    let hashes = AtomicU64::new(0);
+    let found: Mutex<Option<Block>> = Mutex::new(None);
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        let watcher = thread::current();
+        for i in 0..threads {
+            let (done, hashes, found, watcher) = (&done, &hashes, &found, watcher.clone());
+            let mut candidate = template.clone();
+            scope.spawn(move || {
+                if candidate.mine_range(i as u64, threads as u64, done, hashes) {
+                    found.lock().unwrap().get_or_insert(candidate);
+                    done.store(true, Ordering::Relaxed);
+                    watcher.unpark(); // 唤醒监视线程立即结束
+                }
+            });
+        }
+
+        let mut last_report = Instant::now();
+        while !done.load(Ordering::Relaxed) {
+            thread::park_timeout(WATCH_INTERVAL);
+            if done.load(Ordering::Relaxed) {
+                break;
+            }
+
+            if stop.load(Ordering::Relaxed) {
+                println!("🛑 挖矿已停止");
+                done.store(true, Ordering::Relaxed);
+                break;
+            }
+
+            let tip_changed = blockchain.lock().unwrap().get_latest_block().header.hash != template.header.prev_hash;
+            if tip_changed {
+                println!("🔄 收到新的链顶，放弃区块 {} 的挖矿", template.height);
+                done.store(true, Ordering::Relaxed);
+                break;
+            }
+
+            if last_report.elapsed() >= HASHRATE_REPORT_INTERVAL {
+                let hashrate = hashes.load(Ordering::Relaxed) as f64 / start.elapsed().as_secs_f64();
+                println!("⛏️ 区块 {} 挖矿中: {:.0} H/s ({} 线程)", template.height, hashrate, threads);
+                last_report = Instant::now();
+            }
+        }
+    });
+
+    let stats = MiningStats {
+        hashes: hashes.load(Ordering::Relaxed),
+        elapsed: start.elapsed(),
+        threads,
+    };
+    (found.into_inner().unwrap(), stats)
+}
+
+/// 从交易池创建区块模板，在锁外多线程挖矿后提交到区块链
+pub fn mine_block(
+    blockchain: &Arc<Mutex<Blockchain>>,
+    miner_address: String,
+    threads: usize,
+    stop: &AtomicBool,
+) -> Result<(Block, MiningStats), String> {
+    let mut template = blockchain.lock().unwrap().create_block_template(miner_address)?;
+
+    println!("Mining block {}...", template.block.height);
+    let (solved, stats) = solve(&template.block, threads, blockchain, stop);
+    template.block = solved.ok_or("挖矿已取消")?;
+
+    let block = blockchain.lock().unwrap().submit_block(template)?;
+    println!("Block {} mined! Nonce: {}", block.height, block.header.nonce);
+    Ok((block, stats))
+}
+
+/// 出块条件不满足（如未轮到本节点出块）时的重试间隔
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 挖矿服务状态
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MiningStatus {
+    pub running: bool,
+    pub coinbase_address: String,
+    pub threads: usize,
+    pub blocks_mined: u64,
+    pub last_block_height: Option<u64>,
+    pub last_hashrate: f64,
+}
+
+/// 持续挖矿服务 - 在后台线程中不断从交易池创建区块模板（交易池为空时挖空块）、挖矿、上链、保存并广播
+pub struct MiningService {
+    blockchain: Arc<Mutex<Blockchain>>,
+    p2p_node: P2PNode,
+    data_file: String,
+    stop: Arc<AtomicBool>,
+    status: Arc<Mutex<MiningStatus>>,
+}
+
+impl MiningService {
+    /// 创建挖矿服务；p2p_node 用于广播挖出的区块，data_file 为区块链持久化文件
+    pub fn new(blockchain: Arc<Mutex<Blockchain>>, p2p_node: P2PNode, data_file: &str) -> Self {
+        Self {
+            blockchain,
+            p2p_node,
+            data_file: data_file.to_string(),
+            stop: Arc::new(AtomicBool::new(true)),
+            status: Arc::new(Mutex::new(MiningStatus {
+                threads: default_threads(),
+                ..MiningStatus::default()
+            })),
+        }
+    }
+
+    /// 当前状态
+    pub fn status(&self) -> MiningStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// 设置出块奖励地址（下一个区块模板生效）
+    pub fn set_coinbase_address(&self, address: String) -> Result<(), String> {
+        if address.is_empty() {
+            return Err("奖励地址不能为空".to_string());
+        }
+        self.status.lock().unwrap().coinbase_address = address;
+        Ok(())
+    }
+
+    /// 启动后台挖矿线程，线程数限制为可用 CPU 核心数
+    pub fn start(&self, threads: usize) -> Result<(), String> {
+        {
+            let mut status = self.status.lock().unwrap();
+            if status.running {
+                return Err("挖矿服务已在运行".to_string());
+            }
+            if status.coinbase_address.is_empty() {
+                return Err("请先设置出块奖励地址".to_string());
+            }
+            status.running = true;
+            status.threads = clamp_threads(threads);
+        }
+        self.stop.store(false, Ordering::Relaxed);
+
+        let blockchain = self.blockchain.clone();
+        let p2p_node = self.p2p_node.handle();
+        let data_file = self.data_file.clone();
+        let stop = self.stop.clone();
+        let status = self.status.clone();
+        thread::spawn(move || {
+            Self::run(&blockchain, &p2p_node, &data_file, &stop, &status);
+            status.lock().unwrap().running = false;
+            println!("🛑 挖矿服务已停止");
+        });
+
+        println!("⛏️ 挖矿服务已启动");
+        Ok(())
+    }
+
+    /// 停止挖矿服务（正在进行的挖矿会被取消）
+    pub fn stop(&self) -> Result<(), String> {
+        if !self.status.lock().unwrap().running {
+            return Err("挖矿服务未运行".to_string());
+        }
+        self.stop.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 挖矿循环
+    fn run(
+        blockchain: &Arc<Mutex<Blockchain>>,
+        p2p_node: &P2PNode,
+        data_file: &str,
+        stop: &AtomicBool,
+        status: &Mutex<MiningStatus>,
+    ) {
+        let mut last_error: Option<String> = None;
+        while !stop.load(Ordering::Relaxed) {
+            let (coinbase_address, threads) = {
+                let status = status.lock().unwrap();
+                (status.coinbase_address.clone(), status.threads)
+            };
+
+            match mine_block(blockchain, coinbase_address, threads, stop) {
+                Ok((block, stats)) => {
+                    last_error = None;
+                    {
+                        let mut status = status.lock().unwrap();
+                        status.blocks_mined += 1;
+                        status.last_block_height = Some(block.height);
+                        status.last_hashrate = stats.hashrate();
+                    }
+
+                    if let Err(e) = blockchain.lock().unwrap().save_to_file(data_file) {
+                        println!("❌ 保存区块链失败: {}", e);
+                    }
+                    if let Err(e) = p2p_node.broadcast_block(block) {
+                        println!("❌ 广播区块失败: {}", e);
+                    }
+                }
+                Err(e) => {
+                    // 同样的错误（如未轮到本节点出块）只提示一次
+                    if last_error.as_ref() != Some(&e) && !stop.load(Ordering::Relaxed) {
+                        println!("⚠️ 挖矿服务: {}", e);
+                    }
+                    last_error = Some(e);
+                    thread::sleep(IDLE_INTERVAL);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_spec::ChainSpec;
+
+    fn regtest() -> Arc<Mutex<Blockchain>> {
+        Arc::new(Mutex::new(Blockchain::from_spec(&ChainSpec::regtest())))
+    }
+
+    #[test]
+    fn solve_finds_nonce_meeting_target() {
+        let blockchain = regtest();
+        let template = blockchain.lock().unwrap().create_block_template("alice".to_string()).unwrap();
+        let (solved, stats) = solve(&template.block, 2, &blockchain, &AtomicBool::new(false));
+
+        let block = solved.unwrap();
+        assert!(block.header.meets_target());
+        assert_eq!(block.header.hash, block.calculate_hash());
+        assert_eq!(block.header.prev_hash, template.block.header.prev_hash);
+        assert_eq!(stats.threads, 2);
+        assert!(stats.hashes >= 1);
+    }
+
+    #[test]
+    fn solve_is_cancelled_by_stop_flag() {
+        let blockchain = regtest();
+        let mut template = blockchain.lock().unwrap().create_block_template("alice".to_string()).unwrap().block;
+        template.header.bits = 0x0300_0001; // 目标值极小，实际不可能挖出
+        let (solved, _) = solve(&template, 1, &blockchain, &AtomicBool::new(true));
+        assert!(solved.is_none());
+    }
+
+    #[test]
+    fn mined_block_is_accepted_by_other_nodes() {
+        let blockchain = regtest();
+        let mut other = Blockchain::from_spec(&ChainSpec::regtest());
+        for _ in 0..2 {
+            let (block, stats) = mine_block(&blockchain, "alice".to_string(), 2, &AtomicBool::new(false)).unwrap();
+            assert_eq!(stats.threads, 2);
+            other.accept_block(block).unwrap();
+        }
+        assert_eq!(other.get_length(), 3);
+        assert_eq!(other.get_balance("alice"), blockchain.lock().unwrap().get_balance("alice"));
+    }
+
+    /// 等待条件成立，最多约 5 秒
+    fn wait_until(condition: impl Fn() -> bool) -> bool {
+        (0..500).any(|_| {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+            false
+        })
+    }
+
+    #[test]
+    fn service_start_stop_and_status() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let blockchain = regtest();
+        let data_file = std::env::temp_dir().join(format!("miner_test_{}.json", std::process::id()));
+        let data_file = data_file.to_str().unwrap();
+        let p2p_node = P2PNode::new("127.0.0.1:0".parse().unwrap(), blockchain.clone(), runtime.handle().clone(), "");
+        let service = MiningService::new(blockchain.clone(), p2p_node, data_file);
+
+        assert!(!service.status().running);
+        assert!(service.stop().is_err());
+        assert!(service.start(1).is_err()); // 未设置奖励地址
+        assert!(service.set_coinbase_address(String::new()).is_err());
+        service.set_coinbase_address("alice".to_string()).unwrap();
+
+        // 请求的线程数限制为 CPU 核心数；交易池为空时也会持续出块
+        service.start(100_000).unwrap();
+        assert!(service.status().running);
+        assert_eq!(service.status().threads, default_threads());
+        assert!(service.start(1).is_err());
+        assert!(wait_until(|| service.status().blocks_mined >= 2));
+
+        service.stop().unwrap();
+        assert!(wait_until(|| !service.status().running));
+        assert!(service.stop().is_err());
+        let status = service.status();
+        let length = blockchain.lock().unwrap().get_length() as u64;
+        assert_eq!(status.last_block_height, Some(length - 1));
+        assert!(blockchain.lock().unwrap().is_chain_valid());
+        let _ = std::fs::remove_file(data_file);
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt;
+
+/// 行为分数达到该值时断开连接并封禁节点
+pub const BAN_THRESHOLD: u32 = 100;
+/// 自动封禁的时长（小时）
+pub const DEFAULT_BAN_HOURS: i64 = 24;
+
+/// 节点违反协议的行为类别，每类行为累加不同的分数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Misbehavior {
+    UndecodableMessage,  // 无法解码的消息帧或负载
+    OversizedMessage,    // 列表长度超过协议上限
+    InvalidBlock,        // 区块或区块头不依赖链状态的完整性检查失败（哈希、难度目标、签名、交易格式）
+    InvalidTransaction,  // 交易格式或签名无效
+    UnsolicitedResponse, // 与任何未完成请求都不对应的响应
+}
+
+impl Misbehavior {
+    /// 该类行为增加的分数
+    pub fn score(self) -> u32 {
+        match self {
+            Misbehavior::UndecodableMessage => 50,
+            Misbehavior::OversizedMessage => 20,
+            Misbehavior::InvalidBlock => 100,
+            Misbehavior::InvalidTransaction => 10,
+            Misbehavior::UnsolicitedResponse => 50,
+        }
+    }
+
+    /// 在当前行为分数上累加该类行为的分数，返回新分数和是否达到封禁阈值
+    pub fn apply(self, score: u32) -> (u32, bool) {
+        let score = score.saturating_add(self.score());
+        (score, score >= BAN_THRESHOLD)
+    }
+}
+
+impl fmt::Display for Misbehavior {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            Misbehavior::UndecodableMessage => "发送了无法解码的数据",
+            Misbehavior::OversizedMessage => "发送了超过协议上限的消息",
+            Misbehavior::InvalidBlock => "发送了无效区块",
+            Misbehavior::InvalidTransaction => "发送了无效交易",
+            Misbehavior::UnsolicitedResponse => "发送了未请求的响应",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_block_bans_immediately() {
+        assert_eq!(Misbehavior::InvalidBlock.apply(0), (100, true));
+    }
+
+    #[test]
+    fn minor_misbehavior_accumulates_to_ban() {
+        let mut score = 0;
+        for _ in 0..9 {
+            let (next, banned) = Misbehavior::InvalidTransaction.apply(score);
+            assert!(!banned);
+            score = next;
+        }
+        assert_eq!(Misbehavior::InvalidTransaction.apply(score), (100, true));
+
+        let (score, banned) = Misbehavior::UndecodableMessage.apply(0);
+        assert!(!banned);
+        assert!(Misbehavior::UndecodableMessage.apply(score).1);
+        assert!(!Misbehavior::OversizedMessage.apply(60).1);
+
+        let (score, banned) = Misbehavior::UnsolicitedResponse.apply(0);
+        assert!(!banned);
+        assert!(Misbehavior::UnsolicitedResponse.apply(score).1);
+    }
+
+    #[test]
+    fn score_saturates() {
+        assert_eq!(Misbehavior::InvalidBlock.apply(u32::MAX), (u32::MAX, true));
+    }
+}
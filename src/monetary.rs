@@ -0,0 +1,188 @@
+use crate::block::Block;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// 发行奖励的系统账户（作为交易发送者时表示新发行的代币）
+pub const ISSUER_ADDRESS: &str = "system";
+/// 销毁地址（发送到此地址的代币从流通中移除）
+pub const BURN_ADDRESS: &str = "burn";
+
+/// 创世分配
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenesisAllocation {
+    pub address: String,
+    pub amount: u64,
+}
+
+/// 默认 coinbase 成熟期（区块数）
+pub const DEFAULT_COINBASE_MATURITY: u64 = 10;
+
+fn default_coinbase_maturity() -> u64 {
+    DEFAULT_COINBASE_MATURITY
+}
+
+/// 货币政策 - 初始出块奖励、减半周期、供应上限、创世分配和 coinbase 成熟期
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonetaryPolicy {
+    pub initial_subsidy: u64,
+    pub halving_interval: u64, // 每隔多少个区块奖励减半
+    pub max_supply: u64,       // 总供应上限（含创世分配）
+    pub genesis_allocations: Vec<GenesisAllocation>,
+    #[serde(default = "default_coinbase_maturity")]
+    pub coinbase_maturity: u64, // coinbase 奖励需经过多少个区块才能花费
+}
+
+impl Default for MonetaryPolicy {
+    /// 与旧版本一致：奖励 100，系统账户创世分配 1000
+    fn default() -> Self {
+        Self::new(100, 10_000, vec![GenesisAllocation {
+            address: ISSUER_ADDRESS.to_string(),
+            amount: 1000,
+        }])
+    }
+}
+
+impl MonetaryPolicy {
+    /// 创建货币政策，供应上限为减半序列总和（约为初始奖励 × 减半周期 × 2）加上创世分配
+    pub fn new(initial_subsidy: u64, halving_interval: u64, genesis_allocations: Vec<GenesisAllocation>) -> Self {
+        let genesis_supply: u64 = genesis_allocations.iter().map(|allocation| allocation.amount).sum();
+        Self {
+            initial_subsidy,
+            halving_interval,
+            max_supply: genesis_supply.saturating_add(initial_subsidy.saturating_mul(halving_interval).saturating_mul(2)),
+            genesis_allocations,
+            coinbase_maturity: DEFAULT_COINBASE_MATURITY,
+        }
+    }
+
+    /// 创世分配总额
+    pub fn genesis_supply(&self) -> u64 {
+        self.genesis_allocations.iter().map(|allocation| allocation.amount).sum()
+    }
+
+    /// 指定高度已经历的减半次数
+    pub fn halvings(&self, height: u64) -> u64 {
+        height / self.halving_interval.max(1)
+    }
+
+    /// 指定高度的区块奖励：基础奖励按减半次数右移，并受剩余可发行量限制
+    pub fn subsidy(&self, base_subsidy: u64, height: u64, current_supply: u64) -> u64 {
+        let halvings = self.halvings(height);
+        let subsidy = if halvings >= 64 { 0 } else { base_subsidy >> halvings };
+        subsidy.min(self.max_supply.saturating_sub(current_supply))
+    }
+}
+
+/// 供应审计结果
+#[derive(Debug, Clone, Serialize)]
+pub struct SupplyAudit {
+    pub genesis_allocated: u64,
+    pub issued: u64,
+    pub burned: u64,
+    pub expected_supply: u64, // 创世分配 + 已发行 - 已销毁
+    pub actual_supply: u64,   // 账户余额总和
+    pub max_supply: u64,
+    pub balanced: bool,
+    pub within_cap: bool,
+}
+
+impl SupplyAudit {
+    /// 审计是否通过
+    pub fn is_ok(&self) -> bool {
+        self.balanced && self.within_cap
+    }
+}
+
+/// 统计区块的新发行量和销毁量
+///
+/// 系统账户转出的金额中，手续费部分来自交易发送者，不计入新发行；出块者未领取的手续费视为销毁
+pub fn block_issued_and_burned(block: &Block) -> (u64, u64) {
+    let minted: u64 = block.transactions.iter().filter(|tx| tx.sender == ISSUER_ADDRESS).map(|tx| tx.amount).sum();
+    let fees: u64 = block.transactions.iter().map(|tx| tx.fee).sum();
+    let burned: u64 = block.transactions
+        .iter()
+        .flat_map(|tx| tx.credits())
+        .filter(|(address, _)| *address == BURN_ADDRESS)
+        .map(|(_, amount)| amount)
+        .sum();
+    (minted.saturating_sub(fees), burned + fees.saturating_sub(minted))
+}
+
+/// 按区块顺序增量维护的发行统计和近期 coinbase 奖励索引（只保留成熟期内的区块），由链重放得到，不持久化
+#[derive(Debug, Clone, Default)]
+pub struct SupplyLedger {
+    pub issued: u64,
+    pub burned: u64,
+    recent_coinbase: VecDeque<(u64, Vec<(String, u64)>)>, // 高度 -> 该高度 coinbase 各输出
+}
+
+impl SupplyLedger {
+    /// 计入已连接到链上的区块，并丢弃在下一个高度已经成熟的 coinbase 奖励
+    pub fn connect(&mut self, block: &Block, maturity: u64) {
+        let (issued, burned) = block_issued_and_burned(block);
+        self.issued = self.issued.saturating_add(issued);
+        self.burned = self.burned.saturating_add(burned);
+
+        let outputs: Vec<(String, u64)> = block.transactions
+            .iter()
+            .filter(|tx| tx.is_coinbase())
+            .flat_map(|tx| tx.credits())
+            .map(|(address, amount)| (address.to_string(), amount))
+            .collect();
+        if !outputs.is_empty() {
+            self.recent_coinbase.push_back((block.height, outputs));
+        }
+
+        let next_height = block.height + 1;
+        while self.recent_coinbase.front().is_some_and(|(height, _)| next_height - height >= maturity) {
+            self.recent_coinbase.pop_front();
+        }
+    }
+
+    /// 地址在指定高度尚未成熟的 coinbase 奖励（高度 h 的奖励在高度 h + maturity 起才能花费）
+    pub fn immature(&self, address: &str, height: u64, maturity: u64) -> u64 {
+        self.recent_coinbase
+            .iter()
+            .filter(|(block_height, _)| *block_height < height && height - block_height < maturity)
+            .flat_map(|(_, outputs)| outputs)
+            .filter(|(output_address, _)| output_address == address)
+            .map(|(_, amount)| amount)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{CoinbaseOutput, Transaction};
+
+    fn coinbase_block(height: u64, address: &str, amount: u64) -> Block {
+        let output = CoinbaseOutput { address: address.to_string(), amount };
+        let coinbase = Transaction::new_coinbase(height, address.to_string(), vec![output]);
+        Block::new(String::new(), vec![coinbase], height, 1)
+    }
+
+    #[test]
+    fn coinbase_matures_after_maturity_blocks() {
+        let mut ledger = SupplyLedger::default();
+        for height in 1..=5 {
+            ledger.connect(&coinbase_block(height, "miner", 10), 3);
+        }
+
+        // 高度 6 时，高度 4、5 的奖励未成熟
+        assert_eq!(ledger.immature("miner", 6, 3), 20);
+        assert_eq!(ledger.immature("other", 6, 3), 0);
+        assert_eq!(ledger.issued, 50);
+    }
+
+    #[test]
+    fn index_keeps_only_immature_blocks() {
+        let mut ledger = SupplyLedger::default();
+        for height in 1..=100 {
+            ledger.connect(&coinbase_block(height, "miner", 10), 3);
+        }
+
+        assert_eq!(ledger.recent_coinbase.len(), 2);
+        assert_eq!(ledger.immature("miner", 101, 3), 20);
+    }
+}
@@ -1,147 +1,618 @@
+use crate::address_book::{AddressBook, MAX_ADDR_ITEMS};
 use crate::blockchain::Blockchain;
-use crate::block::{Block, Transaction};
+use crate::block::{Block, BlockHeader, Transaction};
+use crate::codec::{self, Frame, FrameDecoder};
+use crate::consensus::Evidence;
+use crate::finality::Vote;
+use crate::inventory::{InventoryItem, KnownInventory, MAX_INVENTORY_ITEMS};
+use crate::misbehavior::{Misbehavior, BAN_THRESHOLD, DEFAULT_BAN_HOURS};
+use chrono::Utc;
+use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::io::{Read, Write};
+use std::time::{Duration, Instant};
 use bincode::{serialize, deserialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, oneshot, watch, Notify};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::timeout;
+
+/// 当前协议版本
+pub const PROTOCOL_VERSION: u32 = 6;
+/// 可兼容的最低协议版本
+pub const MIN_PROTOCOL_VERSION: u32 = 6;
+/// 服务标志：保存完整区块链，可响应同步请求
+pub const SERVICE_FULL_CHAIN: u64 = 1;
+/// 服务标志：本节点持有验证者密钥，参与最终性投票
+pub const SERVICE_VALIDATOR: u64 = 1 << 1;
+
+/// 建立 TCP 连接的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// 握手超时时间
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// 单次写入的超时时间
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+/// 等待请求响应的超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// 心跳间隔
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// 检查并补充出站连接的间隔
+const DIAL_INTERVAL: Duration = Duration::from_secs(10);
+/// 自动维持的出站连接数
+const TARGET_OUTBOUND_CONNECTIONS: usize = 8;
+/// 不超过该数量的新地址会转发给其他节点
+const ADDR_RELAY_LIMIT: usize = 10;
+/// 转发新地址的节点数
+const ADDR_RELAY_PEERS: usize = 2;
+/// 超过该时间未收到任何消息的节点视为断开
+const PEER_TIMEOUT: Duration = Duration::from_secs(90);
+/// 停止节点时等待每个后台任务退出的时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// 每个连接发送队列的容量（消息数）
+const OUTGOING_QUEUE_SIZE: usize = 256;
+/// 单个 Headers 消息的最大区块头数
+const MAX_HEADERS: usize = 2000;
+/// GetHeaders 定位器的最大哈希数
+const MAX_LOCATOR_HASHES: usize = 64;
+/// 下载区块体时每批请求的区块数
+const BLOCK_BATCH_SIZE: u64 = 64;
+/// 每批区块的最大下载尝试次数
+const MAX_BATCH_ATTEMPTS: u32 = 3;
 
 /// P2P 消息类型
+///
+/// 请求和响应携带 request_id 用于匹配；request_id 为 0 的响应是主动推送，不对应任何请求
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Message {
-    /// 新交易
+    /// 交易数据（响应 GetData）
     NewTransaction(Transaction),
-    /// 新区块
+    /// 区块数据（响应 GetData）
     NewBlock(Block),
     /// 请求区块链
-    RequestChain,
+    RequestChain { request_id: u64 },
     /// 响应区块链
-    ResponseChain(Vec<Block>),
+    ResponseChain { request_id: u64, blocks: Vec<Block> },
     /// 请求区块链长度
-    RequestChainLength,
+    RequestChainLength { request_id: u64 },
     /// 响应区块链长度
-    ResponseChainLength(usize),
+    ResponseChainLength { request_id: u64, length: usize },
+    /// 按区块定位器请求其后的区块头（到 stop_hash 为止，为空时不限）
+    GetHeaders { request_id: u64, locator: Vec<String>, stop_hash: String },
+    /// 响应区块头，按高度顺序排列
+    Headers { request_id: u64, headers: Vec<BlockHeader> },
+    /// 请求对方已知的节点地址
+    GetAddr,
+    /// 节点地址列表（响应 GetAddr 或转发新得知的地址）
+    Addr(Vec<SocketAddr>),
     /// 请求特定区块范围
-    RequestBlocks { start: u64, end: u64 },
+    RequestBlocks { request_id: u64, start: u64, end: u64 },
     /// 响应区块范围
-    ResponseBlocks(Vec<Block>),
-    /// 节点发现
-    Discovery(SocketAddr),
-    /// 心跳消息
-    Ping,
-    /// 心跳响应
-    Pong,
+    ResponseBlocks { request_id: u64, blocks: Vec<Block> },
+    /// 握手：本节点版本信息，连接双方各发送一次
+    Version(VersionInfo),
+    /// 握手：确认对方版本信息兼容
+    Verack,
+    /// 公告本节点拥有的交易或区块
+    Inv(Vec<InventoryItem>),
+    /// 请求对方公告过的交易或区块
+    GetData(Vec<InventoryItem>),
+    /// 心跳消息（随机数用于匹配响应并计算往返延迟）
+    Ping { nonce: u64 },
+    /// 心跳响应，原样带回 Ping 的随机数
+    Pong { nonce: u64 },
     /// 节点状态同步
     SyncStatus {
         chain_length: usize,
         latest_hash: String,
         total_transactions: usize,
+        params_hash: String,
     },
     /// 同步完成确认
     SyncComplete,
+    /// 验证者作恶证据
+    Evidence(Evidence),
+    /// 最终性投票（预投票/预提交）
+    Vote(Vote),
 }
 
-/// P2P 节点
+impl Message {
+    /// 消息帧头中的消息类型
+    pub fn message_type(&self) -> u8 {
+        match self {
+            Message::NewTransaction(_) => 1,
+            Message::NewBlock(_) => 2,
+            Message::RequestChain { .. } => 3,
+            Message::ResponseChain { .. } => 4,
+            Message::RequestChainLength { .. } => 5,
+            Message::ResponseChainLength { .. } => 6,
+            Message::RequestBlocks { .. } => 7,
+            Message::ResponseBlocks { .. } => 8,
+            Message::Version(_) => 9,
+            Message::Ping { .. } => 10,
+            Message::Pong { .. } => 11,
+            Message::SyncStatus { .. } => 12,
+            Message::SyncComplete => 13,
+            Message::Evidence(_) => 14,
+            Message::Vote(_) => 15,
+            Message::Verack => 16,
+            Message::Inv(_) => 17,
+            Message::GetData(_) => 18,
+            Message::GetHeaders { .. } => 19,
+            Message::Headers { .. } => 20,
+            Message::GetAddr => 21,
+            Message::Addr(_) => 22,
+        }
+    }
+
+    /// 响应消息对应的请求 ID（非响应消息返回 None）
+    fn response_id(&self) -> Option<u64> {
+        match self {
+            Message::ResponseChain { request_id, .. }
+            | Message::ResponseChainLength { request_id, .. }
+            | Message::ResponseBlocks { request_id, .. }
+            | Message::Headers { request_id, .. } => Some(*request_id),
+            _ => None,
+        }
+    }
+}
+
+/// 握手时交换的节点版本信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionInfo {
+    pub protocol_version: u32,
+    pub chain_id: String,
+    pub genesis_hash: String,
+    pub params_hash: String, // 硬分叉计划哈希
+    pub best_height: u64,
+    pub listen_address: SocketAddr,
+    pub nonce: u64, // 节点随机数，用于识别连接到自身
+    pub services: u64,
+}
+
+/// 服务标志的可读描述
+pub fn describe_services(services: u64) -> String {
+    let names: Vec<&str> = [(SERVICE_FULL_CHAIN, "完整链"), (SERVICE_VALIDATOR, "验证者")]
+        .iter()
+        .filter(|(flag, _)| services & flag != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    if names.is_empty() { "无".to_string() } else { names.join(",") }
+}
+
+/// 连接的握手进度
+#[derive(Default)]
+struct Handshake {
+    dialed: Option<SocketAddr>,   // 出站连接拨号的地址
+    accepted: Option<SocketAddr>, // 入站连接的对端套接字地址
+    remote: Option<VersionInfo>,  // 已校验通过的对方版本信息
+    notify: Option<oneshot::Sender<Result<(), String>>>, // 出站连接的握手结果通知
+}
+
+/// 收到区块的处理结果
+#[derive(Debug, Clone, PartialEq)]
+enum BlockOutcome {
+    Accepted,             // 已追加到链顶
+    Orphan,               // 前序区块未知，需要同步
+    Invalid,              // 哈希、工作量证明、区块头签名或交易格式无效，在任何节点上都无效
+    Rejected,             // 不满足依赖本地链状态的规则（可能是双方状态不同），不处罚
+    Ignored,              // 已存在或不在链顶
+    DoubleSign(Evidence), // 与本地同高度区块构成双签
+}
+
+/// 与对等节点的长连接 - 读取任务解析消息，写入任务按顺序写出有界发送队列中的消息
+#[derive(Debug, Clone)]
+pub struct PeerConnection {
+    id: u64,
+    outgoing: mpsc::Sender<Vec<u8>>,
+    closed: Arc<Notify>, // 通知读取任务关闭连接
+}
+
+impl PeerConnection {
+    /// 为连接的写入端启动写任务
+    fn open(mut writer: OwnedWriteHalf, id: u64) -> (Self, JoinHandle<()>) {
+        let (outgoing, mut receiver) = mpsc::channel::<Vec<u8>>(OUTGOING_QUEUE_SIZE);
+        let closed = Arc::new(Notify::new());
+        let writer_closed = closed.clone();
+        let writer_task = tokio::spawn(async move {
+            while let Some(data) = receiver.recv().await {
+                match timeout(WRITE_TIMEOUT, writer.write_all(&data)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => {
+                        println!("❌ 写入连接失败: {}", e);
+                        break;
+                    }
+                    Err(_) => {
+                        println!("❌ 写入连接超时");
+                        break;
+                    }
+                }
+            }
+            let _ = timeout(WRITE_TIMEOUT, writer.shutdown()).await;
+            writer_closed.notify_one();
+        });
+
+        (Self { id, outgoing, closed }, writer_task)
+    }
+
+    /// 发送消息（放入发送队列，由写任务发送）
+    pub fn send(&self, message: &Message) -> Result<(), String> {
+        let data = P2PNode::encode_message(message).map_err(|e| e.to_string())?;
+        self.send_raw(data)
+    }
+
+    /// 发送已编码的消息帧；发送队列已满时不等待，直接返回错误
+    fn send_raw(&self, data: Vec<u8>) -> Result<(), String> {
+        self.outgoing.try_send(data).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => "发送队列已满".to_string(),
+            mpsc::error::TrySendError::Closed(_) => "连接已关闭".to_string(),
+        })
+    }
+
+    /// 关闭连接，读写任务随之退出
+    pub fn close(&self) {
+        self.closed.notify_one();
+    }
+}
+
+/// P2P 节点 - 网络任务运行在与 Web 服务器共享的 tokio 运行时中，公开方法可在普通线程中调用
 pub struct P2PNode {
     pub address: SocketAddr,
     pub blockchain: Arc<Mutex<Blockchain>>,
-    pub peers: Arc<Mutex<HashMap<SocketAddr, PeerInfo>>>,
-    pub listener: Option<TcpListener>,
+    pub peers: Arc<Mutex<HashMap<SocketAddr, PeerInfo>>>, // 出站连接以拨号地址为键，入站连接以对端套接字地址为键
+    runtime: Handle,
+    shutdown: Arc<watch::Sender<bool>>,     // 为 true 时节点未运行，后台任务退出
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>, // 后台任务，停止节点时等待其退出
+    pending_requests: Arc<Mutex<HashMap<u64, oneshot::Sender<Message>>>>, // 等待响应的请求
+    requested_inventory: Arc<Mutex<HashMap<InventoryItem, Instant>>>,      // 已发出 GetData 尚未收到的条目
+    pub address_book: Arc<Mutex<AddressBook>>, // 已知节点地址，用于自动维持出站连接
+    address_book_file: String,                 // 地址簿数据文件
+    next_id: Arc<AtomicU64>, // 连接 ID 和请求 ID 计数器
+    syncing: Arc<AtomicBool>, // 是否有区块同步正在进行
+    nonce: u64,               // 本节点随机数，握手时识别连接到自身
 }
 
 #[derive(Debug, Clone)]
 pub struct PeerInfo {
-    pub address: SocketAddr,
-    pub last_seen: std::time::Instant,
+    pub address: SocketAddr, // 连接的对端地址（出站为拨号地址，入站为接受连接时的套接字地址），封禁按此 IP
+    pub last_seen: Instant,
+    pub outbound: bool, // 是否由本节点主动发起连接
+    pub version: VersionInfo, // 握手时对方的版本信息
+    pub pending_ping: Option<(u64, Instant)>, // 尚未收到响应的 Ping（随机数, 发送时间）
+    pub latency: Option<Duration>,            // 最近一次 Ping 的往返延迟
+    pub known_inventory: KnownInventory,      // 对方已拥有或已被告知的交易和区块
+    pub misbehavior: u32,                     // 违反协议行为的累计分数
+    pub connection: PeerConnection,
+}
+
+impl PeerInfo {
+    /// 对方可被连接的地址：出站连接为拨号地址；入站连接为通告的监听地址，但只在与套接字 IP 一致时采信
+    fn dial_address(&self) -> Option<SocketAddr> {
+        if self.outbound {
+            Some(self.address)
+        } else if self.version.listen_address.ip() == self.address.ip() {
+            Some(self.version.listen_address)
+        } else {
+            None
+        }
+    }
+
+    /// 发送带新随机数的 Ping，记录发送时间
+    fn send_ping(&mut self) {
+        let nonce = rand::random();
+        match self.connection.send(&Message::Ping { nonce }) {
+            Ok(()) => self.pending_ping = Some((nonce, Instant::now())),
+            Err(e) => println!("❌ 向 {} 发送心跳失败: {}", self.address, e),
+        }
+    }
+
+    /// 处理 Pong：随机数与未响应的 Ping 一致时更新往返延迟
+    fn receive_pong(&mut self, nonce: u64) -> bool {
+        match self.pending_ping {
+            Some((expected, sent_at)) if expected == nonce => {
+                self.latency = Some(sent_at.elapsed());
+                self.pending_ping = None;
+                true
+            }
+            _ => false,
+        }
+    }
 }
 
 impl P2PNode {
-    /// 创建新节点
-    pub fn new(address: SocketAddr, blockchain: Arc<Mutex<Blockchain>>) -> Self {
+    /// 创建新节点，网络任务在 runtime 中运行；从 address_book_file 加载上次保存的节点地址
+    pub fn new(address: SocketAddr, blockchain: Arc<Mutex<Blockchain>>, runtime: Handle, address_book_file: &str) -> Self {
+        let address_book = match AddressBook::load_from_file(address_book_file) {
+            Ok(address_book) => {
+                println!("📇 从 {} 加载了 {} 个节点地址", address_book_file, address_book.len());
+                address_book
+            }
+            Err(_) if fs::metadata(address_book_file).is_err() => AddressBook::new(),
+            Err(e) => {
+                println!("⚠️ 加载节点地址文件 {} 失败: {}，使用空地址簿", address_book_file, e);
+                AddressBook::new()
+            }
+        };
+
         Self {
             address,
             blockchain,
             peers: Arc::new(Mutex::new(HashMap::new())),
-            listener: None,
+            runtime,
+            shutdown: Arc::new(watch::Sender::new(true)),
+            tasks: Arc::new(Mutex::new(Vec::new())),
+            pending_requests: Arc::new(Mutex::new(HashMap::new())),
+            requested_inventory: Arc::new(Mutex::new(HashMap::new())),
+            address_book: Arc::new(Mutex::new(address_book)),
+            address_book_file: address_book_file.to_string(),
+            next_id: Arc::new(AtomicU64::new(1)),
+            syncing: Arc::new(AtomicBool::new(false)),
+            nonce: rand::random(),
         }
     }
 
     /// 启动节点
     pub fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.is_running() {
+            return Err("P2P 节点已在运行".into());
+        }
         println!("🚀 启动 P2P 节点: {}", self.address);
 
         // 绑定监听器
-        let listener = TcpListener::bind(self.address)?;
-        self.listener = Some(listener.try_clone()?);
+        let listener = self.runtime.block_on(TcpListener::bind(self.address))?;
+        self.shutdown.send_replace(false);
 
         println!("✅ P2P 节点监听在: {}", self.address);
 
-        // 启动监听线程
-        let peers = self.peers.clone();
-        let blockchain = self.blockchain.clone();
-        let listener_clone = listener.try_clone()?;
+        // 启动监听任务
+        let node = self.handle();
+        self.spawn(async move {
+            node.listen_for_connections(listener).await;
+        });
 
-        thread::spawn(move || {
-            Self::listen_for_connections(listener_clone, peers, blockchain);
+        // 启动心跳任务
+        let node = self.handle();
+        self.spawn(async move {
+            node.heartbeat_loop().await;
         });
 
-        // 启动心跳线程
-        let peers_heartbeat = self.peers.clone();
-        thread::spawn(move || {
-            Self::heartbeat_loop(peers_heartbeat);
+        // 启动出站连接维护任务
+        let node = self.handle();
+        self.spawn(async move {
+            node.maintain_connections().await;
         });
 
         Ok(())
     }
 
-    /// 连接到其他节点
+    /// 节点是否正在运行
+    pub fn is_running(&self) -> bool {
+        !*self.shutdown.borrow()
+    }
+
+    /// 在运行时中启动后台任务，停止节点时等待其退出
+    fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let task = self.runtime.spawn(future);
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(task);
+    }
+
+    /// 连接到其他节点并完成握手，连接建立后保持并复用
     pub fn connect_to_peer(&self, peer_address: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
-        println!("🔗 连接到节点: {}", peer_address);
+        Ok(self.runtime.block_on(self.connect(peer_address))?)
+    }
 
-        match TcpStream::connect(peer_address) {
-                Ok(mut stream) => {
-                // 发送发现消息
-                let discovery_msg = Message::Discovery(self.address);
-                let data = serialize(&discovery_msg)?;
-                stream.write_all(&data)?;
-
-                // 添加到对等节点列表
-                self.peers.lock().unwrap().insert(peer_address, PeerInfo {
-                    address: peer_address,
-                    last_seen: std::time::Instant::now(),
-                });
+    /// 解析种子地址（host:port）并加入地址簿，返回加入的地址数
+    pub fn add_seeds(&self, seeds: &[String]) -> usize {
+        let mut address_book = self.address_book.lock().unwrap();
+        let mut added = 0;
+        for seed in seeds {
+            match seed.to_socket_addrs() {
+                Ok(addresses) => {
+                    for address in addresses.filter(|address| *address != self.address) {
+                        address_book.add_seed(address);
+                        added += 1;
+                    }
+                }
+                Err(e) => println!("⚠️ 无法解析种子地址 {}: {}", seed, e),
+            }
+        }
+        added
+    }
+
+    /// 建立出站连接并等待握手完成，结果记录到地址簿
+    async fn connect(&self, peer_address: SocketAddr) -> Result<(), String> {
+        let result = self.dial(peer_address).await;
+        // 双方同时连接时重复的连接会被关闭，此时已有连接不算失败；拒绝连接已封禁的节点也不算失败
+        let connected = Self::connected_to(&self.peers.lock().unwrap(), peer_address);
+        let mut address_book = self.address_book.lock().unwrap();
+        match result {
+            Ok(()) => address_book.mark_success(peer_address),
+            Err(_) if !self.is_running() || peer_address == self.address || connected || address_book.is_banned(peer_address.ip()) => {}
+            Err(_) => address_book.mark_failure(peer_address),
+        }
+        result
+    }
+
+    /// 建立 TCP 连接、发送版本信息并等待握手完成
+    async fn dial(&self, peer_address: SocketAddr) -> Result<(), String> {
+        if !self.is_running() {
+            return Err("P2P 节点未启动".to_string());
+        }
+        if peer_address == self.address {
+            return Err("不能连接到本节点".to_string());
+        }
+        if self.address_book.lock().unwrap().is_banned(peer_address.ip()) {
+            return Err(format!("节点 {} 已被封禁", peer_address));
+        }
+        if Self::connected_to(&self.peers.lock().unwrap(), peer_address) {
+            println!("ℹ️ 已与节点 {} 建立连接", peer_address);
+            return Ok(());
+        }
+
+        println!("🔗 连接到节点: {}", peer_address);
+        let stream = match timeout(CONNECT_TIMEOUT, TcpStream::connect(peer_address)).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => return Err(format!("连接 {} 失败: {}", peer_address, e)),
+            Err(_) => return Err(format!("连接 {} 超时", peer_address)),
+        };
+        let (reader, writer) = stream.into_split();
+        let (connection, writer_task) = PeerConnection::open(writer, self.next_id());
+        connection.send(&Message::Version(self.version_info()))?;
+
+        // 握手完成后由读取任务加入对等节点列表
+        let (notify, result) = oneshot::channel();
+        let handshake = Handshake { dialed: Some(peer_address), notify: Some(notify), ..Default::default() };
+        let node = self.handle();
+        let reader_connection = connection.clone();
+        self.spawn(async move {
+            node.handle_connection(reader, reader_connection, writer_task, handshake).await;
+        });
 
+        match timeout(HANDSHAKE_TIMEOUT, result).await {
+            Ok(Ok(Ok(()))) => {
                 println!("✅ 成功连接到节点: {}", peer_address);
                 Ok(())
             }
-            Err(e) => {
-                println!("❌ 连接失败 {}: {}", peer_address, e);
-                Err(e.into())
+            Ok(Ok(Err(e))) => Err(format!("与节点 {} 握手失败: {}", peer_address, e)),
+            Ok(Err(_)) => Err(format!("与节点 {} 的连接在握手完成前关闭", peer_address)),
+            Err(_) => {
+                connection.close();
+                Err(format!("与节点 {} 握手超时", peer_address))
             }
         }
     }
 
-    /// 广播交易
+    /// 本节点的版本信息
+    fn version_info(&self) -> VersionInfo {
+        let blockchain = self.blockchain.lock().unwrap();
+        let validator = if blockchain.validator_key.is_some() { SERVICE_VALIDATOR } else { 0 };
+        VersionInfo {
+            protocol_version: PROTOCOL_VERSION,
+            chain_id: blockchain.chain_params.chain_id.clone(),
+            genesis_hash: blockchain.chain[0].header.hash.clone(),
+            params_hash: blockchain.chain_params.schedule_hash(),
+            best_height: blockchain.get_length() as u64 - 1,
+            listen_address: self.address,
+            nonce: self.nonce,
+            services: SERVICE_FULL_CHAIN | validator,
+        }
+    }
+
+    /// 检查对方版本信息是否与本节点兼容
+    fn check_version(&self, version: &VersionInfo) -> Result<(), String> {
+        if version.nonce == self.nonce {
+            return Err("连接到了本节点".to_string());
+        }
+        if version.protocol_version < MIN_PROTOCOL_VERSION {
+            return Err(format!("协议版本 {} 过低 (最低 {})", version.protocol_version, MIN_PROTOCOL_VERSION));
+        }
+
+        let blockchain = self.blockchain.lock().unwrap();
+        if version.chain_id != blockchain.chain_params.chain_id {
+            return Err(format!("链标识不一致 (本地 {}, 远程 {})", blockchain.chain_params.chain_id, version.chain_id));
+        }
+        if version.genesis_hash != blockchain.chain[0].header.hash {
+            return Err(format!("创世区块不一致 (本地 {}, 远程 {})", blockchain.chain[0].header.hash, version.genesis_hash));
+        }
+        let params_hash = blockchain.chain_params.schedule_hash();
+        if version.params_hash != params_hash {
+            return Err(format!("硬分叉计划不一致 (本地 {}, 远程 {})", params_hash, version.params_hash));
+        }
+        Ok(())
+    }
+
+    /// 将交易加入本地交易池（已在池中则跳过）并向网络公告
     pub fn broadcast_transaction(&self, transaction: Transaction) -> Result<(), Box<dyn std::error::Error>> {
-        let message = Message::NewTransaction(transaction);
-        self.broadcast_message(message)
+        let id = transaction.txid();
+        {
+            let mut blockchain = self.blockchain.lock().unwrap();
+            if blockchain.pending_transaction(&id).is_none() {
+                blockchain.add_transaction(transaction)?;
+            }
+        }
+        self.announce(InventoryItem::Transaction(id), None);
+        Ok(())
     }
 
-    /// 广播区块
+    /// 将作恶证据以交易加入本地交易池并广播给其他节点（except 为证据的来源节点），
+    /// 证据由本节点验证者（未配置时为匿名）提交
+    pub fn broadcast_evidence(&self, evidence: Evidence, except: Option<SocketAddr>) -> Result<(), String> {
+        {
+            let mut blockchain = self.blockchain.lock().unwrap();
+            let reporter = blockchain.validator_key
+                .as_ref()
+                .map(|key| key.address.clone())
+                .unwrap_or_else(|| "anonymous".to_string());
+            blockchain.submit_evidence(reporter, evidence.clone())?;
+        }
+
+        let message = Message::Evidence(evidence);
+        let peers = self.peers.lock().unwrap();
+        for (peer_addr, peer_info) in peers.iter().filter(|(peer_addr, _)| Some(**peer_addr) != except) {
+            if let Err(e) = peer_info.connection.send(&message) {
+                println!("❌ 发送证据到 {} 失败: {}", peer_addr, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// 创建共享全部状态的句柄，供后台任务和服务使用
+    pub fn handle(&self) -> P2PNode {
+        Self {
+            address: self.address,
+            blockchain: self.blockchain.clone(),
+            peers: self.peers.clone(),
+            runtime: self.runtime.clone(),
+            shutdown: self.shutdown.clone(),
+            tasks: self.tasks.clone(),
+            pending_requests: self.pending_requests.clone(),
+            requested_inventory: self.requested_inventory.clone(),
+            address_book: self.address_book.clone(),
+            address_book_file: self.address_book_file.clone(),
+            next_id: self.next_id.clone(),
+            syncing: self.syncing.clone(),
+            nonce: self.nonce,
+        }
+    }
+
+    /// 向网络公告本地链上的区块
     pub fn broadcast_block(&self, block: Block) -> Result<(), Box<dyn std::error::Error>> {
-        let message = Message::NewBlock(block);
-        self.broadcast_message(message)
+        if self.blockchain.lock().unwrap().find_block(&block.header.hash).is_none() {
+            return Err("只能公告本地链上的区块".into());
+        }
+        self.announce(InventoryItem::Block(block.header.hash), None);
+        Ok(())
     }
 
-    /// 广播消息到所有对等节点
+    /// 本节点验证者对最新区块投票并广播
+    pub fn broadcast_finality_votes(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let votes = self.blockchain.lock().unwrap().prevote_latest();
+        let count = votes.len();
+        for vote in votes {
+            self.broadcast_message(Message::Vote(vote))?;
+        }
+        Ok(count)
+    }
+
+    /// 通过已建立的连接广播消息到所有对等节点
     fn broadcast_message(&self, message: Message) -> Result<(), Box<dyn std::error::Error>> {
-        let data = serialize(&message)?;
+        let data = Self::encode_message(&message)?;
         let peers = self.peers.lock().unwrap();
 
-        for (peer_addr, _) in peers.iter() {
-            if let Err(e) = self.send_to_peer(*peer_addr, &data) {
+        for (peer_addr, peer_info) in peers.iter() {
+            if let Err(e) = peer_info.connection.send_raw(data.clone()) {
                 println!("❌ 发送消息到 {} 失败: {}", peer_addr, e);
             }
         }
@@ -149,503 +620,1281 @@ impl P2PNode {
         Ok(())
     }
 
-    /// 发送消息到特定节点
-    fn send_to_peer(&self, peer_address: SocketAddr, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
-        let mut stream = TcpStream::connect(peer_address)?;
-        stream.write_all(data)?;
-        Ok(())
+    /// 向尚不知道该条目的节点公告库存（except 为条目的来源节点）
+    fn announce(&self, item: InventoryItem, except: Option<SocketAddr>) {
+        let mut peers = self.peers.lock().unwrap();
+        for (peer_addr, peer_info) in peers.iter_mut() {
+            if Some(*peer_addr) == except || !peer_info.known_inventory.insert(item.clone()) {
+                continue;
+            }
+            if let Err(e) = peer_info.connection.send(&Message::Inv(vec![item.clone()])) {
+                println!("❌ 向 {} 公告{}失败: {}", peer_addr, item, e);
+            }
+        }
     }
 
-    /// 监听连接
-    fn listen_for_connections(
-        listener: TcpListener,
-        peers: Arc<Mutex<HashMap<SocketAddr, PeerInfo>>>,
-        blockchain: Arc<Mutex<Blockchain>>,
-    ) {
-        println!("👂 开始监听 P2P 连接...");
+    /// 记录节点已拥有该条目，之后不再向其公告
+    fn mark_known(&self, peer_addr: SocketAddr, item: &InventoryItem) {
+        if let Some(peer_info) = self.peers.lock().unwrap().get_mut(&peer_addr) {
+            peer_info.known_inventory.insert(item.clone());
+        }
+    }
 
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    let peer_addr = stream.peer_addr().unwrap();
-                    println!("🔗 新连接来自: {}", peer_addr);
+    /// 本节点是否已拥有该条目
+    fn has_inventory(&self, item: &InventoryItem) -> bool {
+        let blockchain = self.blockchain.lock().unwrap();
+        match item {
+            InventoryItem::Transaction(id) => blockchain.has_transaction(id),
+            InventoryItem::Block(hash) => blockchain.find_block(hash).is_some(),
+        }
+    }
 
-                    // 添加到对等节点列表
-                    peers.lock().unwrap().insert(peer_addr, PeerInfo {
-                        address: peer_addr,
-                        last_seen: std::time::Instant::now(),
-                    });
+    /// 处理库存公告：向公告者请求本节点没有且未向其他节点请求过的条目
+    fn handle_inv(&self, peer_addr: SocketAddr, items: Vec<InventoryItem>, connection: &PeerConnection) -> Result<(), String> {
+        for item in &items {
+            self.mark_known(peer_addr, item);
+        }
 
-                    // 处理消息
-                    let blockchain_clone = blockchain.clone();
-                    let peers_clone = peers.clone();
+        let wanted: Vec<InventoryItem> = {
+            let mut requested = self.requested_inventory.lock().unwrap();
+            requested.retain(|_, requested_at| requested_at.elapsed() < REQUEST_TIMEOUT);
+            items
+                .into_iter()
+                .filter(|item| !requested.contains_key(item) && !self.has_inventory(item))
+                .collect()
+        };
+        if wanted.is_empty() {
+            return Ok(());
+        }
 
-                    thread::spawn(move || {
-                        Self::handle_connection(stream, blockchain_clone, peers_clone);
-                    });
+        let mut requested = self.requested_inventory.lock().unwrap();
+        for item in &wanted {
+            requested.insert(item.clone(), Instant::now());
+        }
+        connection.send(&Message::GetData(wanted))
+    }
+
+    /// 处理数据请求：发送交易池中的交易或链上的区块
+    fn handle_get_data(&self, peer_addr: SocketAddr, items: Vec<InventoryItem>, connection: &PeerConnection) -> Result<(), String> {
+        for item in items {
+            let message = {
+                let blockchain = self.blockchain.lock().unwrap();
+                match &item {
+                    InventoryItem::Transaction(id) => blockchain.pending_transaction(id).cloned().map(Message::NewTransaction),
+                    InventoryItem::Block(hash) => blockchain.find_block(hash).cloned().map(Message::NewBlock),
                 }
-                Err(e) => {
-                    println!("❌ 连接错误: {}", e);
+            };
+            match message {
+                Some(message) => {
+                    connection.send(&message)?;
+                    self.mark_known(peer_addr, &item);
                 }
+                None => println!("ℹ️ 节点 {} 请求的{}不存在", peer_addr, item),
             }
         }
+        Ok(())
     }
 
-    /// 处理连接
-    fn handle_connection(
-        mut stream: TcpStream,
-        blockchain: Arc<Mutex<Blockchain>>,
-        peers: Arc<Mutex<HashMap<SocketAddr, PeerInfo>>>,
-    ) {
-        let peer_addr = stream.peer_addr().unwrap();
-        let mut buffer = [0; 4096]; // 增加缓冲区大小以支持更大的消息
+    /// 收到条目数据后标记对方已拥有，并清除请求记录
+    fn receive_inventory(&self, peer_addr: SocketAddr, item: &InventoryItem) {
+        self.mark_known(peer_addr, item);
+        self.requested_inventory.lock().unwrap().remove(item);
+    }
+
+    /// 获取到指定节点的连接
+    fn connection(&self, peer_address: SocketAddr) -> Result<PeerConnection, String> {
+        self.peers
+            .lock()
+            .unwrap()
+            .get(&peer_address)
+            .map(|peer_info| peer_info.connection.clone())
+            .ok_or_else(|| format!("未连接到节点 {}", peer_address))
+    }
+
+    /// 向节点发送请求并等待对应 request_id 的响应
+    async fn request<F>(&self, peer_address: SocketAddr, build: F) -> Result<Message, String>
+    where
+        F: FnOnce(u64) -> Message,
+    {
+        let connection = self.connection(peer_address)?;
+        let request_id = self.next_id();
+        let (sender, receiver) = oneshot::channel();
+        self.pending_requests.lock().unwrap().insert(request_id, sender);
+
+        let result = match connection.send(&build(request_id)) {
+            Ok(()) => match timeout(REQUEST_TIMEOUT, receiver).await {
+                Ok(Ok(message)) => Ok(message),
+                Ok(Err(_)) => Err(format!("与节点 {} 的连接已关闭", peer_address)),
+                Err(_) => Err(format!("等待节点 {} 响应超时", peer_address)),
+            },
+            Err(e) => Err(e),
+        };
+        self.pending_requests.lock().unwrap().remove(&request_id);
+        result
+    }
+
+    /// 分配新的连接 ID 或请求 ID
+    fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// 监听连接，直到节点停止
+    async fn listen_for_connections(&self, listener: TcpListener) {
+        println!("👂 开始监听 P2P 连接...");
+        let mut shutdown = self.shutdown.subscribe();
 
         loop {
-            match stream.read(&mut buffer) {
-                Ok(size) if size > 0 => {
-                    let data = &buffer[..size];
-                    match deserialize::<Message>(data) {
-                        Ok(message) => {
-                            if let Err(e) = Self::process_message(message, &blockchain, &peers, &mut stream, peer_addr) {
-                                println!("❌ 处理消息失败: {}", e);
+            let (stream, remote_addr) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        println!("❌ 连接错误: {}", e);
+                        continue;
+                    }
+                },
+                _ = shutdown.wait_for(|stopped| *stopped) => break,
+            };
+            if self.address_book.lock().unwrap().is_banned(remote_addr.ip()) {
+                println!("🚫 拒绝来自已封禁地址 {} 的连接", remote_addr);
+                continue;
+            }
+            println!("🔗 新连接来自: {}", remote_addr);
+
+            // 握手完成后才以对端套接字地址加入对等节点列表；对方通告的监听地址只用于地址簿
+            let (reader, writer) = stream.into_split();
+            let (connection, writer_task) = PeerConnection::open(writer, self.next_id());
+            let handshake = Handshake { accepted: Some(remote_addr), ..Default::default() };
+            let node = self.handle();
+            self.spawn(async move {
+                node.handle_connection(reader, connection, writer_task, handshake).await;
+            });
+        }
+
+        println!("👂 停止监听 P2P 连接");
+    }
+
+    /// 将消息编码为带帧头的字节流
+    fn encode_message(message: &Message) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let payload = serialize(message)?;
+        Ok(codec::encode_frame(message.message_type(), &payload)?)
+    }
+
+    /// 从消息帧解码消息，并核对帧头中的消息类型
+    fn decode_message(frame: Frame) -> Result<Message, String> {
+        let message: Message = deserialize(&frame.payload).map_err(|e| format!("反序列化消息失败: {}", e))?;
+        if message.message_type() != frame.message_type {
+            return Err(format!("消息类型不一致: 帧头 {}, 负载 {}", frame.message_type, message.message_type()));
+        }
+        Ok(message)
+    }
+
+    /// 连接读取循环：按帧切分读取到的数据，一次读取可能包含半个或多个消息
+    ///
+    /// 握手完成前只处理 Version/Verack，握手失败或超时即断开连接
+    async fn handle_connection(
+        &self,
+        mut reader: OwnedReadHalf,
+        connection: PeerConnection,
+        writer_task: JoinHandle<()>,
+        mut handshake: Handshake,
+    ) {
+        let remote_addr = reader.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+        let mut shutdown = self.shutdown.subscribe();
+        let mut buffer = vec![0; 4096];
+        let mut decoder = FrameDecoder::new();
+        let mut peer_address: Option<SocketAddr> = None; // 握手完成后对方在对等节点列表中的地址
+
+        'read: loop {
+            // 握手阶段超时较短；握手后对方每个心跳周期都会发送 Ping，长时间无数据视为断开
+            let read_timeout = if peer_address.is_some() { PEER_TIMEOUT } else { HANDSHAKE_TIMEOUT };
+            let size = tokio::select! {
+                read = timeout(read_timeout, reader.read(&mut buffer)) => match read {
+                    Ok(Ok(0)) => break, // 连接关闭
+                    Ok(Ok(size)) => size,
+                    Ok(Err(e)) => {
+                        println!("❌ 读取消息失败: {}", e);
+                        break;
+                    }
+                    Err(_) => {
+                        println!("⏱️ 读取来自 {} 的消息超时，断开连接", remote_addr);
+                        break;
+                    }
+                },
+                _ = connection.closed.notified() => break,
+                _ = shutdown.wait_for(|stopped| *stopped) => break,
+            };
+            decoder.extend(&buffer[..size]);
+
+            loop {
+                let frame = match decoder.next_frame() {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => break,
+                    Err(e) => {
+                        // 帧格式错误后无法重新对齐，断开连接
+                        println!("❌ 来自 {} 的消息帧无效，断开连接: {}", remote_addr, e);
+                        if let Some(peer_addr) = peer_address {
+                            self.misbehaving(peer_addr, Misbehavior::UndecodableMessage);
+                        }
+                        break 'read;
+                    }
+                };
+                let message = match Self::decode_message(frame) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        println!("❌ 来自 {} 的消息无法解码: {}", remote_addr, e);
+                        match peer_address {
+                            Some(peer_addr) => {
+                                self.misbehaving(peer_addr, Misbehavior::UndecodableMessage);
+                                continue;
                             }
+                            None => break 'read, // 握手阶段只接受 Version/Verack
                         }
+                    }
+                };
+
+                let Some(peer_addr) = peer_address else {
+                    match self.handle_handshake(&connection, &mut handshake, message) {
+                        Ok(address) => peer_address = address,
                         Err(e) => {
-                            println!("❌ 反序列化消息失败: {}", e);
+                            println!("🚫 与 {} 握手失败，断开连接: {}", remote_addr, e);
+                            if let Some(notify) = handshake.notify.take() {
+                                let _ = notify.send(Err(e));
+                            }
+                            break 'read;
                         }
                     }
+                    continue;
+                };
+                if let Some(peer_info) = self.peers.lock().unwrap().get_mut(&peer_addr)
+                    && peer_info.connection.id == connection.id
+                {
+                    peer_info.last_seen = Instant::now();
                 }
-                Ok(_) => break, // 连接关闭
-                Err(e) => {
-                    println!("❌ 读取消息失败: {}", e);
-                    break;
+
+                if let Err(e) = self.process_message(message, &connection, peer_addr) {
+                    println!("❌ 处理消息失败: {}", e);
                 }
             }
         }
+
+        if let Some(notify) = handshake.notify.take() {
+            let _ = notify.send(Err("连接在握手完成前关闭".to_string()));
+        }
+        // 仅移除属于本连接的记录（同一地址可能已建立新连接）
+        if let Some(peer_addr) = peer_address {
+            self.remove_peer(peer_addr, connection.id);
+        }
+        writer_task.abort();
+    }
+
+    /// 处理握手消息，握手完成时加入对等节点列表并返回对方地址
+    ///
+    /// 双方各发送 Version，收到兼容的 Version 后回复 Verack；入站连接在收到对方 Version 后才发送自己的 Version
+    fn handle_handshake(
+        &self,
+        connection: &PeerConnection,
+        handshake: &mut Handshake,
+        message: Message,
+    ) -> Result<Option<SocketAddr>, String> {
+        match message {
+            Message::Version(version) => {
+                if handshake.remote.is_some() {
+                    return Err("重复的版本消息".to_string());
+                }
+                self.check_version(&version)?;
+                if let Some(accepted) = handshake.accepted {
+                    // 通告的监听地址与套接字 IP 一致时才据此识别与同一节点的重复连接
+                    if version.listen_address.ip() == accepted.ip()
+                        && Self::connected_to(&self.peers.lock().unwrap(), version.listen_address)
+                    {
+                        return Err(format!("已与节点 {} 建立连接", version.listen_address));
+                    }
+                    connection.send(&Message::Version(self.version_info()))?;
+                }
+                connection.send(&Message::Verack)?;
+                handshake.remote = Some(version);
+                Ok(None)
+            }
+            Message::Verack => {
+                let version = handshake.remote.clone().ok_or("在版本消息之前收到 Verack")?;
+                let address = handshake.dialed.or(handshake.accepted).ok_or("连接的对端地址未知")?;
+                {
+                    let mut peers = self.peers.lock().unwrap();
+                    if peers.contains_key(&address) || Self::connected_to(&peers, address) {
+                        return Err(format!("已与节点 {} 建立连接", address));
+                    }
+                    let mut peer_info = PeerInfo {
+                        address,
+                        last_seen: Instant::now(),
+                        outbound: handshake.dialed.is_some(),
+                        version: version.clone(),
+                        pending_ping: None,
+                        latency: None,
+                        known_inventory: KnownInventory::default(),
+                        misbehavior: 0,
+                        connection: connection.clone(),
+                    };
+                    // 立即测量一次延迟
+                    peer_info.send_ping();
+                    peers.insert(address, peer_info);
+                }
+
+                println!("🤝 与节点 {} 握手完成: 协议版本 {}, 高度 {}, 服务 {}",
+                         address, version.protocol_version, version.best_height, describe_services(version.services));
+
+                // 出站连接向对方索取地址；入站节点通告的监听地址是新地址时转发给其他节点
+                if handshake.dialed.is_some() {
+                    connection.send(&Message::GetAddr)?;
+                } else if self.address_book.lock().unwrap().add(version.listen_address) {
+                    self.relay_addresses(vec![version.listen_address], address);
+                }
+                if let Some(notify) = handshake.notify.take() {
+                    let _ = notify.send(Ok(()));
+                }
+                Ok(Some(address))
+            }
+            other => Err(format!("握手完成前收到消息类型 {}", other.message_type())),
+        }
     }
 
     /// 处理接收到的消息
     fn process_message(
+        &self,
         message: Message,
-        blockchain: &Arc<Mutex<Blockchain>>,
-        peers: &Arc<Mutex<HashMap<SocketAddr, PeerInfo>>>,
-        stream: &mut TcpStream,
+        connection: &PeerConnection,
         peer_addr: SocketAddr,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        // 响应交给等待中的请求
+        if let Some(request_id) = message.response_id()
+            && let Some(waiter) = self.pending_requests.lock().unwrap().remove(&request_id)
+        {
+            let _ = waiter.send(message);
+            return Ok(());
+        }
+
         match message {
+            Message::Inv(items) | Message::GetData(items) if items.len() > MAX_INVENTORY_ITEMS => {
+                println!("⚠️ 节点 {} 的库存消息包含 {} 个条目，超过上限 {}，忽略", peer_addr, items.len(), MAX_INVENTORY_ITEMS);
+                self.misbehaving(peer_addr, Misbehavior::OversizedMessage);
+            }
+            Message::Inv(items) => {
+                self.handle_inv(peer_addr, items, connection)?;
+            }
+            Message::GetData(items) => {
+                self.handle_get_data(peer_addr, items, connection)?;
+            }
             Message::NewTransaction(transaction) => {
-                println!("📦 收到新交易: {:?}", transaction.id);
-                let mut blockchain = blockchain.lock().unwrap();
-                if let Err(e) = blockchain.add_transaction(transaction) {
-                    println!("❌ 添加交易失败: {}", e);
+                // 库存 ID 在本地按交易内容计算，不信任来源节点声明的 ID
+                let id = transaction.txid();
+                if transaction.id != id {
+                    println!("❌ 交易 {} 的 ID 与内容不符", transaction.id);
+                    self.misbehaving(peer_addr, Misbehavior::InvalidTransaction);
+                    return Ok(());
+                }
+                let item = InventoryItem::Transaction(id);
+                self.receive_inventory(peer_addr, &item);
+                if self.has_inventory(&item) {
+                    return Ok(());
+                }
+
+                println!("📦 收到新交易: {}", item);
+                if let Err(e) = transaction.check_format() {
+                    println!("❌ 交易无效: {}", e);
+                    self.misbehaving(peer_addr, Misbehavior::InvalidTransaction);
+                    return Ok(());
+                }
+                let result = self.blockchain.lock().unwrap().add_pending_transaction(transaction);
+                match result {
+                    Ok(()) => self.announce(item, Some(peer_addr)),
+                    Err(e) => println!("❌ 添加交易失败: {}", e),
                 }
             }
             Message::NewBlock(block) => {
+                let item = InventoryItem::Block(block.header.hash.clone());
+                self.receive_inventory(peer_addr, &item);
+
                 println!("🧱 收到新区块: {}", block.header.hash);
-                let mut blockchain = blockchain.lock().unwrap();
-                Self::handle_new_block(&mut blockchain, block);
+                let (outcome, votes) = {
+                    let mut blockchain = self.blockchain.lock().unwrap();
+                    let outcome = Self::handle_new_block(&mut blockchain, block);
+                    (outcome, blockchain.prevote_latest())
+                };
+                match outcome {
+                    BlockOutcome::Accepted => self.announce(item, Some(peer_addr)),
+                    BlockOutcome::Orphan => {
+                        // 缺少前序区块，向来源节点同步
+                        let node = self.handle();
+                        self.spawn(async move {
+                            if let Err(e) = node.sync_with_peer(peer_addr).await {
+                                println!("❌ 与节点 {} 同步失败: {}", peer_addr, e);
+                            }
+                        });
+                    }
+                    BlockOutcome::Invalid => self.misbehaving(peer_addr, Misbehavior::InvalidBlock),
+                    BlockOutcome::Rejected | BlockOutcome::Ignored => {}
+                    BlockOutcome::DoubleSign(evidence) => {
+                        if let Err(e) = self.broadcast_evidence(evidence, None) {
+                            println!("❌ 提交双签证据失败: {}", e);
+                        }
+                    }
+                }
+                for vote in votes {
+                    self.broadcast_message(Message::Vote(vote))?;
+                }
             }
-            Message::RequestChain => {
+            Message::RequestChain { request_id } => {
                 println!("📋 收到完整区块链请求");
-                Self::handle_chain_request(blockchain, stream, peer_addr);
+                let blocks = self.blockchain.lock().unwrap().chain.clone();
+                let length = blocks.len();
+                connection.send(&Message::ResponseChain { request_id, blocks })?;
+                println!("📤 发送区块链响应到 {}，长度: {}", peer_addr, length);
             }
-            Message::RequestChainLength => {
+            Message::RequestChainLength { request_id } => {
                 println!("📏 收到区块链长度请求");
-                Self::handle_chain_length_request(blockchain, stream, peer_addr);
+                let length = self.blockchain.lock().unwrap().get_length();
+                connection.send(&Message::ResponseChainLength { request_id, length })?;
+                println!("📤 发送链长度响应到 {}: {}", peer_addr, length);
+            }
+            Message::GetHeaders { locator, .. } if locator.len() > MAX_LOCATOR_HASHES => {
+                println!("⚠️ 节点 {} 的区块定位器包含 {} 个哈希，超过上限 {}，忽略", peer_addr, locator.len(), MAX_LOCATOR_HASHES);
+                self.misbehaving(peer_addr, Misbehavior::OversizedMessage);
+            }
+            Message::GetHeaders { request_id, locator, stop_hash } => {
+                let headers = self.blockchain.lock().unwrap().headers_after(&locator, &stop_hash, MAX_HEADERS);
+                let count = headers.len();
+                connection.send(&Message::Headers { request_id, headers })?;
+                println!("📤 发送 {} 个区块头到 {}", count, peer_addr);
+            }
+            Message::Headers { headers, .. } => {
+                println!("⚠️ 收到节点 {} 未请求的 {} 个区块头，忽略", peer_addr, headers.len());
+            }
+            Message::GetAddr => {
+                let addresses: Vec<SocketAddr> = self.address_book
+                    .lock()
+                    .unwrap()
+                    .sample(MAX_ADDR_ITEMS)
+                    .into_iter()
+                    .filter(|address| *address != peer_addr)
+                    .collect();
+                println!("📇 向节点 {} 发送 {} 个地址", peer_addr, addresses.len());
+                connection.send(&Message::Addr(addresses))?;
+            }
+            Message::Addr(addresses) if addresses.len() > MAX_ADDR_ITEMS => {
+                println!("⚠️ 节点 {} 的地址消息包含 {} 个地址，超过上限 {}，忽略", peer_addr, addresses.len(), MAX_ADDR_ITEMS);
+                self.misbehaving(peer_addr, Misbehavior::OversizedMessage);
             }
-            Message::RequestBlocks { start, end } => {
+            Message::Addr(addresses) => {
+                let total = addresses.len();
+                let new_addresses: Vec<SocketAddr> = {
+                    let mut address_book = self.address_book.lock().unwrap();
+                    addresses
+                        .into_iter()
+                        .filter(|address| *address != self.address && address_book.add(*address))
+                        .collect()
+                };
+                println!("📇 从节点 {} 收到 {} 个地址，新增 {} 个", peer_addr, total, new_addresses.len());
+                if !new_addresses.is_empty() && total <= ADDR_RELAY_LIMIT {
+                    self.relay_addresses(new_addresses, peer_addr);
+                }
+            }
+            Message::RequestBlocks { request_id, start, end } => {
                 println!("📦 收到区块范围请求: {}-{}", start, end);
-                Self::handle_blocks_request(blockchain, start, end, stream, peer_addr);
+                let blocks = self.blocks_in_range(start, end);
+                let count = blocks.len();
+                connection.send(&Message::ResponseBlocks { request_id, blocks })?;
+                println!("📤 发送区块范围响应到 {}: {}-{} ({} 区块)", peer_addr, start, end, count);
             }
-            Message::ResponseChain(chain) => {
-                println!("📋 收到完整区块链响应，长度: {}", chain.len());
-                let mut blockchain = blockchain.lock().unwrap();
-                Self::handle_chain_response(&mut blockchain, chain);
+            Message::ResponseChain { request_id, blocks } => {
+                // 本节点通过区块头优先同步更新链，不接受未请求的完整链
+                println!("⚠️ 节点 {} 发送了未请求的完整区块链 (请求 {}，长度 {})，忽略", peer_addr, request_id, blocks.len());
+                self.misbehaving(peer_addr, Misbehavior::UnsolicitedResponse);
             }
-            Message::ResponseChainLength(length) => {
+            Message::ResponseChainLength { length, .. } => {
                 println!("📏 收到区块链长度响应: {}", length);
-                Self::handle_chain_length_response(blockchain, length, stream, peer_addr);
             }
-            Message::ResponseBlocks(blocks) => {
-                println!("📦 收到区块响应，数量: {}", blocks.len());
-                let mut blockchain = blockchain.lock().unwrap();
-                Self::handle_blocks_response(&mut blockchain, blocks);
+            Message::ResponseBlocks { blocks, .. } => {
+                // 区块范围只在同步时按请求下载；没有等待者的响应（如超时后才到达）直接忽略
+                println!("ℹ️ 忽略节点 {} 未请求的区块响应 ({} 区块)", peer_addr, blocks.len());
             }
-            Message::SyncStatus { chain_length, latest_hash, total_transactions } => {
+            Message::SyncStatus { chain_length, latest_hash, total_transactions, params_hash } => {
                 println!("🔄 收到同步状态: 链长度={}, 最新哈希={}, 总交易={}",
                          chain_length, latest_hash, total_transactions);
-                Self::handle_sync_status(blockchain, chain_length, latest_hash, total_transactions);
+                if !self.check_params_hash(peer_addr, &params_hash) {
+                    return Ok(());
+                }
+                self.update_best_height(peer_addr, chain_length.saturating_sub(1) as u64);
+                if self.handle_sync_status(chain_length, latest_hash, total_transactions) {
+                    // 同步需要等待响应，不能阻塞本连接的读取
+                    let node = self.handle();
+                    self.spawn(async move {
+                        if let Err(e) = node.sync_with_peer(peer_addr).await {
+                            println!("❌ 与节点 {} 同步失败: {}", peer_addr, e);
+                        }
+                    });
+                }
             }
             Message::SyncComplete => {
                 println!("✅ 收到同步完成确认");
             }
-            Message::Discovery(peer_addr) => {
-                println!("🔍 发现新节点: {}", peer_addr);
-                peers.lock().unwrap().insert(peer_addr, PeerInfo {
-                    address: peer_addr,
-                    last_seen: std::time::Instant::now(),
-                });
+            Message::Evidence(evidence) => {
+                println!("⚖️ 收到验证者 {} 的作恶证据", evidence.validator());
+                // 已在交易池或已处理的证据不再转发
+                if let Err(e) = self.broadcast_evidence(evidence, Some(peer_addr)) {
+                    println!("ℹ️ 未转发证据: {}", e);
+                }
+            }
+            Message::Vote(vote) => {
+                println!("🗳️ 收到 {} 对高度 {} 的 {:?}", vote.validator, vote.height, vote.vote_type);
+                let result = self.blockchain.lock().unwrap().process_vote(vote);
+                match result {
+                    Ok(votes) => {
+                        for vote in votes {
+                            self.broadcast_message(Message::Vote(vote))?;
+                        }
+                    }
+                    Err(e) => println!("❌ 投票无效: {}", e),
+                }
+            }
+            Message::Version(_) | Message::Verack => {
+                println!("⚠️ 节点 {} 在握手完成后重复握手，忽略", peer_addr);
             }
-            Message::Ping => {
-                println!("🏓 收到 Ping");
-                // 响应 Pong - 这里需要发送响应
+            Message::Ping { nonce } => {
+                connection.send(&Message::Pong { nonce })?;
             }
-            Message::Pong => {
-                println!("🏓 收到 Pong");
+            Message::Pong { nonce } => {
+                let matched = self.peers
+                    .lock()
+                    .unwrap()
+                    .get_mut(&peer_addr)
+                    .is_some_and(|peer_info| peer_info.receive_pong(nonce));
+                if !matched {
+                    println!("⚠️ 节点 {} 的 Pong 随机数不匹配，忽略", peer_addr);
+                }
             }
         }
 
         Ok(())
     }
 
-    /// 心跳循环
-    fn heartbeat_loop(peers: Arc<Mutex<HashMap<SocketAddr, PeerInfo>>>) {
-        loop {
-            thread::sleep(std::time::Duration::from_secs(30));
+    /// 检查对方的硬分叉计划哈希，不一致时移出对等节点列表并断开连接
+    fn check_params_hash(&self, peer_addr: SocketAddr, params_hash: &str) -> bool {
+        let local_hash = self.blockchain.lock().unwrap().chain_params.schedule_hash();
+        if local_hash == params_hash {
+            return true;
+        }
+
+        println!("🚫 节点 {} 的硬分叉计划不一致 (本地 {}, 远程 {})，拒绝连接", peer_addr, local_hash, params_hash);
+        if let Some(peer_info) = self.peers.lock().unwrap().remove(&peer_addr) {
+            peer_info.connection.close();
+        }
+        false
+    }
+
+    /// 移除对等节点并关闭连接（仅当记录的连接就是 connection_id 时）
+    fn remove_peer(&self, peer_addr: SocketAddr, connection_id: u64) {
+        let mut peers = self.peers.lock().unwrap();
+        if peers.get(&peer_addr).is_some_and(|peer_info| peer_info.connection.id == connection_id)
+            && let Some(peer_info) = peers.remove(&peer_addr)
+        {
+            peer_info.connection.close();
+            println!("👋 节点 {} 已断开", peer_addr);
+        }
+    }
+
+    /// 记录节点违反协议的行为，累计分数达到 BAN_THRESHOLD 时断开连接并封禁
+    fn misbehaving(&self, peer_addr: SocketAddr, misbehavior: Misbehavior) {
+        let (score, ban) = {
+            let mut peers = self.peers.lock().unwrap();
+            let Some(peer_info) = peers.get_mut(&peer_addr) else {
+                return;
+            };
+            let (score, ban) = misbehavior.apply(peer_info.misbehavior);
+            peer_info.misbehavior = score;
+            (score, ban)
+        };
+        println!("⚠️ 节点 {} {}，行为分数 {}/{}", peer_addr, misbehavior, score, BAN_THRESHOLD);
+        if ban {
+            self.ban_peer(peer_addr, chrono::Duration::hours(DEFAULT_BAN_HOURS));
+        }
+    }
+
+    /// 封禁节点的 IP：断开该 IP 的所有连接，封禁期间拒绝其入站连接且不主动连接
+    pub fn ban_peer(&self, peer_address: SocketAddr, duration: chrono::Duration) {
+        let until = Utc::now() + duration;
+        self.address_book.lock().unwrap().ban(peer_address, until);
 
-            let mut peers = peers.lock().unwrap();
-            let mut to_remove = Vec::new();
+        let banned: Vec<PeerInfo> = {
+            let mut peers = self.peers.lock().unwrap();
+            let addresses: Vec<SocketAddr> = peers.keys().filter(|address| address.ip() == peer_address.ip()).cloned().collect();
+            addresses.iter().filter_map(|address| peers.remove(address)).collect()
+        };
+        for peer_info in banned {
+            peer_info.connection.close();
+            println!("👋 断开已封禁节点 {}", peer_info.address);
+        }
+        println!("🚫 已封禁 {} 至 {}", peer_address.ip(), until.format("%Y-%m-%d %H:%M:%S"));
+        self.save_address_book();
+    }
+
+    /// 解除 IP 的封禁，返回该 IP 此前是否被封禁
+    pub fn unban_peer(&self, ip: IpAddr) -> bool {
+        let unbanned = self.address_book.lock().unwrap().unban(ip);
+        if unbanned {
+            println!("✅ 已解除 {} 的封禁", ip);
+            self.save_address_book();
+        }
+        unbanned
+    }
+
+    /// 保存地址簿到数据文件：复制快照后在锁外写入，不阻塞握手和地址消息的处理
+    fn save_address_book(&self) {
+        let address_book = self.address_book.lock().unwrap().clone();
+        if let Err(e) = address_book.save_to_file(&self.address_book_file) {
+            println!("❌ 保存节点地址失败: {}", e);
+        }
+    }
 
-            for (addr, peer_info) in peers.iter() {
-                if peer_info.last_seen.elapsed() > std::time::Duration::from_secs(60) {
-                    println!("💔 节点 {} 超时，移除", addr);
-                    to_remove.push(*addr);
+    /// 心跳循环：定期向每个节点发送 Ping 测量延迟并保持连接，移除长时间无消息的节点
+    async fn heartbeat_loop(&self) {
+        let mut shutdown = self.shutdown.subscribe();
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        interval.tick().await; // 第一次立即触发
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.wait_for(|stopped| *stopped) => break,
+            }
+
+            let seen: Vec<SocketAddr> = {
+                let mut peers = self.peers.lock().unwrap();
+                peers.retain(|addr, peer_info| {
+                    let alive = peer_info.last_seen.elapsed() <= PEER_TIMEOUT;
+                    if !alive {
+                        println!("💔 节点 {} 超时，移除", addr);
+                        peer_info.connection.close();
+                    }
+                    alive
+                });
+                for peer_info in peers.values_mut() {
+                    peer_info.send_ping();
+                }
+                peers.values().filter_map(PeerInfo::dial_address).collect()
+            };
+
+            // 更新已连接节点的活跃时间并保存地址簿
+            {
+                let mut address_book = self.address_book.lock().unwrap();
+                for address in seen {
+                    address_book.mark_seen(address);
                 }
             }
+            self.save_address_book();
+        }
+    }
+
+    /// 将新得知的地址转发给除来源外随机的几个节点
+    fn relay_addresses(&self, addresses: Vec<SocketAddr>, source: SocketAddr) {
+        let mut targets: Vec<PeerConnection> = self.peers
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|peer_info| peer_info.address != source)
+            .map(|peer_info| peer_info.connection.clone())
+            .collect();
+        targets.shuffle(&mut rand::thread_rng());
+        for connection in targets.into_iter().take(ADDR_RELAY_PEERS) {
+            let _ = connection.send(&Message::Addr(addresses.clone()));
+        }
+    }
+
+    /// 定期从地址簿挑选地址发起连接，使出站连接数保持在 TARGET_OUTBOUND_CONNECTIONS
+    async fn maintain_connections(&self) {
+        let mut shutdown = self.shutdown.subscribe();
+        let mut interval = tokio::time::interval(DIAL_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.wait_for(|stopped| *stopped) => break,
+            }
 
-            for addr in to_remove {
-                peers.remove(&addr);
+            let (outbound, connected): (usize, Vec<SocketAddr>) = {
+                let peers = self.peers.lock().unwrap();
+                (peers.values().filter(|peer_info| peer_info.outbound).count(), peers.values().filter_map(PeerInfo::dial_address).collect())
+            };
+            if outbound >= TARGET_OUTBOUND_CONNECTIONS {
+                continue;
+            }
+
+            let candidates = self.address_book
+                .lock()
+                .unwrap()
+                .dial_candidates(TARGET_OUTBOUND_CONNECTIONS - outbound, |address| {
+                    *address == self.address || connected.contains(address)
+                });
+            let mut dials = JoinSet::new();
+            for address in candidates {
+                println!("🔌 自动连接节点 {} (出站连接 {}/{})", address, outbound, TARGET_OUTBOUND_CONNECTIONS);
+                let node = self.handle();
+                dials.spawn(async move {
+                    if let Err(e) = node.connect(address).await {
+                        println!("❌ 自动连接失败: {}", e);
+                    }
+                });
+            }
+            tokio::select! {
+                _ = async { while dials.join_next().await.is_some() {} } => {}
+                _ = shutdown.wait_for(|stopped| *stopped) => break,
             }
         }
     }
 
+    /// 是否已与可通过 address 连接的节点建立连接（按拨号地址或采信的监听地址判断）
+    fn connected_to(peers: &HashMap<SocketAddr, PeerInfo>, address: SocketAddr) -> bool {
+        peers.values().any(|peer_info| peer_info.dial_address() == Some(address))
+    }
+
     /// 获取对等节点列表
     pub fn get_peers(&self) -> Vec<SocketAddr> {
         self.peers.lock().unwrap().keys().cloned().collect()
     }
 
-    /// 停止节点
+    /// 获取对等节点详情
+    pub fn peer_infos(&self) -> Vec<PeerInfo> {
+        self.peers.lock().unwrap().values().cloned().collect()
+    }
+
+    /// 停止节点：通知后台任务退出，关闭所有连接并等待任务结束
     pub fn stop(&mut self) {
+        if !self.is_running() {
+            return;
+        }
         println!("🛑 停止 P2P 节点");
-        // 这里可以添加清理逻辑
+        self.shutdown.send_replace(true);
+
+        for (_, peer_info) in self.peers.lock().unwrap().drain() {
+            peer_info.connection.close();
+        }
+        self.pending_requests.lock().unwrap().clear();
+
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        self.runtime.block_on(async {
+            for task in tasks {
+                let _ = timeout(SHUTDOWN_TIMEOUT, task).await;
+            }
+        });
+
+        self.save_address_book();
+        println!("✅ P2P 节点已停止");
     }
 
     /// 处理新区块
-    fn handle_new_block(blockchain: &mut Blockchain, block: Block) {
-        // 验证区块
-        if !blockchain.is_chain_valid() {
-            println!("❌ 区块验证失败");
-            return;
+    fn handle_new_block(blockchain: &mut Blockchain, block: Block) -> BlockOutcome {
+        // 检查是否与本地同高度区块构成双签
+        if let Some(evidence) = blockchain.detect_double_sign(&block) {
+            println!("⚠️ 检测到验证者 {} 在高度 {} 双签", evidence.validator(), block.height);
+            return BlockOutcome::DoubleSign(evidence);
         }
 
         // 检查是否已经有这个区块
-        let latest_block = blockchain.get_latest_block();
-        if block.header.prev_hash != latest_block.header.hash {
-            println!("⚠️ 收到非连续区块，尝试同步");
-            // 这里应该触发同步逻辑
-            return;
+        if blockchain.find_block(&block.header.hash).is_some() {
+            println!("ℹ️ 区块已存在");
+            return BlockOutcome::Ignored;
         }
 
-        // 尝试替换链（如果新区块更长）
-        let new_chain = vec![block];
-        if blockchain.replace_chain(new_chain) {
-            println!("✅ 区块链已更新");
-        } else {
-            println!("ℹ️ 区块已存在或不是更长的链");
+        // 哈希、区块头签名或交易格式无效的区块无论能否连接都是无效的
+        if let Err(e) = block.check_integrity() {
+            println!("❌ {}", e);
+            return BlockOutcome::Invalid;
         }
-    }
 
-    /// 处理区块链请求
-    fn handle_chain_request(blockchain: &Arc<Mutex<Blockchain>>, stream: &mut TcpStream, peer_addr: SocketAddr) {
-        let blockchain = blockchain.lock().unwrap();
-        let chain = blockchain.chain.clone();
-
-        // 发送区块链响应
-        let response = Message::ResponseChain(chain.clone());
-        match serialize(&response) {
-            Ok(data) => {
-                if let Err(e) = stream.write_all(&data) {
-                    println!("❌ 发送区块链响应失败: {}", e);
-                } else {
-                    println!("📤 发送区块链响应到 {}，长度: {}", peer_addr, chain.len());
-                }
-            }
-            Err(e) => {
-                println!("❌ 序列化区块链响应失败: {}", e);
+        let latest_block = blockchain.get_latest_block();
+        if block.header.prev_hash != latest_block.header.hash {
+            if block.height >= blockchain.get_length() as u64 {
+                println!("⚠️ 收到非连续区块，尝试同步");
+                return BlockOutcome::Orphan;
             }
+            println!("ℹ️ 区块不在当前链顶，忽略");
+            return BlockOutcome::Ignored;
         }
-    }
 
-    /// 处理链长度请求
-    fn handle_chain_length_request(blockchain: &Arc<Mutex<Blockchain>>, stream: &mut TcpStream, peer_addr: SocketAddr) {
-        let blockchain = blockchain.lock().unwrap();
-        let length = blockchain.get_length();
-
-        // 发送链长度响应
-        let response = Message::ResponseChainLength(length);
-        match serialize(&response) {
-            Ok(data) => {
-                if let Err(e) = stream.write_all(&data) {
-                    println!("❌ 发送链长度响应失败: {}", e);
-                } else {
-                    println!("📤 发送链长度响应到 {}: {}", peer_addr, length);
-                }
+        match blockchain.accept_block(block) {
+            Ok(()) => {
+                println!("✅ 区块链已更新");
+                BlockOutcome::Accepted
             }
             Err(e) => {
-                println!("❌ 序列化链长度响应失败: {}", e);
+                println!("❌ {}", e);
+                BlockOutcome::Rejected
             }
         }
     }
 
-    /// 处理区块范围请求
-    fn handle_blocks_request(blockchain: &Arc<Mutex<Blockchain>>, start: u64, end: u64, stream: &mut TcpStream, peer_addr: SocketAddr) {
-        let blockchain = blockchain.lock().unwrap();
+    /// 获取区块范围 [start, end]（超出链尾的部分截断，范围无效时返回空列表）
+    fn blocks_in_range(&self, start: u64, end: u64) -> Vec<Block> {
+        let blockchain = self.blockchain.lock().unwrap();
         let chain_length = blockchain.get_length() as u64;
 
         if start >= chain_length || end < start {
             println!("❌ 无效的区块范围请求: {}-{}", start, end);
-            return;
+            return Vec::new();
         }
 
         let actual_end = end.min(chain_length - 1);
-        let blocks: Vec<Block> = blockchain.chain
+        blockchain.chain
             .iter()
             .skip(start as usize)
             .take((actual_end - start + 1) as usize)
             .cloned()
-            .collect();
+            .collect()
+    }
 
-        // 发送区块范围响应
-        let response = Message::ResponseBlocks(blocks);
-        match serialize(&response) {
-            Ok(data) => {
-                if let Err(e) = stream.write_all(&data) {
-                    println!("❌ 发送区块范围响应失败: {}", e);
-                } else {
-                    println!("📤 发送区块范围响应到 {}: {}-{} ({} 区块)",
-                             peer_addr, start, actual_end, actual_end - start + 1);
-                }
-            }
-            Err(e) => {
-                println!("❌ 序列化区块范围响应失败: {}", e);
+    /// 处理同步状态，返回是否需要从对方同步
+    fn handle_sync_status(&self, remote_length: usize, remote_hash: String, remote_transactions: usize) -> bool {
+        let blockchain = self.blockchain.lock().unwrap();
+        let local_length = blockchain.get_length();
+        let local_transactions = blockchain.get_total_transactions();
+        let local_hash = blockchain.get_latest_block().header.hash.clone();
+
+        println!("🔄 同步状态比较:");
+        println!("  本地: 长度={}, 哈希={}, 交易={}",
+                 local_length, local_hash, local_transactions);
+        println!("  远程: 长度={}, 哈希={}, 交易={}",
+                 remote_length, remote_hash, remote_transactions);
+
+        // 只在对方链更长时自动同步；等长分叉由手动同步按累计工作量处理，避免双方反复互相同步
+        if remote_length > local_length {
+            println!("📈 需要同步到更新的链");
+            true
+        } else {
+            if remote_length == local_length && remote_hash != local_hash {
+                println!("⚠️ 与对方链等长但最新区块不同");
+            } else {
+                println!("✅ 本地链是最新的");
             }
+            false
         }
     }
 
-    /// 处理链响应
-    fn handle_chain_response(blockchain: &mut Blockchain, new_chain: Vec<Block>) {
-        println!("🔄 处理区块链响应，长度: {}", new_chain.len());
+    /// 广播同步状态
+    pub fn broadcast_sync_status(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let blockchain = self.blockchain.lock().unwrap();
+        let chain_length = blockchain.get_length();
+        let latest_hash = blockchain.get_latest_block().header.hash.clone();
+        let total_transactions = blockchain.get_total_transactions();
+        let params_hash = blockchain.chain_params.schedule_hash();
 
-        // 验证新链
-        if new_chain.is_empty() {
-            println!("❌ 收到空链");
-            return;
+        let message = Message::SyncStatus {
+            chain_length,
+            latest_hash: latest_hash.clone(),
+            total_transactions,
+            params_hash,
+        };
+
+        self.broadcast_message(message)?;
+        println!("📡 广播同步状态: 长度={}, 哈希={}, 交易={}",
+                 chain_length, latest_hash, total_transactions);
+
+        Ok(())
+    }
+
+    /// 与指定节点同步：先下载并验证区块头链，再从多个节点并行下载区块体
+    pub fn start_sync_with_peer(&self, peer_address: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(self.runtime.block_on(self.sync_with_peer(peer_address))?)
+    }
+
+    /// 同步流程（同一时间只进行一次同步）
+    async fn sync_with_peer(&self, peer_address: SocketAddr) -> Result<(), String> {
+        if self.syncing.swap(true, Ordering::SeqCst) {
+            println!("⏳ 已有同步正在进行，跳过与节点 {} 的同步", peer_address);
+            return Ok(());
         }
+        println!("🔄 开始与节点 {} 的同步流程", peer_address);
+        let result = self.sync_headers_first(peer_address).await;
+        self.syncing.store(false, Ordering::SeqCst);
+        result?;
 
-        // 验证链的完整性
-        let temp_chain = new_chain.clone();
-        let mut is_valid = true;
+        // 广播我们的状态，链更短的节点会向我们同步
+        self.broadcast_sync_status().map_err(|e| e.to_string())
+    }
 
-        for i in 1..temp_chain.len() {
-            let current = &temp_chain[i];
-            let previous = &temp_chain[i - 1];
+    /// 区块头优先同步：获取区块头 → 验证区块头链 → 分批下载区块体 → 替换本地链
+    async fn sync_headers_first(&self, peer_address: SocketAddr) -> Result<(), String> {
+        // 1. 按区块定位器获取区块头，每次最多 MAX_HEADERS 个，直到对方没有更多
+        let mut headers: Vec<BlockHeader> = Vec::new();
+        let mut locator = self.blockchain.lock().unwrap().block_locator();
+        loop {
+            let request_locator = locator.clone();
+            let batch = match self
+                .request(peer_address, |request_id| Message::GetHeaders { request_id, locator: request_locator, stop_hash: String::new() })
+                .await?
+            {
+                Message::Headers { headers, .. } => headers,
+                other => return Err(format!("意外的响应: {:?}", other.message_type())),
+            };
+            if batch.len() > MAX_HEADERS {
+                self.misbehaving(peer_address, Misbehavior::OversizedMessage);
+                return Err(format!("区块头数量 {} 超过上限 {}", batch.len(), MAX_HEADERS));
+            }
+
+            // 区块头之间不连续或哈希不满足自身难度在任何节点上都无效
+            let connected = headers.last()
+                .into_iter()
+                .chain(&batch)
+                .collect::<Vec<_>>()
+                .windows(2)
+                .all(|pair| pair[1].prev_hash == pair[0].hash);
+            if !connected || batch.iter().any(|header| !header.meets_target()) {
+                self.misbehaving(peer_address, Misbehavior::InvalidBlock);
+                return Err("区块头不连续或不满足难度要求".to_string());
+            }
 
-            if !current.is_valid(&previous.header.hash) {
-                println!("❌ 链验证失败在区块 {}", i);
-                is_valid = false;
+            let more = batch.len() == MAX_HEADERS;
+            if let Some(last) = batch.last() {
+                locator = vec![last.hash.clone()];
+            }
+            headers.extend(batch);
+            if !more {
                 break;
             }
+            println!("📑 已从 {} 接收 {} 个区块头", peer_address, headers.len());
         }
 
-        if !is_valid {
-            println!("❌ 新链验证失败，忽略");
-            return;
+        if headers.is_empty() {
+            println!("✅ 本地链是最新的");
+            return Ok(());
         }
 
-        // 比较链长度
-        if new_chain.len() > blockchain.get_length() {
-            println!("📈 新链更长 ({} > {})，替换区块链",
-                     new_chain.len(), blockchain.get_length());
-
-            if blockchain.replace_chain(new_chain) {
-                println!("✅ 区块链替换成功");
-                // 广播新链到其他节点
-                // TODO: 广播新链
-            } else {
-                println!("❌ 区块链替换失败");
-            }
-        } else {
-            println!("ℹ️ 新链不更长，保持当前链");
-        }
+        // 2. 下载区块体前按本地链验证区块头链（连接点和难度规则依赖本地链状态，不处罚）
+        let fork_height = {
+            let blockchain = self.blockchain.lock().unwrap();
+            let fork_height = blockchain.validate_headers(&headers)?;
+            blockchain.check_branch(fork_height, &headers)?;
+            fork_height
+        };
+        let best_height = (fork_height + headers.len() - 1) as u64;
+        self.update_best_height(peer_address, best_height);
+        println!("📑 区块头验证通过: 高度 {}-{}，共 {} 个", fork_height, best_height, headers.len());
+
+        // 3. 并行下载区块体
+        let blocks = self.download_blocks(peer_address, fork_height as u64, &headers).await?;
+
+        // 4. 按完整规则验证并替换本地链；下载期间本地链可能已更新，先重新检查分支
+        let result = {
+            let mut blockchain = self.blockchain.lock().unwrap();
+            blockchain.check_branch(fork_height, &headers)?;
+            blockchain.accept_branch(fork_height, blocks).map(|()| blockchain.get_length() - 1)
+        };
+        // 区块已通过完整性检查，不满足依赖链状态的规则时不处罚
+        let height = result?;
+        println!("✅ 区块链已同步到高度 {}", height);
+        Ok(())
     }
 
-    /// 处理链长度响应
-    fn handle_chain_length_response(blockchain: &Arc<Mutex<Blockchain>>, remote_length: usize, stream: &mut TcpStream, peer_addr: SocketAddr) {
-        let blockchain = blockchain.lock().unwrap();
-        let local_length = blockchain.get_length();
+    /// 下载区块头对应的区块体：按 BLOCK_BATCH_SIZE 分批，同时向每个可用节点请求一批，
+    /// 失败或超时的批次交给其他节点重试
+    async fn download_blocks(&self, source: SocketAddr, start_height: u64, headers: &[BlockHeader]) -> Result<Vec<Block>, String> {
+        let end_height = start_height + headers.len() as u64 - 1;
+        let mut queue: VecDeque<(u64, u64, u32)> = (start_height..=end_height)
+            .step_by(BLOCK_BATCH_SIZE as usize)
+            .map(|start| (start, (start + BLOCK_BATCH_SIZE - 1).min(end_height), 0))
+            .collect();
+        let mut idle = self.download_peers(source, end_height);
+        let mut downloaded: Vec<Option<Block>> = vec![None; headers.len()];
+        let mut received = 0;
+        let mut in_flight = JoinSet::new();
+        println!("📥 开始从 {} 个节点下载 {} 个区块", idle.len(), headers.len());
 
-        println!("🔄 比较链长度: 本地={}, 远程={}", local_length, remote_length);
+        loop {
+            while !queue.is_empty()
+                && let Some(peer) = idle.pop_front()
+            {
+                let (start, end, attempts) = queue.pop_front().unwrap();
+                let node = self.handle();
+                in_flight.spawn(async move {
+                    let result = node.request(peer, |request_id| Message::RequestBlocks { request_id, start, end }).await;
+                    (peer, start, end, attempts, result)
+                });
+            }
 
-        if remote_length > local_length {
-            println!("📈 远程链更长，需要同步");
-            // 请求完整的区块链
-            let request_message = Message::RequestChain;
-            match serialize(&request_message) {
-                Ok(data) => {
-                    if let Err(e) = stream.write_all(&data) {
-                        println!("❌ 请求区块链失败: {}", e);
-                    } else {
-                        println!("📤 请求完整区块链从 {}", peer_addr);
-                    }
-                }
-                Err(e) => {
-                    println!("❌ 序列化区块链请求失败: {}", e);
+            let Some(joined) = in_flight.join_next().await else {
+                break;
+            };
+            let (peer, start, end, attempts, result) = joined.map_err(|e| e.to_string())?;
+            let checked = match result {
+                Ok(Message::ResponseBlocks { blocks, .. }) if let Some(e) = blocks.iter().find_map(|block| block.check_integrity().err()) => {
+                    self.misbehaving(peer, Misbehavior::InvalidBlock);
+                    Err(e)
                 }
-            }
-        } else if remote_length < local_length {
-            println!("📈 本地链更长，考虑广播我们的链");
-            // 广播我们的完整链
-            let chain = blockchain.chain.clone();
-            let response_message = Message::ResponseChain(chain);
-            match serialize(&response_message) {
-                Ok(data) => {
-                    if let Err(e) = stream.write_all(&data) {
-                        println!("❌ 广播区块链失败: {}", e);
-                    } else {
-                        println!("📤 广播完整区块链到 {}", peer_addr);
+                Ok(Message::ResponseBlocks { blocks, .. }) => Self::check_batch(headers, start_height, start, end, blocks),
+                Ok(other) => Err(format!("意外的响应: {:?}", other.message_type())),
+                Err(e) => Err(e),
+            };
+
+            match checked {
+                Ok(blocks) => {
+                    received += blocks.len();
+                    for block in blocks {
+                        let index = (block.height - start_height) as usize;
+                        downloaded[index] = Some(block);
                     }
+                    println!("📥 下载区块 {}/{} (高度 {}-{} 来自 {})", received, headers.len(), start, end, peer);
+                    idle.push_front(peer);
                 }
                 Err(e) => {
-                    println!("❌ 序列化区块链响应失败: {}", e);
-                }
-            }
-        } else {
-            println!("📊 链长度相同，检查最新区块哈希");
-            // 请求远程最新区块进行比较
-            let request_message = Message::RequestBlocks { start: remote_length as u64 - 1, end: remote_length as u64 - 1 };
-            match serialize(&request_message) {
-                Ok(data) => {
-                    if let Err(e) = stream.write_all(&data) {
-                        println!("❌ 请求最新区块失败: {}", e);
-                    } else {
-                        println!("📤 请求最新区块从 {}", peer_addr);
+                    println!("⚠️ 从节点 {} 下载区块 {}-{} 失败 (第 {} 次): {}", peer, start, end, attempts + 1, e);
+                    if attempts + 1 >= MAX_BATCH_ATTEMPTS {
+                        return Err(format!("区块 {}-{} 下载失败 {} 次，放弃同步", start, end, MAX_BATCH_ATTEMPTS));
+                    }
+                    queue.push_back((start, end, attempts + 1));
+                    // 失败的节点排在最后，优先由其他节点重试；已断开的节点不再使用
+                    if self.peers.lock().unwrap().contains_key(&peer) {
+                        idle.push_back(peer);
                     }
-                }
-                Err(e) => {
-                    println!("❌ 序列化区块请求失败: {}", e);
                 }
             }
         }
+
+        if !queue.is_empty() {
+            return Err("没有可用的节点下载区块".to_string());
+        }
+        Ok(downloaded.into_iter().flatten().collect())
     }
 
-    /// 处理区块响应
-    fn handle_blocks_response(blockchain: &mut Blockchain, blocks: Vec<Block>) {
-        println!("🔄 处理区块响应，数量: {}", blocks.len());
+    /// 可下载区块体的节点：提供区块数据且已知高度不低于 end_height 的节点，同步来源节点排在最前
+    fn download_peers(&self, source: SocketAddr, end_height: u64) -> VecDeque<SocketAddr> {
+        let mut peers: VecDeque<SocketAddr> = self.peers
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|peer_info| peer_info.address != source)
+            .filter(|peer_info| peer_info.version.services & SERVICE_FULL_CHAIN != 0 && peer_info.version.best_height >= end_height)
+            .map(|peer_info| peer_info.address)
+            .collect();
+        peers.push_front(source);
+        peers
+    }
 
-        if blocks.is_empty() {
-            println!("❌ 收到空区块列表");
-            return;
+    /// 检查下载的一批区块与已验证的区块头一致：数量和高度连续、哈希与区块头相同
+    fn check_batch(headers: &[BlockHeader], start_height: u64, start: u64, end: u64, blocks: Vec<Block>) -> Result<Vec<Block>, String> {
+        if blocks.len() as u64 != end - start + 1 {
+            return Err(format!("期望 {} 个区块，收到 {} 个", end - start + 1, blocks.len()));
         }
-
-        // 验证区块序列
-        for (i, block) in blocks.iter().enumerate() {
-            if i == 0 {
-                // 第一个区块应该连接到当前链
-                let latest_block = blockchain.get_latest_block();
-                if block.header.prev_hash != latest_block.header.hash {
-                    println!("❌ 区块 {} 不连接到当前链", block.height);
-                    return;
-                }
-            } else {
-                // 后续区块应该连接到前一个区块
-                let prev_block = &blocks[i - 1];
-                if block.header.prev_hash != prev_block.header.hash {
-                    println!("❌ 区块序列断裂在区块 {}", block.height);
-                    return;
-                }
+        for (height, block) in (start..=end).zip(&blocks) {
+            let header = &headers[(height - start_height) as usize];
+            if block.height != height || block.header.hash != header.hash {
+                return Err(format!("区块 {} 与区块头不一致", height));
             }
         }
+        Ok(blocks)
+    }
 
-        // 添加区块到链
-        for block in &blocks {
-            blockchain.chain.push(block.clone());
-            println!("✅ 添加区块 {} 到链", block.height);
+    /// 更新节点的已知最高区块高度
+    fn update_best_height(&self, peer_address: SocketAddr, best_height: u64) {
+        if let Some(peer_info) = self.peers.lock().unwrap().get_mut(&peer_address) {
+            peer_info.version.best_height = peer_info.version.best_height.max(best_height);
         }
+    }
+}
 
-        println!("✅ 成功添加 {} 个区块", blocks.len());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_spec::ChainSpec;
+
+    #[test]
+    fn large_chain_response_survives_fragmented_reads() {
+        let mut chain = Blockchain::from_spec(&ChainSpec::regtest()).chain;
+        for height in 1..50 {
+            let transactions = (0..5).map(|i| Transaction::new(format!("sender{}", i), "receiver".to_string(), 1)).collect();
+            chain.push(Block::new(chain.last().unwrap().header.hash.clone(), transactions, height, 1));
+        }
+        let data = [
+            P2PNode::encode_message(&Message::ResponseChain { request_id: 7, blocks: chain.clone() }).unwrap(),
+            P2PNode::encode_message(&Message::Ping { nonce: 42 }).unwrap(),
+        ]
+        .concat();
+        assert!(data.len() > 4096);
+
+        let mut decoder = FrameDecoder::new();
+        let mut messages = Vec::new();
+        for chunk in data.chunks(1000) {
+            decoder.extend(chunk);
+            while let Some(frame) = decoder.next_frame().unwrap() {
+                messages.push(P2PNode::decode_message(frame).unwrap());
+            }
+        }
+
+        assert_eq!(messages.len(), 2);
+        match &messages[0] {
+            Message::ResponseChain { request_id, blocks: received } => {
+                assert_eq!(*request_id, 7);
+                assert_eq!(received.len(), chain.len());
+                assert_eq!(received.last().unwrap().header.hash, chain.last().unwrap().header.hash);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert!(matches!(messages[1], Message::Ping { nonce: 42 }));
     }
 
-    /// 处理同步状态
-    fn handle_sync_status(
-        blockchain: &Arc<Mutex<Blockchain>>,
-        remote_length: usize,
-        remote_hash: String,
-        remote_transactions: usize,
-    ) {
-        let blockchain = blockchain.lock().unwrap();
-        let local_length = blockchain.get_length();
-        let local_transactions = blockchain.get_total_transactions();
-        let local_hash = blockchain.get_latest_block().header.hash.clone();
+    /// 在本地随机端口启动使用 regtest 链的节点
+    fn start_node(runtime: &tokio::runtime::Runtime, name: &str) -> (P2PNode, std::path::PathBuf) {
+        let address_book_file = std::env::temp_dir().join(format!("p2p_{}_test_{}.json", name, std::process::id()));
+        let address = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let blockchain = Arc::new(Mutex::new(Blockchain::from_spec(&ChainSpec::regtest())));
+        let mut node = P2PNode::new(address, blockchain, runtime.handle().clone(), address_book_file.to_str().unwrap());
+        node.start().unwrap();
+        (node, address_book_file)
+    }
 
-        println!("🔄 同步状态比较:");
-        println!("  本地: 长度={}, 哈希={}, 交易={}",
-                 local_length, local_hash, local_transactions);
-        println!("  远程: 长度={}, 哈希={}, 交易={}",
-                 remote_length, remote_hash, remote_transactions);
+    /// 以原始 TCP 连接向节点发送 version 完成入站握手，返回连接和本端套接字地址
+    async fn inbound_handshake(node: &P2PNode, version: VersionInfo) -> (TcpStream, SocketAddr) {
+        let mut stream = TcpStream::connect(node.address).await.unwrap();
+        stream.write_all(&P2PNode::encode_message(&Message::Version(version)).unwrap()).await.unwrap();
+        let mut decoder = FrameDecoder::new();
+        let mut buffer = vec![0; 4096];
+        'handshake: loop {
+            let size = stream.read(&mut buffer).await.unwrap();
+            assert!(size > 0, "连接在握手完成前关闭");
+            decoder.extend(&buffer[..size]);
+            while let Some(frame) = decoder.next_frame().unwrap() {
+                if matches!(P2PNode::decode_message(frame).unwrap(), Message::Verack) {
+                    break 'handshake;
+                }
+            }
+        }
+        stream.write_all(&P2PNode::encode_message(&Message::Verack).unwrap()).await.unwrap();
 
-        // 决定是否需要同步
-        if remote_length > local_length ||
-           (remote_length == local_length && remote_hash != local_hash) {
-            println!("📈 需要同步到更新的链");
-            // TODO: 触发同步逻辑
-        } else {
-            println!("✅ 本地链是最新的");
+        let socket_address = stream.local_addr().unwrap();
+        for _ in 0..100 {
+            if node.peers.lock().unwrap().contains_key(&socket_address) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
         }
+        (stream, socket_address)
     }
 
-    /// 请求区块链同步
-    pub fn request_chain_sync(&self, peer_address: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
-        println!("🔄 请求与节点 {} 同步", peer_address);
+    #[test]
+    fn misbehaving_inbound_peer_is_banned_by_socket_address() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (mut node, address_book_file) = start_node(&runtime, "ban");
 
-        let message = Message::RequestChainLength;
-        let data = serialize(&message)?;
-        self.send_to_peer(peer_address, &data)?;
+        // 入站连接通告其他节点的监听地址
+        let forged: SocketAddr = "10.1.2.3:8333".parse().unwrap();
+        let version = VersionInfo { listen_address: forged, nonce: node.nonce.wrapping_add(1), ..node.version_info() };
+        let (_stream, socket_address) = runtime.block_on(inbound_handshake(&node, version));
 
-        Ok(())
-    }
+        assert_eq!(node.get_peers(), vec![socket_address]);
+        assert!(node.address_book.lock().unwrap().entries().iter().any(|known| known.address == forged));
 
-    /// 广播同步状态
-    pub fn broadcast_sync_status(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let blockchain = self.blockchain.lock().unwrap();
-        let chain_length = blockchain.get_length();
-        let latest_hash = blockchain.get_latest_block().header.hash.clone();
-        let total_transactions = blockchain.get_total_transactions();
+        node.misbehaving(socket_address, Misbehavior::InvalidBlock);
+        {
+            let address_book = node.address_book.lock().unwrap();
+            assert!(address_book.is_banned(socket_address.ip()));
+            assert!(!address_book.is_banned(forged.ip()));
+        }
+        assert!(node.get_peers().is_empty());
 
-        let message = Message::SyncStatus {
-            chain_length,
-            latest_hash: latest_hash.clone(),
-            total_transactions,
-        };
+        node.stop();
+        let _ = fs::remove_file(&address_book_file);
+    }
 
-        self.broadcast_message(message)?;
-        println!("📡 广播同步状态: 长度={}, 哈希={}, 交易={}",
-                 chain_length, latest_hash, total_transactions);
+    #[test]
+    fn unsolicited_chain_response_is_dropped_and_scored() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (mut node, address_book_file) = start_node(&runtime, "unsolicited");
+        let version = VersionInfo { nonce: node.nonce.wrapping_add(1), ..node.version_info() };
+        let (mut stream, socket_address) = runtime.block_on(inbound_handshake(&node, version));
+
+        // 对方主动发送的更长链不会替换本地链
+        let mut chain = node.blockchain.lock().unwrap().chain.clone();
+        for height in 1..5 {
+            chain.push(Block::new(chain.last().unwrap().header.hash.clone(), Vec::new(), height, 1));
+        }
+        let response = P2PNode::encode_message(&Message::ResponseChain { request_id: 9_999, blocks: chain }).unwrap();
+        runtime.block_on(async {
+            stream.write_all(&response).await.unwrap();
+            for _ in 0..100 {
+                if node.peers.lock().unwrap().get(&socket_address).is_some_and(|peer_info| peer_info.misbehavior > 0) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
 
-        Ok(())
+        assert_eq!(node.peers.lock().unwrap()[&socket_address].misbehavior, Misbehavior::UnsolicitedResponse.score());
+        assert_eq!(node.blockchain.lock().unwrap().get_length(), 1);
+
+        node.stop();
+        let _ = fs::remove_file(&address_book_file);
     }
 
-    /// 启动同步流程
-    pub fn start_sync_with_peer(&self, peer_address: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
-        println!("🔄 开始与节点 {} 的同步流程", peer_address);
+    #[test]
+    fn handshake_rejects_incompatible_versions() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (mut node, address_book_file) = start_node(&runtime, "version");
+        let compatible = VersionInfo { nonce: node.nonce.wrapping_add(1), ..node.version_info() };
+        assert!(node.check_version(&compatible).is_ok());
+
+        let testnet = Blockchain::from_spec(&ChainSpec::testnet());
+        let mut forked = Blockchain::from_spec(&ChainSpec::regtest());
+        forked.schedule_consensus_fork(crate::consensus::ConsensusType::PoS, 10, Default::default()).unwrap();
+        let incompatible = [
+            (VersionInfo { nonce: node.nonce, ..compatible.clone() }, "本节点"),
+            (VersionInfo { protocol_version: MIN_PROTOCOL_VERSION - 1, ..compatible.clone() }, "协议版本"),
+            (VersionInfo { chain_id: "rust-chain-testnet".to_string(), ..compatible.clone() }, "链标识"),
+            (VersionInfo { genesis_hash: testnet.chain[0].header.hash.clone(), ..compatible.clone() }, "创世区块"),
+            (VersionInfo { params_hash: forked.chain_params.schedule_hash(), ..compatible.clone() }, "硬分叉计划"),
+        ];
+        for (version, reason) in &incompatible {
+            let error = node.check_version(version).unwrap_err();
+            assert!(error.contains(reason), "{}", error);
+        }
 
-        // 1. 请求链长度
-        let length_message = Message::RequestChainLength;
-        let length_data = serialize(&length_message)?;
-        self.send_to_peer(peer_address, &length_data)?;
+        // 不兼容的入站连接在握手阶段被断开，不加入对等节点列表
+        let (version, _) = incompatible[4].clone();
+        let closed = runtime.block_on(async {
+            let mut stream = TcpStream::connect(node.address).await.unwrap();
+            stream.write_all(&P2PNode::encode_message(&Message::Version(version)).unwrap()).await.unwrap();
+            let mut buffer = vec![0; 4096];
+            matches!(timeout(HANDSHAKE_TIMEOUT, stream.read(&mut buffer)).await, Ok(Ok(0)) | Ok(Err(_)))
+        });
+        assert!(closed);
+        assert!(node.get_peers().is_empty());
 
-        // 2. 广播我们的状态
-        self.broadcast_sync_status()?;
+        node.stop();
+        let _ = fs::remove_file(&address_book_file);
+    }
 
-        Ok(())
+    #[test]
+    fn connected_peers_are_persisted_in_address_book() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (mut server, server_file) = start_node(&runtime, "server");
+        let (mut client, client_file) = start_node(&runtime, "client");
+
+        client.connect_to_peer(server.address).unwrap();
+        assert_eq!(client.get_peers(), vec![server.address]);
+        assert!(client.peer_infos()[0].outbound);
+        // 双方已连接时再次连接复用已有连接
+        client.connect_to_peer(server.address).unwrap();
+        assert_eq!(client.get_peers().len(), 1);
+
+        // 停止节点时保存地址簿，重新创建节点后加载同样的地址和连接记录
+        client.stop();
+        let reloaded = P2PNode::new(client.address, client.blockchain.clone(), runtime.handle().clone(), client_file.to_str().unwrap());
+        let known = reloaded.address_book.lock().unwrap().entries();
+        let server_entry = known.iter().find(|known| known.address == server.address).unwrap();
+        assert!(server_entry.last_success.is_some());
+        assert_eq!(server_entry.failures, 0);
+        assert_eq!(
+            reloaded.address_book.lock().unwrap().dial_candidates(8, |_| false),
+            vec![server.address]
+        );
+
+        server.stop();
+        let _ = fs::remove_file(&server_file);
+        let _ = fs::remove_file(&client_file);
     }
 }
@@ -0,0 +1,208 @@
+/// 256 位目标值（大端字节序）
+pub type Target = [u8; 32];
+
+/// 最低难度目标的紧凑编码（约等于一个十六进制前导零，2^252 - 1）
+pub const POW_LIMIT_BITS: u32 = 0x200f_ffff;
+
+/// 紧凑编码转换为 256 位目标值
+///
+/// 高 8 位为字节长度 exponent，低 23 位为尾数 mantissa，目标值 = mantissa * 256^(exponent - 3)
+pub fn compact_to_target(bits: u32) -> Target {
+    let mut target = [0u8; 32];
+    let exponent = bits >> 24;
+    let mantissa = bits & 0x007f_ffff;
+    if mantissa == 0 || bits & 0x0080_0000 != 0 {
+        return target; // 负数或零目标视为无效
+    }
+
+    // 尾数第 i 字节位于目标值从低位数第 exponent - 1 - i 字节，超出 256 位的部分丢弃
+    for (i, byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+        let position = exponent as i64 - 1 - i as i64;
+        if (0..32).contains(&position) {
+            target[31 - position as usize] = *byte;
+        }
+    }
+    target
+}
+
+/// 256 位目标值转换为紧凑编码
+pub fn target_to_compact(target: &Target) -> u32 {
+    let Some(first) = target.iter().position(|byte| *byte != 0) else {
+        return 0;
+    };
+
+    let mut exponent = (32 - first) as u32;
+    let mut mantissa = target[first..]
+        .iter()
+        .take(3)
+        .fold(0u32, |acc, byte| (acc << 8) | *byte as u32);
+    if exponent < 3 {
+        mantissa <<= 8 * (3 - exponent);
+    }
+
+    // 最高位为符号位，需要时右移一个字节
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        exponent += 1;
+    }
+    (exponent << 24) | mantissa
+}
+
+/// 区块哈希（十六进制）是否不大于目标值
+pub fn hash_meets_target(hash_hex: &str, target: &Target) -> bool {
+    match hex::decode(hash_hex) {
+        Ok(hash) if hash.len() == 32 => hash.as_slice() <= target.as_slice(),
+        _ => false,
+    }
+}
+
+/// 由旧版十六进制前导零难度换算目标值紧凑编码
+pub fn difficulty_to_bits(difficulty: u32) -> u32 {
+    let zero_bits = (difficulty.max(1) * 4).min(248) as usize;
+    let mut target = [0xffu8; 32];
+    for (i, byte) in target.iter_mut().enumerate() {
+        let bit_start = i * 8;
+        if bit_start + 8 <= zero_bits {
+            *byte = 0;
+        } else if bit_start < zero_bits {
+            *byte = 0xff >> (zero_bits - bit_start);
+        }
+    }
+    target_to_compact(&target)
+}
+
+/// 目标值对应的近似十六进制前导零个数（用于展示）
+pub fn leading_zero_digits(bits: u32) -> u32 {
+    let target = compact_to_target(bits);
+    let mut zeros = 0;
+    for byte in target {
+        if byte == 0 {
+            zeros += 2;
+            continue;
+        }
+        if byte < 0x10 {
+            zeros += 1;
+        }
+        break;
+    }
+    zeros
+}
+
+/// 目标值对应的期望工作量：2^256 / (target + 1)，按紧凑编码计算并在 u128 内饱和
+pub fn work_from_bits(bits: u32) -> u128 {
+    let exponent = (bits >> 24) as i32;
+    let mantissa = (bits & 0x007f_ffff) as u128;
+    if mantissa == 0 || bits & 0x0080_0000 != 0 {
+        return 0;
+    }
+
+    // 2^256 / (mantissa * 2^(8 * (exponent - 3))) = 2^shift / mantissa
+    let shift = 256 - 8 * (exponent - 3);
+    if shift <= 0 {
+        return 1;
+    }
+    if shift <= 127 {
+        return ((1u128 << shift) / mantissa).max(1);
+    }
+    let base = (1u128 << 127) / mantissa;
+    base.checked_shl((shift - 127) as u32)
+        .filter(|work| work >> (shift - 127) == base)
+        .unwrap_or(u128::MAX)
+}
+
+/// 按实际耗时与目标耗时的比例调整目标值（耗时比限制在 1/4 到 4 倍之间）
+pub fn retarget(bits: u32, actual_secs: i64, expected_secs: i64) -> u32 {
+    let expected_secs = expected_secs.max(1) as u128;
+    let actual_secs = (actual_secs.max(1) as u128).clamp(expected_secs / 4, expected_secs * 4);
+
+    let mut exponent = bits >> 24;
+    let mut mantissa = (bits & 0x007f_ffff) as u128 * actual_secs / expected_secs;
+    while mantissa > 0x007f_ffff {
+        mantissa >>= 8;
+        exponent += 1;
+    }
+    while mantissa < 0x0000_8000 && exponent > 3 {
+        mantissa <<= 8;
+        exponent -= 1;
+    }
+
+    let bits = (exponent << 24) | mantissa as u32;
+    if exponent > 32 || compact_to_target(bits) > compact_to_target(POW_LIMIT_BITS) {
+        POW_LIMIT_BITS
+    } else {
+        bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_round_trip() {
+        for bits in [POW_LIMIT_BITS, 0x1d00_ffff, 0x0312_3456, 0x0212_3400] {
+            assert_eq!(target_to_compact(&compact_to_target(bits)), bits);
+        }
+
+        let target = compact_to_target(0x1d00_ffff);
+        assert_eq!(&target[..6], &[0, 0, 0, 0, 0xff, 0xff]);
+        assert!(target[6..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn zero_and_negative_targets_are_invalid() {
+        assert_eq!(compact_to_target(0x1d00_0000), [0u8; 32]);
+        assert_eq!(compact_to_target(0x1d80_ffff), [0u8; 32]);
+        assert_eq!(target_to_compact(&[0u8; 32]), 0);
+        assert_eq!(work_from_bits(0x1d80_ffff), 0);
+    }
+
+    #[test]
+    fn mantissa_beyond_256_bits_is_dropped() {
+        let mut expected = [0u8; 32];
+        expected[0] = 0x56;
+        assert_eq!(compact_to_target(0x2212_3456), expected);
+    }
+
+    #[test]
+    fn hash_meets_target_at_boundary() {
+        let target = compact_to_target(POW_LIMIT_BITS);
+        let boundary = format!("0fffff{}", "00".repeat(29));
+        let above = format!("0fffff{}01", "00".repeat(28));
+        assert!(hash_meets_target(&boundary, &target));
+        assert!(!hash_meets_target(&above, &target));
+        assert!(!hash_meets_target("0fffff", &target));
+        assert!(!hash_meets_target("not hex", &target));
+    }
+
+    #[test]
+    fn difficulty_to_bits_matches_leading_zeros() {
+        for difficulty in 1..=8 {
+            assert_eq!(leading_zero_digits(difficulty_to_bits(difficulty)), difficulty);
+        }
+    }
+
+    #[test]
+    fn work_from_bits_saturates() {
+        assert_eq!(work_from_bits(POW_LIMIT_BITS), 16);
+        assert_eq!(work_from_bits(0x1d00_ffff), 0x1_0001_0001);
+        assert!(work_from_bits(0x1c00_ffff) > work_from_bits(0x1d00_ffff));
+        assert_eq!(work_from_bits(0x0300_0001), u128::MAX);
+    }
+
+    #[test]
+    fn retarget_scales_and_clamps() {
+        let bits = 0x1d00_ffff;
+        assert_eq!(retarget(bits, 600, 600), bits);
+        assert_eq!(retarget(bits, 1200, 600), 0x1d01_fffe);
+
+        // 耗时比限制在 1/4 到 4 倍之间
+        assert_eq!(retarget(bits, 2400, 600), 0x1d03_fffc);
+        assert_eq!(retarget(bits, 100_000, 600), retarget(bits, 2400, 600));
+        assert_eq!(retarget(bits, 1, 600), 0x1c3f_ff00);
+        assert_eq!(retarget(bits, -5, 600), retarget(bits, 150, 600));
+
+        // 不会低于最低难度
+        assert_eq!(retarget(POW_LIMIT_BITS, 2400, 600), POW_LIMIT_BITS);
+    }
+}
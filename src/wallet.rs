@@ -1,9 +1,11 @@
 use crate::block::Transaction;
+use ring::rand::SystemRandom;
 use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
 use std::sync::{Arc, Mutex};
 use std::io::{self, Write};
-use hex;
 
 /// 钱包结构体 - 管理用户的密钥对和地址
 #[derive(Debug, Clone)]
@@ -28,7 +30,7 @@ impl Wallet {
 
     /// 从私钥恢复钱包（简化版）
     pub fn from_private_key(name: String, private_key_hex: &str) -> Result<Self, String> {
-        let private_key_bytes = hex::decode(private_key_hex)
+        hex::decode(private_key_hex)
             .map_err(|_| "无效的私钥格式".to_string())?;
 
         // 简化版：实际应该使用私钥重新生成密钥对
@@ -53,6 +55,50 @@ impl Wallet {
     }
 }
 
+/// 验证者密钥 - 用于 PoS/DPoS 验证者签名区块头
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidatorKey {
+    pub address: String,
+    pkcs8: String, // PKCS#8 编码的私钥（十六进制字符串）
+}
+
+impl ValidatorKey {
+    /// 为验证者生成新的签名密钥
+    pub fn generate(address: String) -> Self {
+        let rng = SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        Self {
+            address,
+            pkcs8: hex::encode(pkcs8.as_ref()),
+        }
+    }
+
+    /// 获取签名密钥对
+    pub fn keypair(&self) -> Ed25519KeyPair {
+        Ed25519KeyPair::from_pkcs8(&hex::decode(&self.pkcs8).unwrap()).unwrap()
+    }
+
+    /// 保存验证者密钥到文件
+    pub fn save_to_file(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(filename, json)?;
+        Ok(())
+    }
+
+    /// 从文件加载验证者密钥（私钥无效时返回错误）
+    pub fn load_from_file(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = fs::read_to_string(filename)?;
+        let key: ValidatorKey = serde_json::from_str(&json)?;
+        Ed25519KeyPair::from_pkcs8(&hex::decode(&key.pkcs8)?).map_err(|_| "无效的验证者私钥")?;
+        Ok(key)
+    }
+
+    /// 获取公钥（十六进制字符串）
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.keypair().public_key().as_ref())
+    }
+}
+
 /// 钱包管理器 - 管理多个钱包
 pub struct WalletManager {
     wallets: Arc<Mutex<HashMap<String, Wallet>>>,
@@ -75,7 +121,7 @@ impl WalletManager {
         }
 
         let wallet = Wallet::new(name.clone());
-        let public_key = wallet.public_key.clone();
+        let public_key = wallet.get_public_key().to_string();
 
         wallets.insert(name.clone(), wallet);
 
@@ -95,7 +141,7 @@ impl WalletManager {
         }
 
         let wallet = Wallet::from_private_key(name.clone(), &private_key_hex)?;
-        let public_key = wallet.public_key.clone();
+        let public_key = wallet.get_public_key().to_string();
 
         wallets.insert(name.clone(), wallet);
 
@@ -138,7 +184,7 @@ impl WalletManager {
     }
 }
 
-/// 钱包 CLI 功能
+// 钱包 CLI 功能
 
 /// 创建钱包 CLI
 pub fn create_wallet_cli(wallet_manager: &WalletManager) {
@@ -212,8 +258,8 @@ pub fn view_wallet_cli(wallet_manager: &WalletManager) {
     match wallet_manager.get_wallet(&name) {
         Some(wallet) => {
             println!("✅ 钱包信息:");
-            println!("📬 钱包地址: {}", wallet.address);
-            println!("🔑 公钥: {}", wallet.public_key);
+            println!("📬 钱包地址: {}", wallet.get_address());
+            println!("🔑 公钥: {}", wallet.get_public_key());
             println!("🔒 私钥哈希: {}", hex::encode(&wallet.encrypted_private_key[..8])); // 只显示前8字节
         }
         None => println!("❌ 未找到钱包 '{}'", name),
@@ -235,7 +281,7 @@ pub fn list_wallets_cli(wallet_manager: &WalletManager) {
     println!("找到 {} 个钱包:", wallets.len());
     for (i, wallet_name) in wallets.iter().enumerate() {
         if let Some(wallet) = wallet_manager.get_wallet(wallet_name) {
-            println!("{}. 📬 {} - 🔑 {}", i + 1, wallet.address, &wallet.public_key[..16]);
+            println!("{}. 📬 {} - 🔑 {}", i + 1, wallet.get_address(), &wallet.get_public_key()[..16]);
         }
     }
 }
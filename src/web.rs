@@ -1,14 +1,19 @@
 use crate::blockchain::Blockchain;
-use crate::block::{Block, Transaction};
+use crate::block::{Transaction, TransactionKind};
+use crate::consensus::{DelegatedProofOfStake, DelegationInfo, Evidence, StakingReward, UnbondingEntry};
+use crate::finality::VoteType;
+use crate::governance::{GovernanceParam, ParamChange, Proposal};
+use crate::miner::{self, MiningService, MiningStatus};
+use crate::monetary::SupplyAudit;
 use crate::wallet::WalletManager;
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
     response::{Html, Json},
     routing::{get, post},
     Router,
 };
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 use std::net::SocketAddr;
 use tower_http::cors::CorsLayer;
@@ -18,7 +23,9 @@ use tower_http::services::ServeDir;
 #[derive(Clone)]
 pub struct AppState {
     pub blockchain: Arc<Mutex<Blockchain>>,
+    #[allow(dead_code)]
     pub wallet_manager: Arc<WalletManager>,
+    pub mining_service: Arc<MiningService>,
 }
 
 /// API响应结构体
@@ -31,12 +38,15 @@ struct ApiResponse<T> {
 
 #[derive(Serialize)]
 struct BlockchainInfo {
+    chain_id: String,
+    genesis_hash: String,
     length: usize,
     total_transactions: usize,
     difficulty: u32,
     mining_reward: u64,
     is_valid: bool,
     consensus_type: String,
+    total_work: u128,
 }
 
 #[derive(Serialize)]
@@ -47,24 +57,47 @@ struct BlockInfo {
     timestamp: String,
     nonce: u64,
     difficulty: u32,
+    bits: String,
     transaction_count: usize,
+    finalized: bool,
     transactions: Vec<TransactionInfo>,
 }
 
+#[derive(Serialize)]
+struct FinalityInfo {
+    finalized_height: u64,
+    finalized_hash: Option<String>,
+    latest_height: u64,
+    total_weight: u64,
+    pending: Vec<PendingFinality>,
+}
+
+#[derive(Serialize)]
+struct PendingFinality {
+    height: u64,
+    block_hash: String,
+    prevote_weight: u64,
+    precommit_weight: u64,
+}
+
 #[derive(Serialize)]
 struct TransactionInfo {
     id: String,
     sender: String,
     receiver: String,
     amount: u64,
+    fee: u64,
     timestamp: String,
     has_signature: bool,
+    is_coinbase: bool,
 }
 
 #[derive(Serialize)]
 struct BalanceInfo {
     address: String,
     balance: u64,
+    spendable: u64, // 扣除未成熟 coinbase 奖励后的可花费余额
+    staking_rewards: u64,
 }
 
 #[derive(Deserialize)]
@@ -72,6 +105,8 @@ struct CreateTransactionRequest {
     sender: String,
     receiver: String,
     amount: u64,
+    #[serde(default)]
+    fee: u64,
 }
 
 #[derive(Deserialize)]
@@ -79,6 +114,84 @@ struct MineRequest {
     miner_address: String,
 }
 
+#[derive(Deserialize)]
+struct StartMiningRequest {
+    coinbase_address: Option<String>,
+    threads: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct CandidateInfo {
+    address: String,
+    own_stake: u64,
+    weight: u64,
+    jailed: bool,
+}
+
+#[derive(Serialize)]
+struct DelegationsInfo {
+    delegations: Vec<DelegationInfo>,
+    unbonding: Vec<UnbondingEntry>,
+}
+
+#[derive(Deserialize)]
+struct RegisterCandidateRequest {
+    candidate: String,
+    amount: u64,
+}
+
+#[derive(Deserialize)]
+struct DeregisterCandidateRequest {
+    candidate: String,
+}
+
+#[derive(Deserialize)]
+struct DelegateRequest {
+    delegator: String,
+    candidate: String,
+    amount: u64,
+}
+
+#[derive(Deserialize)]
+struct CommissionRequest {
+    candidate: String,
+    rate: u64,
+}
+
+#[derive(Deserialize)]
+struct EvidenceRequest {
+    reporter: String,
+    evidence: Evidence,
+}
+
+#[derive(Deserialize)]
+struct UnjailRequest {
+    validator: String,
+}
+
+#[derive(Deserialize)]
+struct RedelegateRequest {
+    delegator: String,
+    from: String,
+    to: String,
+    amount: u64,
+}
+
+#[derive(Deserialize)]
+struct ProposalRequest {
+    proposer: String,
+    param: GovernanceParam,
+    value: u64,
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct GovernanceVoteRequest {
+    voter: String,
+    proposal_id: String,
+    approve: bool,
+}
+
 /// 获取区块链信息
 async fn get_blockchain_info(
     State(state): State<AppState>,
@@ -86,12 +199,15 @@ async fn get_blockchain_info(
     let blockchain = state.blockchain.lock().unwrap();
 
     let info = BlockchainInfo {
+        chain_id: blockchain.chain_params.chain_id.clone(),
+        genesis_hash: blockchain.chain[0].header.hash.clone(),
         length: blockchain.get_length(),
         total_transactions: blockchain.get_total_transactions(),
         difficulty: blockchain.difficulty,
         mining_reward: blockchain.mining_reward,
         is_valid: blockchain.is_chain_valid(),
         consensus_type: format!("{:?}", blockchain.consensus_type),
+        total_work: Blockchain::total_work(&blockchain.chain),
     };
 
     Json(ApiResponse {
@@ -114,14 +230,18 @@ async fn get_blocks(State(state): State<AppState>) -> Json<ApiResponse<Vec<Block
             timestamp: block.header.timestamp.to_rfc3339(),
             nonce: block.header.nonce,
             difficulty: block.header.difficulty,
+            bits: format!("{:08x}", block.header.bits),
             transaction_count: block.transactions.len(),
+            finalized: blockchain.finality.is_finalized(block.height),
             transactions: block.transactions.iter().map(|tx| TransactionInfo {
                 id: tx.id.clone(),
                 sender: tx.sender.clone(),
                 receiver: tx.receiver.clone(),
                 amount: tx.amount,
+                fee: tx.fee,
                 timestamp: tx.timestamp.to_rfc3339(),
                 has_signature: tx.signature.is_some(),
+                is_coinbase: tx.is_coinbase(),
             }).collect(),
         };
         blocks.push(block_info);
@@ -157,14 +277,18 @@ async fn get_block(
         timestamp: block.header.timestamp.to_rfc3339(),
         nonce: block.header.nonce,
         difficulty: block.header.difficulty,
+        bits: format!("{:08x}", block.header.bits),
         transaction_count: block.transactions.len(),
+        finalized: blockchain.finality.is_finalized(block.height),
         transactions: block.transactions.iter().map(|tx| TransactionInfo {
             id: tx.id.clone(),
             sender: tx.sender.clone(),
             receiver: tx.receiver.clone(),
             amount: tx.amount,
+            fee: tx.fee,
             timestamp: tx.timestamp.to_rfc3339(),
             has_signature: tx.signature.is_some(),
+            is_coinbase: tx.is_coinbase(),
         }).collect(),
     };
 
@@ -182,10 +306,14 @@ async fn get_balance(
 ) -> Json<ApiResponse<BalanceInfo>> {
     let blockchain = state.blockchain.lock().unwrap();
     let balance = blockchain.get_balance(&address);
+    let spendable = blockchain.spendable_balance(&address);
+    let staking_rewards = blockchain.get_total_staking_rewards(&address);
 
     let balance_info = BalanceInfo {
         address,
         balance,
+        spendable,
+        staking_rewards,
     };
 
     Json(ApiResponse {
@@ -195,6 +323,65 @@ async fn get_balance(
     })
 }
 
+/// 获取地址的质押奖励历史
+async fn get_staking_rewards(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> Json<ApiResponse<Vec<StakingReward>>> {
+    let blockchain = state.blockchain.lock().unwrap();
+    let rewards = blockchain.get_staking_rewards(&address).to_vec();
+
+    Json(ApiResponse {
+        success: true,
+        data: Some(rewards),
+        error: None,
+    })
+}
+
+/// 获取供应审计结果
+async fn get_supply(State(state): State<AppState>) -> Json<ApiResponse<SupplyAudit>> {
+    let blockchain = state.blockchain.lock().unwrap();
+
+    Json(ApiResponse {
+        success: true,
+        data: Some(blockchain.supply_audit()),
+        error: None,
+    })
+}
+
+/// 获取最终性状态
+async fn get_finality(State(state): State<AppState>) -> Json<ApiResponse<FinalityInfo>> {
+    let blockchain = state.blockchain.lock().unwrap();
+    let validators = blockchain.validator_weights();
+    let finality = &blockchain.finality;
+
+    // 统计本地链上尚未最终确认区块的投票进度
+    let pending = blockchain.chain
+        .iter()
+        .skip(finality.finalized_height as usize + 1)
+        .map(|block| PendingFinality {
+            height: block.height,
+            block_hash: block.header.hash.clone(),
+            prevote_weight: finality.tally(block.height, VoteType::Prevote, &block.header.hash, &validators),
+            precommit_weight: finality.tally(block.height, VoteType::Precommit, &block.header.hash, &validators),
+        })
+        .collect();
+
+    let info = FinalityInfo {
+        finalized_height: finality.finalized_height,
+        finalized_hash: finality.finalized_hash.clone(),
+        latest_height: blockchain.get_latest_block().height,
+        total_weight: validators.values().map(|v| v.weight).sum(),
+        pending,
+    };
+
+    Json(ApiResponse {
+        success: true,
+        data: Some(info),
+        error: None,
+    })
+}
+
 /// 获取待处理交易
 async fn get_pending_transactions(
     State(state): State<AppState>,
@@ -206,8 +393,10 @@ async fn get_pending_transactions(
         sender: tx.sender.clone(),
         receiver: tx.receiver.clone(),
         amount: tx.amount,
+        fee: tx.fee,
         timestamp: tx.timestamp.to_rfc3339(),
         has_signature: tx.signature.is_some(),
+        is_coinbase: tx.is_coinbase(),
     }).collect();
 
     Json(ApiResponse {
@@ -228,7 +417,8 @@ async fn create_transaction(
         request.sender,
         request.receiver,
         request.amount,
-    );
+    )
+    .with_fee(request.fee);
 
     match blockchain.add_transaction(transaction) {
         Ok(_) => Json(ApiResponse {
@@ -249,12 +439,118 @@ async fn mine_block(
     State(state): State<AppState>,
     Json(request): Json<MineRequest>,
 ) -> Json<ApiResponse<String>> {
+    // 挖矿在阻塞线程池中进行，不持有区块链锁，避免阻塞其他请求
+    let blockchain = state.blockchain.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        miner::mine_block(&blockchain, request.miner_address, miner::default_threads(), &AtomicBool::new(false))
+    })
+    .await
+    .unwrap_or_else(|e| Err(format!("挖矿线程异常: {}", e)));
+
+    match result {
+        Ok((block, stats)) => Json(ApiResponse {
+            success: true,
+            data: Some(format!("挖矿成功: 区块 {}，算力 {:.0} H/s", block.height, stats.hashrate())),
+            error: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(e),
+        }),
+    }
+}
+
+/// 获取挖矿服务状态
+async fn get_mining_status(State(state): State<AppState>) -> Json<ApiResponse<MiningStatus>> {
+    Json(ApiResponse {
+        success: true,
+        data: Some(state.mining_service.status()),
+        error: None,
+    })
+}
+
+/// 启动挖矿服务
+async fn start_mining(
+    State(state): State<AppState>,
+    Json(request): Json<StartMiningRequest>,
+) -> Json<ApiResponse<MiningStatus>> {
+    let service = &state.mining_service;
+    let result = request.coinbase_address
+        .map_or(Ok(()), |address| service.set_coinbase_address(address))
+        .and_then(|_| service.start(request.threads.unwrap_or_else(miner::default_threads)));
+
+    match result {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some(service.status()),
+            error: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(e),
+        }),
+    }
+}
+
+/// 停止挖矿服务
+async fn stop_mining(State(state): State<AppState>) -> Json<ApiResponse<String>> {
+    match state.mining_service.stop() {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some("已请求停止挖矿服务".to_string()),
+            error: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(e),
+        }),
+    }
+}
+
+/// 获取治理提案列表
+async fn get_proposals(State(state): State<AppState>) -> Json<ApiResponse<Vec<Proposal>>> {
+    let blockchain = state.blockchain.lock().unwrap();
+    let proposals = blockchain.governance.proposals.values().cloned().collect();
+
+    Json(ApiResponse {
+        success: true,
+        data: Some(proposals),
+        error: None,
+    })
+}
+
+/// 获取单个治理提案
+async fn get_proposal(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<ApiResponse<Proposal>> {
+    let blockchain = state.blockchain.lock().unwrap();
+
+    match blockchain.governance.proposals.get(&id) {
+        Some(proposal) => Json(ApiResponse {
+            success: true,
+            data: Some(proposal.clone()),
+            error: None,
+        }),
+        None => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some("提案不存在".to_string()),
+        }),
+    }
+}
+
+/// 提交本地创建的交易（治理、质押）到交易池
+fn submit_transaction(state: &AppState, transaction: Transaction, message: &str) -> Json<ApiResponse<String>> {
     let mut blockchain = state.blockchain.lock().unwrap();
 
-    match blockchain.mine_pending_transactions(request.miner_address) {
+    match blockchain.add_transaction(transaction) {
         Ok(_) => Json(ApiResponse {
             success: true,
-            data: Some("挖矿成功".to_string()),
+            data: Some(message.to_string()),
             error: None,
         }),
         Err(e) => Json(ApiResponse {
@@ -265,6 +561,145 @@ async fn mine_block(
     }
 }
 
+/// 发起治理提案
+async fn create_proposal(
+    State(state): State<AppState>,
+    Json(request): Json<ProposalRequest>,
+) -> Json<ApiResponse<String>> {
+    let change = ParamChange { param: request.param, value: request.value };
+    let transaction = Transaction::new_proposal(request.proposer, change, request.description);
+    submit_transaction(&state, transaction, "提案交易已加入交易池")
+}
+
+/// 对治理提案投票
+async fn vote_proposal(
+    State(state): State<AppState>,
+    Json(request): Json<GovernanceVoteRequest>,
+) -> Json<ApiResponse<String>> {
+    let transaction = Transaction::new_vote(request.voter, request.proposal_id, request.approve);
+    submit_transaction(&state, transaction, "投票交易已加入交易池")
+}
+
+/// 在 DPoS 共识上执行操作并包装为 API 响应
+fn with_dpos<T>(
+    state: &AppState,
+    op: impl FnOnce(&mut DelegatedProofOfStake, u64) -> Result<T, String>,
+) -> Json<ApiResponse<T>> {
+    let mut blockchain = state.blockchain.lock().unwrap();
+    let height = blockchain.get_length() as u64;
+
+    let result = match blockchain.dpos_consensus.as_mut() {
+        Some(dpos) => op(dpos, height),
+        None => Err("当前未使用 DPoS 共识算法".to_string()),
+    };
+
+    match result {
+        Ok(data) => Json(ApiResponse {
+            success: true,
+            data: Some(data),
+            error: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(e),
+        }),
+    }
+}
+
+/// 获取 DPoS 候选人列表
+async fn get_candidates(State(state): State<AppState>) -> Json<ApiResponse<Vec<CandidateInfo>>> {
+    with_dpos(&state, |dpos, height| {
+        Ok(dpos.candidates.iter().map(|candidate| CandidateInfo {
+            address: candidate.clone(),
+            own_stake: dpos.stakes.get(candidate).map(|s| s.amount).unwrap_or(0),
+            weight: dpos.calculate_candidate_weight(candidate),
+            jailed: dpos.slashing.is_jailed(candidate, height),
+        }).collect())
+    })
+}
+
+/// 注册 DPoS 候选人
+async fn register_candidate(
+    State(state): State<AppState>,
+    Json(request): Json<RegisterCandidateRequest>,
+) -> Json<ApiResponse<String>> {
+    let transaction = Transaction::new_staking(request.candidate, TransactionKind::RegisterCandidate, request.amount);
+    submit_transaction(&state, transaction, "注册交易已加入交易池")
+}
+
+/// 注销 DPoS 候选人
+async fn deregister_candidate(
+    State(state): State<AppState>,
+    Json(request): Json<DeregisterCandidateRequest>,
+) -> Json<ApiResponse<String>> {
+    let transaction = Transaction::new_staking(request.candidate, TransactionKind::DeregisterCandidate, 0);
+    submit_transaction(&state, transaction, "注销交易已加入交易池，打包后质押和委托进入解绑期")
+}
+
+/// 获取委托和解绑队列
+async fn get_delegations(State(state): State<AppState>) -> Json<ApiResponse<DelegationsInfo>> {
+    with_dpos(&state, |dpos, _| {
+        Ok(DelegationsInfo {
+            delegations: dpos.delegations.values().cloned().collect(),
+            unbonding: dpos.unbonding.clone(),
+        })
+    })
+}
+
+/// 委托投票
+async fn delegate(
+    State(state): State<AppState>,
+    Json(request): Json<DelegateRequest>,
+) -> Json<ApiResponse<String>> {
+    let kind = TransactionKind::Delegate { candidate: request.candidate };
+    submit_transaction(&state, Transaction::new_staking(request.delegator, kind, request.amount), "委托交易已加入交易池")
+}
+
+/// 撤销委托
+async fn undelegate(
+    State(state): State<AppState>,
+    Json(request): Json<DelegateRequest>,
+) -> Json<ApiResponse<String>> {
+    let kind = TransactionKind::Undelegate { candidate: request.candidate, amount: request.amount };
+    submit_transaction(&state, Transaction::new_staking(request.delegator, kind, 0), "撤销委托交易已加入交易池，打包后进入解绑期")
+}
+
+/// 转移委托
+async fn redelegate(
+    State(state): State<AppState>,
+    Json(request): Json<RedelegateRequest>,
+) -> Json<ApiResponse<String>> {
+    let kind = TransactionKind::Redelegate { from: request.from, to: request.to, amount: request.amount };
+    submit_transaction(&state, Transaction::new_staking(request.delegator, kind, 0), "转移委托交易已加入交易池")
+}
+
+/// 设置候选人佣金比例
+async fn set_commission(
+    State(state): State<AppState>,
+    Json(request): Json<CommissionRequest>,
+) -> Json<ApiResponse<String>> {
+    let kind = TransactionKind::SetCommission { rate: request.rate };
+    submit_transaction(&state, Transaction::new_staking(request.candidate, kind, 0), "佣金设置交易已加入交易池")
+}
+
+/// 提交验证者作恶证据
+async fn submit_evidence(
+    State(state): State<AppState>,
+    Json(request): Json<EvidenceRequest>,
+) -> Json<ApiResponse<String>> {
+    let kind = TransactionKind::Evidence { evidence: request.evidence };
+    submit_transaction(&state, Transaction::new_staking(request.reporter, kind, 0), "证据交易已加入交易池")
+}
+
+/// 监禁期满的验证者解除监禁
+async fn unjail(
+    State(state): State<AppState>,
+    Json(request): Json<UnjailRequest>,
+) -> Json<ApiResponse<String>> {
+    submit_transaction(&state, Transaction::new_staking(request.validator, TransactionKind::Unjail, 0), "解除监禁交易已加入交易池")
+}
+
 async fn index() -> Html<&'static str> {
     Html("<!DOCTYPE html>
 <html><head><title>Rust区块链系统</title></head>
@@ -344,11 +779,13 @@ setInterval(loadStats, 5000);
 pub async fn start_web_server(
     blockchain: Arc<Mutex<Blockchain>>,
     wallet_manager: Arc<WalletManager>,
+    mining_service: Arc<MiningService>,
     port: u16,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let state = AppState {
         blockchain,
         wallet_manager,
+        mining_service,
     };
 
     // 创建路由
@@ -358,10 +795,28 @@ pub async fn start_web_server(
         .route("/api/blockchain/info", get(get_blockchain_info))
         .route("/api/blocks", get(get_blocks))
         .route("/api/blocks/:height", get(get_block))
+        .route("/api/finality", get(get_finality))
         .route("/api/balance/:address", get(get_balance))
+        .route("/api/supply", get(get_supply))
+        .route("/api/staking-rewards/:address", get(get_staking_rewards))
         .route("/api/pending-transactions", get(get_pending_transactions))
         .route("/api/transactions", post(create_transaction))
         .route("/api/mine", post(mine_block))
+        .route("/api/mining/status", get(get_mining_status))
+        .route("/api/mining/start", post(start_mining))
+        .route("/api/mining/stop", post(stop_mining))
+        .route("/api/dpos/candidates", get(get_candidates).post(register_candidate))
+        .route("/api/dpos/candidates/deregister", post(deregister_candidate))
+        .route("/api/dpos/delegations", get(get_delegations))
+        .route("/api/dpos/delegate", post(delegate))
+        .route("/api/dpos/undelegate", post(undelegate))
+        .route("/api/dpos/redelegate", post(redelegate))
+        .route("/api/dpos/commission", post(set_commission))
+        .route("/api/slashing/evidence", post(submit_evidence))
+        .route("/api/slashing/unjail", post(unjail))
+        .route("/api/governance/proposals", get(get_proposals).post(create_proposal))
+        .route("/api/governance/proposals/:id", get(get_proposal))
+        .route("/api/governance/votes", post(vote_proposal))
         .layer(CorsLayer::permissive())
         .with_state(state);
 